@@ -0,0 +1,4 @@
+// re-embed the migrations whenever one is added or changed
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
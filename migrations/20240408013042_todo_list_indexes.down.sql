@@ -0,0 +1,5 @@
+DROP INDEX todo_labels_label_id_idx;
+
+DROP INDEX todo_labels_todo_id_idx;
+
+DROP INDEX todos_completed_idx;
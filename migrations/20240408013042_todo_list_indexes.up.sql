@@ -0,0 +1,5 @@
+CREATE INDEX todos_completed_idx ON todos (completed);
+
+CREATE INDEX todo_labels_todo_id_idx ON todo_labels (todo_id);
+
+CREATE INDEX todo_labels_label_id_idx ON todo_labels (label_id);
@@ -0,0 +1,5 @@
+DROP INDEX todos_parent_id_idx;
+
+ALTER TABLE todos DROP COLUMN auto_complete;
+
+ALTER TABLE todos DROP COLUMN parent_id;
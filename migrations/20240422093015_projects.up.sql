@@ -0,0 +1,14 @@
+CREATE TABLE projects
+(
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL
+);
+
+-- the default project, which also takes every todo created before projects existed
+INSERT INTO projects ( id, name ) VALUES ( 1, 'Inbox' );
+SELECT setval('projects_id_seq', 1);
+
+ALTER TABLE todos ADD COLUMN project_id INTEGER NOT NULL DEFAULT 1 REFERENCES projects (id);
+ALTER TABLE todos ALTER COLUMN project_id DROP DEFAULT;
+
+CREATE INDEX todos_project_id_idx ON todos (project_id);
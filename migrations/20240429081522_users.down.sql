@@ -0,0 +1,8 @@
+ALTER TABLE projects DROP CONSTRAINT projects_owner_id_check;
+ALTER TABLE projects DROP COLUMN owner_id;
+ALTER TABLE labels DROP COLUMN owner_id;
+ALTER TABLE todos DROP COLUMN owner_id;
+
+DROP TABLE sessions;
+
+DROP TABLE users;
@@ -0,0 +1,42 @@
+CREATE TABLE users
+(
+    id SERIAL PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE sessions
+(
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+-- rows created before accounts existed go to a placeholder owner nobody can sign in as,
+-- until `my_todo claim <username>` hands them to a real account; its name is shorter
+-- than any name registration accepts and its hash never verifies
+INSERT INTO users ( username, password_hash )
+SELECT '~', '!'
+WHERE EXISTS ( SELECT 1 FROM todos )
+OR EXISTS ( SELECT 1 FROM labels )
+OR EXISTS ( SELECT 1 FROM projects WHERE id <> 1 );
+
+ALTER TABLE todos ADD COLUMN owner_id INTEGER REFERENCES users (id);
+ALTER TABLE labels ADD COLUMN owner_id INTEGER REFERENCES users (id);
+ALTER TABLE projects ADD COLUMN owner_id INTEGER REFERENCES users (id);
+
+UPDATE todos SET owner_id = ( SELECT id FROM users WHERE username = '~' );
+UPDATE labels SET owner_id = ( SELECT id FROM users WHERE username = '~' );
+UPDATE projects SET owner_id = ( SELECT id FROM users WHERE username = '~' ) WHERE id <> 1;
+
+-- the default project alone has no owner, every user shares it
+ALTER TABLE todos ALTER COLUMN owner_id SET NOT NULL;
+ALTER TABLE labels ALTER COLUMN owner_id SET NOT NULL;
+ALTER TABLE projects ADD CONSTRAINT projects_owner_id_check CHECK ( owner_id IS NOT NULL OR id = 1 );
+
+CREATE INDEX todos_owner_id_idx ON todos (owner_id);
+CREATE INDEX labels_owner_id_idx ON labels (owner_id);
+CREATE INDEX projects_owner_id_idx ON projects (owner_id);
@@ -0,0 +1,8 @@
+ALTER TABLE todo_labels
+    DROP CONSTRAINT todo_labels_todo_id_label_id_key,
+    DROP CONSTRAINT todo_labels_todo_id_fkey,
+    DROP CONSTRAINT todo_labels_label_id_fkey,
+    ADD CONSTRAINT todo_labels_todo_id_fkey FOREIGN KEY (todo_id) REFERENCES todos (id) DEFERRABLE INITIALLY DEFERRED,
+    ADD CONSTRAINT todo_labels_label_id_fkey FOREIGN KEY (label_id) REFERENCES labels (id) DEFERRABLE INITIALLY DEFERRED;
+
+DROP INDEX labels_owner_id_name_key;
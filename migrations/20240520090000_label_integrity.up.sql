@@ -0,0 +1,33 @@
+-- labels whose names only differ in case are merged into the oldest of them
+WITH canonical AS (
+    SELECT id, min(id) OVER ( PARTITION BY owner_id, lower(name) ) AS keep_id FROM labels
+)
+UPDATE todo_labels SET label_id = canonical.keep_id
+FROM canonical
+WHERE todo_labels.label_id = canonical.id AND canonical.id <> canonical.keep_id;
+
+DELETE FROM labels
+WHERE EXISTS (
+    SELECT 1 FROM labels keep
+    WHERE keep.owner_id IS NOT DISTINCT FROM labels.owner_id
+    AND lower(keep.name) = lower(labels.name)
+    AND keep.id < labels.id
+);
+
+-- a pair stored more than once keeps its first row
+DELETE FROM todo_labels
+WHERE EXISTS (
+    SELECT 1 FROM todo_labels earlier
+    WHERE earlier.todo_id = todo_labels.todo_id
+    AND earlier.label_id = todo_labels.label_id
+    AND earlier.id < todo_labels.id
+);
+
+CREATE UNIQUE INDEX labels_owner_id_name_key ON labels (owner_id, lower(name));
+
+ALTER TABLE todo_labels
+    DROP CONSTRAINT todo_labels_todo_id_fkey,
+    DROP CONSTRAINT todo_labels_label_id_fkey,
+    ADD CONSTRAINT todo_labels_todo_id_fkey FOREIGN KEY (todo_id) REFERENCES todos (id) ON DELETE CASCADE,
+    ADD CONSTRAINT todo_labels_label_id_fkey FOREIGN KEY (label_id) REFERENCES labels (id) ON DELETE CASCADE,
+    ADD CONSTRAINT todo_labels_todo_id_label_id_key UNIQUE (todo_id, label_id);
@@ -0,0 +1,13 @@
+-- manual order of the todos of one owner in one project, spaced 65536 apart so a move
+-- usually finds room between its new neighbours; existing todos keep their creation order
+ALTER TABLE todos ADD COLUMN position BIGINT NOT NULL DEFAULT 0;
+
+UPDATE todos SET position = ranked.n * 65536
+FROM (
+    SELECT id, row_number() OVER ( PARTITION BY owner_id, project_id ORDER BY id ) AS n FROM todos
+) ranked
+WHERE todos.id = ranked.id;
+
+ALTER TABLE todos ALTER COLUMN position DROP DEFAULT;
+
+CREATE INDEX todos_position_idx ON todos (owner_id, project_id, position);
@@ -0,0 +1,7 @@
+DROP TABLE todo_labels;
+DROP TABLE todos;
+DROP TABLE labels;
+DROP TABLE projects;
+DROP TABLE api_tokens;
+DROP TABLE sessions;
+DROP TABLE users;
@@ -0,0 +1,84 @@
+-- the schema of every Postgres migration up to 20240506090000_api_tokens in one go;
+-- timestamps are RFC 3339 text in UTC, which sorts chronologically
+CREATE TABLE users
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT ( strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now') )
+);
+
+CREATE TABLE sessions
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL DEFAULT ( strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now') ),
+    expires_at TEXT NOT NULL
+);
+
+CREATE TABLE api_tokens
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    -- comma separated
+    scopes TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT ( strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now') ),
+    last_used_at TEXT,
+    -- NULL never expires
+    expires_at TEXT
+);
+
+CREATE INDEX api_tokens_user_id_idx ON api_tokens (user_id);
+
+CREATE TABLE projects
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    -- the default project alone has no owner
+    owner_id INTEGER REFERENCES users (id) CHECK ( owner_id IS NOT NULL OR id = 1 )
+);
+
+-- the default project, shared by every user
+INSERT INTO projects ( id, name ) VALUES ( 1, 'Inbox' );
+
+CREATE INDEX projects_owner_id_idx ON projects (owner_id);
+
+CREATE TABLE labels
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    owner_id INTEGER NOT NULL REFERENCES users (id)
+);
+
+CREATE INDEX labels_owner_id_idx ON labels (owner_id);
+
+CREATE TABLE todos
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    text TEXT NOT NULL,
+    completed BOOLEAN NOT NULL DEFAULT false,
+    due_at TEXT,
+    parent_id INTEGER REFERENCES todos (id),
+    auto_complete BOOLEAN NOT NULL DEFAULT false,
+    project_id INTEGER NOT NULL REFERENCES projects (id),
+    owner_id INTEGER NOT NULL REFERENCES users (id)
+);
+
+CREATE INDEX todos_due_at_idx ON todos (due_at) WHERE completed = false;
+CREATE INDEX todos_completed_idx ON todos (completed);
+CREATE INDEX todos_parent_id_idx ON todos (parent_id);
+CREATE INDEX todos_project_id_idx ON todos (project_id);
+CREATE INDEX todos_owner_id_idx ON todos (owner_id);
+
+CREATE TABLE todo_labels
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    todo_id INTEGER NOT NULL REFERENCES todos (id) DEFERRABLE INITIALLY DEFERRED,
+    label_id INTEGER NOT NULL REFERENCES labels (id) DEFERRABLE INITIALLY DEFERRED
+);
+
+CREATE INDEX todo_labels_todo_id_idx ON todo_labels (todo_id);
+CREATE INDEX todo_labels_label_id_idx ON todo_labels (label_id);
@@ -0,0 +1,16 @@
+CREATE TABLE todo_labels_old
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    todo_id INTEGER NOT NULL REFERENCES todos (id) DEFERRABLE INITIALLY DEFERRED,
+    label_id INTEGER NOT NULL REFERENCES labels (id) DEFERRABLE INITIALLY DEFERRED
+);
+
+INSERT INTO todo_labels_old ( id, todo_id, label_id ) SELECT id, todo_id, label_id FROM todo_labels;
+
+DROP TABLE todo_labels;
+ALTER TABLE todo_labels_old RENAME TO todo_labels;
+
+CREATE INDEX todo_labels_todo_id_idx ON todo_labels (todo_id);
+CREATE INDEX todo_labels_label_id_idx ON todo_labels (label_id);
+
+DROP INDEX labels_owner_id_name_key;
@@ -0,0 +1,36 @@
+-- labels whose names only differ in case are merged into the oldest of them;
+-- lower() folds ASCII letters only, so that is the case the index ignores
+UPDATE todo_labels SET label_id = (
+    SELECT min(keep.id) FROM labels keep
+    JOIN labels merged ON keep.owner_id IS merged.owner_id AND lower(keep.name) = lower(merged.name)
+    WHERE merged.id = todo_labels.label_id
+);
+
+DELETE FROM labels
+WHERE EXISTS (
+    SELECT 1 FROM labels keep
+    WHERE keep.owner_id IS labels.owner_id
+    AND lower(keep.name) = lower(labels.name)
+    AND keep.id < labels.id
+);
+
+CREATE UNIQUE INDEX labels_owner_id_name_key ON labels (owner_id, lower(name));
+
+-- SQLite can not change the constraints of a table, so it is rebuilt;
+-- a pair stored more than once keeps its first row
+CREATE TABLE todo_labels_new
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    todo_id INTEGER NOT NULL REFERENCES todos (id) ON DELETE CASCADE,
+    label_id INTEGER NOT NULL REFERENCES labels (id) ON DELETE CASCADE,
+    UNIQUE (todo_id, label_id)
+);
+
+INSERT INTO todo_labels_new ( id, todo_id, label_id )
+SELECT min(id), todo_id, label_id FROM todo_labels GROUP BY todo_id, label_id;
+
+DROP TABLE todo_labels;
+ALTER TABLE todo_labels_new RENAME TO todo_labels;
+
+CREATE INDEX todo_labels_todo_id_idx ON todo_labels (todo_id);
+CREATE INDEX todo_labels_label_id_idx ON todo_labels (label_id);
@@ -1,11 +1,13 @@
 import { useEffect, useState, FC } from "react";
 import 'modern-css-reset';
 import { ThemeProvider, createTheme } from '@mui/material/styles'
-import { Label, NewLabelPayload, NewTodoPayload, Todo, UpdateTodoPayload } from "./types/todo";
-import { Box, Typography, Stack } from "@mui/material";
+import { Label, NewLabelPayload, NewTodoPayload, Project, Todo, UpdateTodoPayload } from "./types/todo";
+import { Credentials } from "./types/user";
+import { Box, Button, Typography, Stack } from "@mui/material";
 import TodoForm from "./components/TodoForm";
 import TodoList from "./components/TodoList";
 import SideNav from "./components/SideNav";
+import LoginForm from "./components/LoginForm";
 import {
   addTodoItem,
   getTodoItem,
@@ -15,48 +17,81 @@ import {
 import {
   addLabelItem, deleteLabelItem, getLabelItems
 } from "./lib/api/label";
+import { DEFAULT_PROJECT_ID, getProjectItems } from "./lib/api/project";
+import { getSessionToken, UnauthorizedError } from "./lib/api/client";
+import { login, logout, register } from "./lib/api/user";
 
+type TodoAppProps = {
+  onSignOut: () => void
+}
 
-const TodoApp: FC = () => {
+const TodoApp: FC<TodoAppProps> = ({ onSignOut }) => {
   const [todos, setTodos] = useState<Todo[]>([])
   const [labels, setLabels] = useState<Label[]>([])
   const [filterLabelId, setFilterLabelId] = useState<number | null>(null)
+  const [projects, setProjects] = useState<Project[]>([])
+  const [projectId, setProjectId] = useState(DEFAULT_PROJECT_ID)
+
+  // an expired session sends the user back to the sign in form
+  const whileSignedIn = async (work: () => Promise<void>) => {
+    try {
+      await work()
+    } catch (e) {
+      if (e instanceof UnauthorizedError) {
+        onSignOut()
+        return
+      }
+      throw e
+    }
+  }
 
-  const onSubmit = async (payload: NewTodoPayload) => {
+  const onSubmit = (payload: NewTodoPayload) => whileSignedIn(async () => {
     if (!payload.text) return
 
-    await addTodoItem(payload)
-    const todos = await getTodoItem()
+    await addTodoItem({ ...payload, project_id: projectId })
+    const todos = await getTodoItem(projectId)
     setTodos(todos)
-  }
+  })
 
-  const onUpdate = async (updateTodo: UpdateTodoPayload) => {
+  const onUpdate = (updateTodo: UpdateTodoPayload) => whileSignedIn(async () => {
     await updateTodoItem(updateTodo)
-    const todos = await getTodoItem()
+    const todos = await getTodoItem(projectId)
     setTodos(todos)
-  }
+  })
 
-  const onDelete = async (id: number) => {
+  const onDelete = (id: number) => whileSignedIn(async () => {
     await deleteTodoItem(id)
-    const todos = await getTodoItem()
+    const todos = await getTodoItem(projectId)
     setTodos(todos)
+  })
+
+  const onLogout = async () => {
+    try {
+      await logout()
+    } finally {
+      onSignOut()
+    }
+  }
+
+  const onSelectProject = (project: Project) => {
+    setProjectId(project.id)
   }
 
   const onSelectLabel = (label: Label | null) => {
     setFilterLabelId(label?.id ?? null)
   }
 
-  const onSubmitNewLabel = async (newLabel: NewLabelPayload) => {
+  const onSubmitNewLabel = (newLabel: NewLabelPayload) => whileSignedIn(async () => {
     if (!labels.some((label) => label.name === newLabel.name)) {
       const res = await addLabelItem(newLabel)
       setLabels([...labels, res])
     }
-  }
+  })
 
-  const onDeleteLabel = async (id: number) => {
+  const onDeleteLabel = (id: number) => whileSignedIn(async () => {
     await deleteLabelItem(id)
     setLabels((prev) => prev.filter((label) => label.id !== id))
-  }
+  })
 
   const dispTodo = filterLabelId 
     ? todos.filter((todo) => 
@@ -65,14 +100,23 @@ const TodoApp: FC = () => {
     : todos
 
   useEffect(() => {
-    (async () => {
-      const todos = await getTodoItem()
-      setTodos(todos)
+    whileSignedIn(async () => {
       const labelResponse = await getLabelItems()
       setLabels(labelResponse)
-    })()
+      const projectResponse = await getProjectItems()
+      setProjects(projectResponse)
+    })
+    // eslint-disable-next-line react-hooks/exhaustive-deps
   }, [])
 
+  useEffect(() => {
+    whileSignedIn(async () => {
+      const todos = await getTodoItem(projectId)
+      setTodos(todos)
+    })
+    // eslint-disable-next-line react-hooks/exhaustive-deps
+  }, [projectId])
+
   return (
     <>
       <Box
@@ -89,7 +133,8 @@ const TodoApp: FC = () => {
           zIndex: 3,
         }}
       >
-        <Typography variant="h1">Todo App</Typography>
+        <Typography variant="h1" sx={{ flexGrow: 1 }}>Todo App</Typography>
+        <Button onClick={onLogout}>sign out</Button>
       </Box>
       <Box
         sx={{
@@ -103,6 +148,9 @@ const TodoApp: FC = () => {
         }}
       >
         <SideNav
+          projects={projects}
+          projectId={projectId}
+          onSelectProject={onSelectProject}
           labels={labels}
           onSelectLabel={onSelectLabel}
           filterLabelId={filterLabelId}
@@ -149,9 +197,29 @@ const theme = createTheme({
 })
 
 const App: FC = () => {
+  const [signedIn, setSignedIn] = useState(getSessionToken() !== null)
+
+  const onLogin = async (credentials: Credentials) => {
+    await login(credentials)
+    setSignedIn(true)
+  }
+
+  const onRegister = async (credentials: Credentials) => {
+    await register(credentials)
+    await onLogin(credentials)
+  }
+
   return (
     <ThemeProvider theme={theme}>
-      <TodoApp/>
+      {signedIn
+        ? <TodoApp onSignOut={() => setSignedIn(false)} />
+        : (
+          <Box sx={{ display: 'flex', justifyContent: 'center', p: 5 }}>
+            <Box maxWidth={400} width="100%">
+              <LoginForm onLogin={onLogin} onRegister={onRegister} />
+            </Box>
+          </Box>
+        )}
     </ThemeProvider>
   )
 }
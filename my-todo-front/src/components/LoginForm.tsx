@@ -0,0 +1,68 @@
+import { FC, useState } from "react"
+import {
+    Box,
+    Button,
+    Paper,
+    Stack,
+    TextField,
+    Typography,
+} from '@mui/material'
+import { Credentials } from "../types/user"
+
+type Props = {
+    onLogin: (credentials: Credentials) => Promise<void>
+    onRegister: (credentials: Credentials) => Promise<void>
+}
+
+const LoginForm: FC<Props> = ({ onLogin, onRegister }) => {
+    const [username, setUsername] = useState('')
+    const [password, setPassword] = useState('')
+    const [error, setError] = useState<string | null>(null)
+
+    const submit = async (action: (credentials: Credentials) => Promise<void>) => {
+        if (!username || !password) return
+
+        try {
+            setError(null)
+            await action({ username, password })
+        } catch (e) {
+            setError(e instanceof Error ? e.message : 'request failed')
+        }
+    }
+
+    return (
+        <Paper elevation={2}>
+            <Box sx={{ p: 2 }}>
+                <Stack spacing={2}>
+                    <Typography variant="h2">sign in</Typography>
+                    <TextField
+                        label="username"
+                        variant="filled"
+                        value={username}
+                        onChange={(e) => setUsername(e.target.value)}
+                        fullWidth
+                    />
+                    <TextField
+                        label="password"
+                        type="password"
+                        variant="filled"
+                        value={password}
+                        onChange={(e) => setPassword(e.target.value)}
+                        fullWidth
+                    />
+                    {error && <Typography color="error">{error}</Typography>}
+                    <Stack direction="row" spacing={2} justifyContent="flex-end">
+                        <Button color="secondary" onClick={() => submit(onRegister)}>
+                            register
+                        </Button>
+                        <Button onClick={() => submit(onLogin)}>
+                            sign in
+                        </Button>
+                    </Stack>
+                </Stack>
+            </Box>
+        </Paper>
+    )
+}
+
+export default LoginForm
@@ -11,12 +11,16 @@ import { Button,
     Box,
 } from "@mui/material";
 import LabelIcon from '@mui/icons-material/Label';
+import FolderIcon from '@mui/icons-material/Folder';
 import EditIcon from '@mui/icons-material/Edit';
 import DeleteIcon from '@mui/icons-material/Delete';
 import { FC, useState } from "react";
-import { Label, NewLabelPayload } from "../types/todo";
+import { Label, NewLabelPayload, Project } from "../types/todo";
 
 type Props = {
+    projects: Project[]
+    projectId: number
+    onSelectProject: (project: Project) => void
     labels: Label[]
     filterLabelId: number | null
     onSelectLabel: (label: Label | null) => void
@@ -26,6 +30,9 @@ type Props = {
 import { modalInnerStyle } from "../styles/modal";
 
 const SideNav: FC<Props> = ({
+    projects,
+    projectId,
+    onSelectProject,
     labels,
     filterLabelId,
     onSelectLabel,
@@ -43,6 +50,20 @@ const SideNav: FC<Props> = ({
   return (
     <div>
         <List>
+            <ListSubheader>Projects</ListSubheader>
+            {projects.map((project) => (
+                <ListItem key={project.id} disablePadding>
+                    <ListItemButton
+                        onClick={() => onSelectProject(project)}
+                        selected={project.id === projectId}
+                    >
+                        <Stack direction="row" alignItems="center" spacing={1}>
+                            <FolderIcon fontSize="small" />
+                            <span>{project.name}</span>
+                        </Stack>
+                    </ListItemButton>
+                </ListItem>
+            ))}
             <ListSubheader>Labels</ListSubheader>
             {labels.map((label) => (
                 <ListItem key={label.id} disablePadding>
@@ -0,0 +1,30 @@
+const API_URL = 'http://localhost:3000'
+const SESSION_KEY = 'my-todo-session'
+
+export class UnauthorizedError extends Error {}
+
+export const getSessionToken = () => localStorage.getItem(SESSION_KEY)
+
+export const setSessionToken = (token: string | null) => {
+    if (token) {
+        localStorage.setItem(SESSION_KEY, token)
+    } else {
+        localStorage.removeItem(SESSION_KEY)
+    }
+}
+
+// every API call goes through here, signed with the session token when there is one;
+// a 401 to a signed request means the session is gone, so it is forgotten
+export const apiFetch = async (path: string, init: RequestInit = {}) => {
+    const headers = new Headers(init.headers)
+    const token = getSessionToken()
+    if (token) {
+        headers.set('Authorization', `Bearer ${token}`)
+    }
+    const res = await fetch(`${API_URL}${path}`, { ...init, headers })
+    if (res.status === 401 && token) {
+        setSessionToken(null)
+        throw new UnauthorizedError('not signed in')
+    }
+    return res
+}
@@ -1,7 +1,8 @@
 import type { Label, NewLabelPayload } from "../../types/todo";
+import { apiFetch } from "./client";
 
 export const getLabelItems = async () => {
-    const res = await fetch('http://localhost:3000/labels')
+    const res = await apiFetch('/labels')
     if (!res.ok) {
         throw new Error('get label request failed')
     }
@@ -10,7 +11,7 @@ export const getLabelItems = async () => {
 }
 
 export const addLabelItem = async (payload: NewLabelPayload) => {
-    const res = await fetch('http://localhost:3000/labels', {
+    const res = await apiFetch('/labels', {
         method: 'POST',
         headers: {
             'Content-Type': 'application/json',
@@ -25,7 +26,7 @@ export const addLabelItem = async (payload: NewLabelPayload) => {
 }
 
 export const deleteLabelItem = async (id: number) => {
-    const res = await fetch(`http://localhost:3000/labels/${id}`, {
+    const res = await apiFetch(`/labels/${id}`, {
         method: 'DELETE',
     })
     if(!res.ok) {
@@ -0,0 +1,14 @@
+import type { Project } from "../../types/todo";
+import { apiFetch } from "./client";
+
+// the default project every user has, where todos go unless told otherwise
+export const DEFAULT_PROJECT_ID = 1
+
+export const getProjectItems = async () => {
+    const res = await apiFetch('/projects')
+    if (!res.ok) {
+        throw new Error('get project request failed')
+    }
+    const json: Project[] = await res.json()
+    return json
+}
@@ -1,7 +1,8 @@
-import type { NewTodoPayload, Todo, UpdateTodoPayload } from "../../types/todo";
+import type { NewTodoPayload, Placement, Todo, TodoPage, UpdateTodoPayload } from "../../types/todo";
+import { apiFetch } from "./client";
 
 export const addTodoItem = async (payload: NewTodoPayload) => {
-    const res = await fetch('http://localhost:3000/todos', {
+    const res = await apiFetch('/todos', {
         method: 'POST',
         headers: {
             'Content-Type': 'application/json',
@@ -15,18 +16,26 @@ export const addTodoItem = async (payload: NewTodoPayload) => {
     return json
 }
 
-export const getTodoItem = async () => {
-    const res = await fetch('http://localhost:3000/todos')
-    if (!res.ok) {
-        throw new Error('get todo request failed')
-    }
-    const json: Todo[] = await res.json()
-    return json
+// positions only order the todos of one project, so the list is read per project
+export const getTodoItem = async (projectId: number) => {
+    const todos: Todo[] = []
+    let cursor: string | null = null
+    do {
+        const query: string = cursor ? `?sort=position&cursor=${cursor}` : '?sort=position'
+        const res = await apiFetch(`/projects/${projectId}/todos${query}`)
+        if (!res.ok) {
+            throw new Error('get todo request failed')
+        }
+        const json: TodoPage = await res.json()
+        todos.push(...json.items)
+        cursor = json.next_cursor
+    } while (cursor)
+    return todos
 }
 
 export const updateTodoItem = async (todo: UpdateTodoPayload) => {
     const {id, ...updateTodo} = todo
-    const res = await fetch(`http://localhost:3000/todos/${id}`, {
+    const res = await apiFetch(`/todos/${id}`, {
         method: 'PATCH',
         headers: {
             'Content-Type': 'application/json', 
@@ -40,8 +49,23 @@ export const updateTodoItem = async (todo: UpdateTodoPayload) => {
     return json
 }
 
+export const moveTodoItem = async (id: number, placement: Placement) => {
+    const res = await apiFetch(`/todos/${id}/move`, {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify(placement),
+    })
+    if (!res.ok) {
+        throw new Error('move todo request failed')
+    }
+    const json: Todo = await res.json()
+    return json
+}
+
 export const deleteTodoItem = async (id: number) => {
-    const res = await fetch(`http://localhost:3000/todos/${id}`, {
+    const res = await apiFetch(`/todos/${id}`, {
         method: 'DELETE',
     })
     if (!res.ok) {
@@ -0,0 +1,44 @@
+import type { Credentials, SessionToken, User } from "../../types/user";
+import { apiFetch, setSessionToken } from "./client";
+
+export const register = async (payload: Credentials) => {
+    const res = await apiFetch('/users', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify(payload),
+    })
+    if (!res.ok) {
+        throw new Error('register request failed')
+    }
+    const json: User = await res.json()
+    return json
+}
+
+export const login = async (payload: Credentials) => {
+    const res = await apiFetch('/login', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify(payload),
+    })
+    if (res.status === 401) {
+        throw new Error('invalid username or password')
+    }
+    if (!res.ok) {
+        throw new Error('login request failed')
+    }
+    const json: SessionToken = await res.json()
+    setSessionToken(json.token)
+    return json
+}
+
+export const logout = async () => {
+    try {
+        await apiFetch('/logout', { method: 'POST' })
+    } finally {
+        setSessionToken(null)
+    }
+}
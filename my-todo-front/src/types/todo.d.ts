@@ -2,12 +2,35 @@ export type Todo = {
     id: number
     text: string
     completed: boolean
+    priority: Priority
+    due_at: string | null
+    parent_id: number | null
+    auto_complete: boolean
+    project_id: number
+    position: number
     labels: Label[]
 }
 
+export type Priority = 'none' | 'low' | 'medium' | 'high' | 'urgent'
+
+export type Placement =
+    | { before: number }
+    | { after: number }
+    | { index: number }
+
+export type TodoPage = {
+    items: Todo[]
+    next_cursor: string | null
+}
+
 export type NewTodoPayload = {
     text: string
     labels: number[]
+    priority?: Priority
+    due_at?: string
+    parent_id?: number
+    auto_complete?: boolean
+    project_id?: number
 }
 
 export type UpdateTodoPayload = {
@@ -15,6 +38,10 @@ export type UpdateTodoPayload = {
     text?: string
     completed?: boolean
     labels?: number[]
+    priority?: Priority
+    due_at?: string | null
+    parent_id?: number | null
+    auto_complete?: boolean
 }
 
 export type Label = {
@@ -24,4 +51,9 @@ export type Label = {
 
 export type NewLabelPayload = {
     name: string
-}
\ No newline at end of file
+}
+
+export type Project = {
+    id: number
+    name: string
+}
@@ -0,0 +1,14 @@
+export type User = {
+    id: number
+    username: string
+}
+
+export type Credentials = {
+    username: string
+    password: string
+}
+
+export type SessionToken = {
+    token: string
+    expires_at: string
+}
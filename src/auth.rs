@@ -0,0 +1,208 @@
+use crate::handlers::AppError;
+use crate::repositories::user::{Scope, UserRepository};
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use axum::{
+    async_trait,
+    extract::{Extension, FromRequestParts, Request, State},
+    http::{header::AUTHORIZATION, request::Parts, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, OnceLock};
+
+/// Marks API tokens apart from session tokens.
+pub const API_TOKEN_PREFIX: &str = "tdk_";
+
+/// The signed in user, put in place by `authenticate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthUser {
+    pub id: i32,
+    pub username: String,
+    /// Granted by the API token, `None` for a session which may do anything.
+    pub scopes: Option<Vec<Scope>>,
+}
+
+impl AuthUser {
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes
+            .as_ref()
+            .is_none_or(|scopes| scopes.contains(&scope))
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthUser
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<AuthUser>()
+            .cloned()
+            .ok_or(AppError::unauthorized("Sign in required"))
+    }
+}
+
+/// Middleware that resolves the bearer token of the request to its user,
+/// refusing the request with 401 unless the token belongs to a live session
+/// or API token.
+pub async fn authenticate<U: UserRepository>(
+    Extension(users): Extension<Arc<U>>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let token = bearer_token(req.headers()).ok_or(AppError::unauthorized("Sign in required"))?;
+    let user = if token.starts_with(API_TOKEN_PREFIX) {
+        users
+            .use_token(hash_token(token), Utc::now())
+            .await?
+            .map(|(user, scopes)| AuthUser {
+                id: user.id,
+                username: user.username,
+                scopes: Some(scopes),
+            })
+            .ok_or(AppError::unauthorized("API token is invalid or expired"))?
+    } else {
+        users
+            .find_session(hash_token(token), Utc::now())
+            .await?
+            .map(|user| AuthUser {
+                id: user.id,
+                username: user.username,
+                scopes: None,
+            })
+            .ok_or(AppError::unauthorized("Session is invalid or expired"))?
+    };
+    req.extensions_mut().insert(user);
+    Ok(next.run(req).await)
+}
+
+/// Middleware, layered after `authenticate`, that refuses API tokens lacking `scope`.
+pub async fn require_scope(
+    State(scope): State<Scope>,
+    user: AuthUser,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if !user.allows(scope) {
+        return Err(AppError::forbidden(
+            format!("API token lacks the {} scope", scope.as_str()),
+            serde_json::json!({ "scope": scope }),
+        ));
+    }
+    Ok(next.run(req).await)
+}
+
+/// Middleware, layered after `authenticate`, that refuses API tokens altogether.
+pub async fn require_session(
+    user: AuthUser,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if user.scopes.is_some() {
+        return Err(AppError::forbidden(
+            "Only a signed in session may do this",
+            serde_json::json!({}),
+        ));
+    }
+    Ok(next.run(req).await)
+}
+
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+pub fn hash_password(password: &str) -> anyhow::Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow::anyhow!("fail hash password: {}", e))?;
+    Ok(hash.to_string())
+}
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+/// The hash a login for an unknown username is verified against, so that it takes as long
+/// as a wrong password and the response time does not tell which usernames exist.
+pub fn dummy_password_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| hash_password(&generate_token()).expect("fail hash dummy password"))
+}
+
+/// A fresh random token; only its `hash_token` is ever stored.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn generate_api_token() -> String {
+    format!("{}{}", API_TOKEN_PREFIX, generate_token())
+}
+
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn password_round_trip() {
+        let hash = hash_password("correct horse").unwrap();
+        assert!(verify_password("correct horse", &hash));
+        assert!(!verify_password("wrong horse", &hash));
+        assert!(!verify_password("correct horse", "not a hash"));
+        assert!(!verify_password("correct horse", dummy_password_hash()));
+    }
+
+    #[test]
+    fn reads_bearer_token() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(None, bearer_token(&headers));
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Basic abc"));
+        assert_eq!(None, bearer_token(&headers));
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer abc"));
+        assert_eq!(Some("abc"), bearer_token(&headers));
+        assert_ne!(generate_token(), generate_token());
+        assert_eq!(64, hash_token("abc").len());
+        assert!(generate_api_token().starts_with(API_TOKEN_PREFIX));
+    }
+
+    #[test]
+    fn sessions_allow_every_scope() {
+        let mut user = AuthUser {
+            id: 1,
+            username: "tester".to_string(),
+            scopes: None,
+        };
+        assert!(Scope::ALL.into_iter().all(|scope| user.allows(scope)));
+        user.scopes = Some(vec![Scope::TodosRead]);
+        assert!(user.allows(Scope::TodosRead));
+        assert!(!user.allows(Scope::TodosWrite));
+    }
+}
@@ -0,0 +1,316 @@
+//! Latency of `GET /todos` against Postgres with a large backlog of todos, through the
+//! repository and through the loader it replaced, which joined a row per label and folded
+//! them back into todos in quadratic time.
+//!
+//! Ignored by default as it seeds 100k todos, run it with
+//! `cargo test --release bench -- --ignored --nocapture`.
+
+use super::*;
+use crate::repositories::health::test_utils::HealthRepositoryForMemory;
+use crate::repositories::label::Label;
+use crate::repositories::project::{test_utils::ProjectRepositoryForMemory, DEFAULT_PROJECT_ID};
+use crate::repositories::todo::{
+    CreateTodo, DeleteMode, Placement, Priority, TodoEntity, TodoListQuery, TodoPage, TodoStats,
+    UpdateTodo, POSITION_GAP,
+};
+use crate::repositories::user::{
+    test_utils::{fixture_user, UserRepositoryForMemory},
+    User,
+};
+
+use axum::{
+    async_trait, body,
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+use std::{env, time::Instant};
+use tower::ServiceExt;
+
+const TOKEN: &str = "bench-token";
+const SIZES: [i64; 2] = [10_000, 100_000];
+const WARMUP: usize = 10;
+const SAMPLES: usize = 200;
+const PATHS: [&str; 3] = ["/todos", "/todos?limit=200", "/todos?sort=text&order=asc"];
+
+/// `TodoRepositoryForDb` listing todos the way it did before labels were aggregated in SQL.
+#[derive(Clone)]
+struct BaselineTodoRepository(TodoRepositoryForDb);
+
+#[derive(FromRow)]
+struct TodoWithLabelFromRow {
+    id: i32,
+    text: String,
+    completed: bool,
+    priority: Priority,
+    due_at: Option<DateTime<Utc>>,
+    parent_id: Option<i32>,
+    auto_complete: bool,
+    project_id: i32,
+    position: i64,
+    label_id: Option<i32>,
+    label_name: Option<String>,
+}
+
+/// Scans every todo folded so far for each row.
+fn fold_entities(rows: Vec<TodoWithLabelFromRow>) -> Vec<TodoEntity> {
+    let mut accum: Vec<TodoEntity> = vec![];
+    'outer: for row in rows {
+        let label = row
+            .label_id
+            .zip(row.label_name)
+            .map(|(id, name)| Label { id, name });
+        for todo in accum.iter_mut() {
+            if todo.id == row.id {
+                todo.labels.extend(label);
+                continue 'outer;
+            }
+        }
+        accum.push(TodoEntity {
+            id: row.id,
+            text: row.text,
+            completed: row.completed,
+            priority: row.priority,
+            due_at: row.due_at,
+            parent_id: row.parent_id,
+            auto_complete: row.auto_complete,
+            project_id: row.project_id,
+            position: row.position,
+            labels: label.into_iter().collect(),
+        });
+    }
+    accum
+}
+
+#[async_trait]
+impl TodoRepository for BaselineTodoRepository {
+    async fn create(&self, owner_id: i32, payload: CreateTodo) -> anyhow::Result<TodoEntity> {
+        self.0.create(owner_id, payload).await
+    }
+
+    async fn find(&self, owner_id: i32, id: i32) -> anyhow::Result<TodoEntity> {
+        self.0.find(owner_id, id).await
+    }
+
+    async fn list(&self, owner_id: i32, query: TodoListQuery) -> anyhow::Result<TodoPage> {
+        // page through todos alone first so that LIMIT counts todos rather than label rows
+        let mut builder = QueryBuilder::<Postgres>::new(
+            "WITH page AS ( SELECT todos.* FROM todos WHERE todos.owner_id = ",
+        );
+        builder.push_bind(owner_id);
+        query.push_conditions(&mut builder);
+        query.push_order_by(&mut builder, "todos");
+        builder.push(" LIMIT ").push_bind(query.limit + 1);
+        builder.push(
+            r#" )
+                SELECT page.*, labels.id as label_id, labels.name as label_name
+                FROM page
+                LEFT OUTER JOIN todo_labels tl ON page.id = tl.todo_id
+                LEFT OUTER JOIN labels ON labels.id = tl.label_id"#,
+        );
+        query.push_order_by(&mut builder, "page");
+        builder.push(", tl.id ASC");
+
+        let items = builder
+            .build_query_as::<TodoWithLabelFromRow>()
+            .fetch_all(&self.0.pool)
+            .await?;
+
+        Ok(TodoPage::new(fold_entities(items), &query))
+    }
+
+    async fn update(
+        &self,
+        owner_id: i32,
+        id: i32,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<TodoEntity> {
+        self.0.update(owner_id, id, payload).await
+    }
+
+    async fn delete(&self, owner_id: i32, id: i32, mode: DeleteMode) -> anyhow::Result<()> {
+        self.0.delete(owner_id, id, mode).await
+    }
+
+    async fn children(&self, owner_id: i32, id: i32) -> anyhow::Result<Vec<TodoEntity>> {
+        self.0.children(owner_id, id).await
+    }
+
+    async fn descendants(&self, owner_id: i32, ids: Vec<i32>) -> anyhow::Result<Vec<TodoEntity>> {
+        self.0.descendants(owner_id, ids).await
+    }
+
+    async fn move_to_project(
+        &self,
+        owner_id: i32,
+        id: i32,
+        project_id: i32,
+    ) -> anyhow::Result<TodoEntity> {
+        self.0.move_to_project(owner_id, id, project_id).await
+    }
+
+    async fn reorder(
+        &self,
+        owner_id: i32,
+        id: i32,
+        placement: Placement,
+    ) -> anyhow::Result<TodoEntity> {
+        self.0.reorder(owner_id, id, placement).await
+    }
+
+    async fn stats(&self) -> anyhow::Result<TodoStats> {
+        self.0.stats().await
+    }
+}
+
+/// Removes everything `owner_id` has, labels go along with their todos.
+async fn clear(pool: &PgPool, owner_id: i32) {
+    for table in ["todos", "labels"] {
+        sqlx::query(&format!("DELETE FROM {} WHERE owner_id = $1", table))
+            .bind(owner_id)
+            .execute(pool)
+            .await
+            .expect("Failed to clear bench data.");
+    }
+}
+
+/// Tops the todos of `owner_id` up to `size`, each carrying up to three of five labels.
+async fn seed(pool: &PgPool, owner_id: i32, size: i64) {
+    sqlx::query(
+        r#"
+            INSERT INTO labels ( name, owner_id )
+            SELECT 'bench ' || n, $1 FROM generate_series(1, 5) n
+            ON CONFLICT DO NOTHING
+        "#,
+    )
+    .bind(owner_id)
+    .execute(pool)
+    .await
+    .expect("Failed to seed labels.");
+    let new_todos = sqlx::query_scalar::<_, Vec<i32>>(
+        r#"
+            WITH inserted AS (
+                INSERT INTO todos ( text, completed, project_id, owner_id, position )
+                SELECT 'bench todo ' || n, n % 3 = 0, $3, $1, n * $4
+                FROM generate_series(
+                    ( SELECT count(*) FROM todos WHERE owner_id = $1 ) + 1, $2
+                ) n
+                RETURNING id
+            )
+            SELECT COALESCE(array_agg(id), '{}') FROM inserted
+        "#,
+    )
+    .bind(owner_id)
+    .bind(size)
+    .bind(DEFAULT_PROJECT_ID)
+    .bind(POSITION_GAP)
+    .fetch_one(pool)
+    .await
+    .expect("Failed to seed todos.");
+    sqlx::query(
+        r#"
+            INSERT INTO todo_labels ( todo_id, label_id )
+            SELECT t.id, labels.id
+            FROM UNNEST ( $1 ) as t (id)
+            JOIN labels ON labels.owner_id = $2
+            WHERE ( t.id + labels.id ) % 5 < 3
+        "#,
+    )
+    .bind(new_todos)
+    .bind(owner_id)
+    .execute(pool)
+    .await
+    .expect("Failed to seed todo labels.");
+    sqlx::query("ANALYZE todos, todo_labels, labels")
+        .execute(pool)
+        .await
+        .expect("Failed to analyze.");
+}
+
+fn app<T: TodoRepository>(pool: &PgPool, users: &UserRepositoryForMemory, todos: T) -> Router {
+    create_app(
+        &Config::default(),
+        todos,
+        LabelRepositoryForDb::new(pool.clone()),
+        ProjectRepositoryForMemory::new(),
+        users.clone(),
+        HealthRepositoryForMemory::new(),
+    )
+}
+
+async fn sample(app: &Router, path: &str) -> Duration {
+    let req = Request::builder()
+        .uri(path)
+        .header(header::AUTHORIZATION, format!("Bearer {}", TOKEN))
+        .body(Body::empty())
+        .unwrap();
+    let started = Instant::now();
+    let res = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(StatusCode::OK, res.status());
+    body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    started.elapsed()
+}
+
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    sorted[(sorted.len() * p / 100).min(sorted.len() - 1)]
+}
+
+#[tokio::test]
+#[ignore]
+async fn bench_list_todos() {
+    let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+    let pool = PgPool::connect(database_url)
+        .await
+        .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+    let owner = fixture_user(&pool, "[bench_list_todos] owner").await;
+    clear(&pool, owner.id).await;
+
+    let users = UserRepositoryForMemory::new().with_session(
+        User {
+            id: owner.id,
+            username: owner.username.clone(),
+        },
+        auth::hash_token(TOKEN),
+    );
+    let current = TodoRepositoryForDb::new(pool.clone());
+    let loaders = [
+        (
+            "before",
+            app(&pool, &users, BaselineTodoRepository(current.clone())),
+        ),
+        ("after", app(&pool, &users, current)),
+    ];
+
+    println!(
+        "{:>8}  {:<28} {:<7} {:>10} {:>10} {:>10}",
+        "todos", "request", "loader", "mean", "p50", "p95"
+    );
+    for size in SIZES {
+        seed(&pool, owner.id, size).await;
+        for path in PATHS {
+            for (loader, app) in &loaders {
+                for _ in 0..WARMUP {
+                    sample(app, path).await;
+                }
+                let mut samples = Vec::with_capacity(SAMPLES);
+                for _ in 0..SAMPLES {
+                    samples.push(sample(app, path).await);
+                }
+                samples.sort();
+                let mean = samples.iter().sum::<Duration>() / SAMPLES as u32;
+                println!(
+                    "{:>8}  {:<28} {:<7} {:>10.2?} {:>10.2?} {:>10.2?}",
+                    size,
+                    path,
+                    loader,
+                    mean,
+                    percentile(&samples, 50),
+                    percentile(&samples, 95)
+                );
+            }
+        }
+    }
+
+    clear(&pool, owner.id).await;
+}
@@ -1,14 +1,224 @@
+pub mod health;
 pub mod label;
+pub mod metrics;
+pub mod project;
 pub mod todo;
+pub mod user;
 
+use crate::repositories::RepositoryError;
 use axum::{
     async_trait,
-    extract::{FromRequest, Request},
+    extract::{
+        rejection::{PathRejection, QueryRejection},
+        FromRequest, FromRequestParts, Request,
+    },
     http::StatusCode,
+    response::{IntoResponse, Response},
     Json,
 };
-use serde::de::DeserializeOwned;
-use validator::Validate;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use validator::{Validate, ValidationErrors};
+
+/// Error returned by every handler, rendered as `{ "code", "message", "details" }`.
+#[derive(Debug)]
+pub struct AppError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+    details: Value,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorBody<'a> {
+    code: &'a str,
+    message: &'a str,
+    details: &'a Value,
+}
+
+impl AppError {
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            code: "bad_request",
+            message: message.into(),
+            details: json!({}),
+        }
+    }
+
+    pub fn validation(errors: ValidationErrors) -> Self {
+        // field name => messages of the rules that failed for it
+        let details = errors
+            .field_errors()
+            .into_iter()
+            .map(|(field, errors)| {
+                let messages = errors
+                    .iter()
+                    .map(|error| {
+                        error
+                            .message
+                            .as_ref()
+                            .map(|message| message.to_string())
+                            .unwrap_or_else(|| error.code.to_string())
+                    })
+                    .collect::<Vec<_>>();
+                (field.to_string(), json!(messages))
+            })
+            .collect::<serde_json::Map<_, _>>();
+        Self {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            code: "validation_error",
+            message: "Validation error".to_string(),
+            details: Value::Object(details),
+        }
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::UNAUTHORIZED,
+            code: "unauthorized",
+            message: message.into(),
+            details: json!({}),
+        }
+    }
+
+    pub fn forbidden(message: impl Into<String>, details: Value) -> Self {
+        Self {
+            status: StatusCode::FORBIDDEN,
+            code: "forbidden",
+            message: message.into(),
+            details,
+        }
+    }
+
+    fn unexpected() -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            code: "unexpected",
+            message: "Unexpected error".to_string(),
+            details: json!({}),
+        }
+    }
+}
+
+impl From<anyhow::Error> for AppError {
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast_ref::<RepositoryError>() {
+            Some(RepositoryError::NotFound(id)) => Self {
+                status: StatusCode::NOT_FOUND,
+                code: "not_found",
+                message: error.to_string(),
+                details: json!({ "id": id }),
+            },
+            Some(RepositoryError::Duplicate(id)) => Self {
+                status: StatusCode::CONFLICT,
+                code: "duplicate",
+                message: error.to_string(),
+                details: json!({ "id": id }),
+            },
+            Some(RepositoryError::HasChildren { id, children }) => Self {
+                status: StatusCode::CONFLICT,
+                code: "has_children",
+                message: error.to_string(),
+                details: json!({ "id": id, "children": children }),
+            },
+            Some(RepositoryError::InvalidParent(parent_id)) => Self {
+                status: StatusCode::UNPROCESSABLE_ENTITY,
+                code: "invalid_parent",
+                message: error.to_string(),
+                details: json!({ "parent_id": parent_id }),
+            },
+            Some(RepositoryError::InvalidProject(project_id)) => Self {
+                status: StatusCode::UNPROCESSABLE_ENTITY,
+                code: "invalid_project",
+                message: error.to_string(),
+                details: json!({ "project_id": project_id }),
+            },
+            Some(RepositoryError::InvalidPlacement(todo_id)) => Self {
+                status: StatusCode::UNPROCESSABLE_ENTITY,
+                code: "invalid_placement",
+                message: error.to_string(),
+                details: json!({ "todo_id": todo_id }),
+            },
+            Some(RepositoryError::NotEmpty { id, todos }) => Self {
+                status: StatusCode::CONFLICT,
+                code: "not_empty",
+                message: error.to_string(),
+                details: json!({ "id": id, "todos": todos }),
+            },
+            Some(RepositoryError::Protected(id)) => Self {
+                status: StatusCode::CONFLICT,
+                code: "protected",
+                message: error.to_string(),
+                details: json!({ "id": id }),
+            },
+            Some(RepositoryError::UnknownLabels(ids)) => Self {
+                status: StatusCode::UNPROCESSABLE_ENTITY,
+                code: "validation_error",
+                message: "Validation error".to_string(),
+                details: json!({
+                    "labels": ids
+                        .iter()
+                        .map(|id| format!("Label {} does not exist", id))
+                        .collect::<Vec<_>>(),
+                }),
+            },
+            Some(RepositoryError::InUse { id, todos }) => Self {
+                status: StatusCode::CONFLICT,
+                code: "in_use",
+                message: error.to_string(),
+                details: json!({ "id": id, "todos": todos }),
+            },
+            Some(RepositoryError::Unexpected(_)) | None => {
+                tracing::error!("unexpected error: {:?}", error);
+                Self::unexpected()
+            }
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let body = ErrorBody {
+            code: self.code,
+            message: &self.message,
+            details: &self.details,
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+impl From<PathRejection> for AppError {
+    fn from(rejection: PathRejection) -> Self {
+        Self {
+            status: rejection.status(),
+            code: "invalid_path",
+            message: format!("Path parse error: [{}]", rejection.body_text()),
+            details: json!({}),
+        }
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self {
+            status: rejection.status(),
+            code: "invalid_query",
+            message: format!("Query parse error: [{}]", rejection.body_text()),
+            details: json!({}),
+        }
+    }
+}
+
+/// `axum::extract::Path` rejecting with an `AppError`.
+#[derive(Debug, FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(AppError))]
+pub struct Path<T>(pub T);
+
+/// `axum::extract::Query` rejecting with an `AppError`.
+#[derive(Debug, FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(AppError))]
+pub struct Query<T>(pub T);
 
 #[derive(Debug)]
 pub struct ValidatedJson<T>(T);
@@ -19,19 +229,18 @@ where
     T: DeserializeOwned + Validate,
     B: Send + Sync,
 {
-    type Rejection = (StatusCode, String);
+    type Rejection = AppError;
 
     async fn from_request(req: Request, state: &B) -> Result<Self, Self::Rejection> {
         let Json(value) = Json::<T>::from_request(req, state)
             .await
-            .map_err(|rejection| {
-                let message = format!("Json parse error: [{}]", rejection);
-                (StatusCode::BAD_REQUEST, message)
+            .map_err(|rejection| AppError {
+                status: rejection.status(),
+                code: "invalid_json",
+                message: format!("Json parse error: [{}]", rejection.body_text()),
+                details: json!({}),
             })?;
-        value.validate().map_err(|rejection| {
-            let message = format!("Validation error: [{}]", rejection).replace('\n', ", ");
-            (StatusCode::BAD_REQUEST, message)
-        })?;
+        value.validate().map_err(AppError::validation)?;
         Ok(ValidatedJson(value))
     }
 }
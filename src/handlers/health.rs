@@ -0,0 +1,130 @@
+use anyhow::Context;
+use axum::{extract::Extension, http::StatusCode, response::IntoResponse, Json};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::repositories::{
+    health::HealthRepository,
+    label::LabelRepository,
+    project::ProjectRepository,
+    todo::{TodoListQuery, TodoRepository},
+    user::UserRepository,
+};
+
+/// A check slower than this counts as failed, so probes never hang on a stuck pool.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+/// No user has this id, reads made as them touch the tables without returning rows.
+const NOBODY: i32 = 0;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Check {
+    pub ok: bool,
+    pub latency_ms: f64,
+    /// Why the check failed, only in general terms as the details go to the log.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: BTreeMap<String, Check>,
+}
+
+/// Liveness: answers as long as the process serves requests at all.
+pub async fn healthz() -> impl IntoResponse {
+    (StatusCode::OK, Json(serde_json::json!({ "status": "ok" })))
+}
+
+/// Readiness: 200 when every check passed, 503 otherwise.
+pub async fn readyz<
+    Health: HealthRepository,
+    Todo: TodoRepository,
+    Label: LabelRepository,
+    Project: ProjectRepository,
+    User: UserRepository,
+>(
+    Extension(health): Extension<Arc<Health>>,
+    Extension(todos): Extension<Arc<Todo>>,
+    Extension(labels): Extension<Arc<Label>>,
+    Extension(projects): Extension<Arc<Project>>,
+    Extension(users): Extension<Arc<User>>,
+) -> impl IntoResponse {
+    let (database, migrations, repositories) = tokio::join!(
+        timed("database", health.ping()),
+        timed("migrations", health.migrations()),
+        timed(
+            "repositories",
+            repositories_usable(
+                todos.as_ref(),
+                labels.as_ref(),
+                projects.as_ref(),
+                users.as_ref()
+            )
+        ),
+    );
+    let checks = BTreeMap::from([
+        ("database".to_string(), database),
+        ("migrations".to_string(), migrations),
+        ("repositories".to_string(), repositories),
+    ]);
+    let ready = checks.values().all(|check| check.ok);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(Readiness { ready, checks }))
+}
+
+async fn timed(name: &str, check: impl Future<Output = anyhow::Result<()>>) -> Check {
+    let started = Instant::now();
+    let error = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => {
+            tracing::warn!("readiness check {} failed: {:#}", name, e);
+            Some("failed")
+        }
+        Err(_) => {
+            tracing::warn!(
+                "readiness check {} timed out after {:?}",
+                name,
+                CHECK_TIMEOUT
+            );
+            Some("timed out")
+        }
+    };
+    Check {
+        ok: error.is_none(),
+        latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+        error: error.map(str::to_string),
+    }
+}
+
+/// Runs one harmless read through every repository.
+async fn repositories_usable(
+    todos: &impl TodoRepository,
+    labels: &impl LabelRepository,
+    projects: &impl ProjectRepository,
+    users: &impl UserRepository,
+) -> anyhow::Result<()> {
+    let query = TodoListQuery {
+        limit: 1,
+        ..TodoListQuery::default()
+    };
+    todos.list(NOBODY, query).await.context("todos")?;
+    labels.all(NOBODY).await.context("labels")?;
+    projects.all(NOBODY).await.context("projects")?;
+    users
+        .find_session(String::new(), Utc::now())
+        .await
+        .context("users")?;
+    Ok(())
+}
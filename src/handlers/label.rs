@@ -1,41 +1,103 @@
 use axum::{
-    extract::{Extension, Path},
+    extract::Extension,
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     Json,
 };
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-use crate::repositories::label::{CreateLabel, LabelRepository};
+use crate::auth::AuthUser;
+use crate::repositories::{
+    label::{CreateLabel, LabelDeleteMode, LabelRepository, MergeLabels, UpdateLabel},
+    RepositoryError,
+};
 
-use super::ValidatedJson;
+use super::{AppError, Path, Query, ValidatedJson};
 
 pub async fn create_label<T: LabelRepository>(
+    user: AuthUser,
     Extension(repository): Extension<Arc<T>>,
     ValidatedJson(payload): ValidatedJson<CreateLabel>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let label = repository
-        .create(payload.name)
-        .await
-        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+) -> Result<impl IntoResponse, AppError> {
+    let label = repository.create(user.id, payload.name).await?;
 
     Ok((StatusCode::CREATED, Json(label)))
 }
 
 pub async fn all_label<T: LabelRepository>(
+    user: AuthUser,
     Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let labels = repository.all().await.unwrap();
+) -> Result<impl IntoResponse, AppError> {
+    let labels = repository.all(user.id).await?;
     Ok((StatusCode::OK, Json(labels)))
 }
 
+pub async fn update_label<T: LabelRepository>(
+    user: AuthUser,
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    ValidatedJson(payload): ValidatedJson<UpdateLabel>,
+) -> Result<impl IntoResponse, AppError> {
+    let label = repository.update(user.id, id, payload.name).await?;
+
+    Ok((StatusCode::OK, Json(label)))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteLabelQuery {
+    mode: Option<LabelDeleteMode>,
+    /// Only report what the delete would do.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// What a dry run of deleting a label reports.
+#[derive(Debug, Serialize)]
+pub struct DeleteLabelPreview {
+    id: i32,
+    /// How many todos the label would be detached from.
+    affected_todos: usize,
+}
+
 pub async fn delete_label<T: LabelRepository>(
+    user: AuthUser,
+    Path(id): Path<i32>,
+    Query(query): Query<DeleteLabelQuery>,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<Response, AppError> {
+    let mode = query.mode.unwrap_or_default();
+    if !query.dry_run {
+        repository.delete(user.id, id, mode).await?;
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+
+    // a dry run fails the same way the delete itself would
+    let todos = repository.usage(user.id, id).await?;
+    if !todos.is_empty() && mode == LabelDeleteMode::Restrict {
+        return Err(anyhow::Error::from(RepositoryError::InUse { id, todos }).into());
+    }
+    let preview = DeleteLabelPreview {
+        id,
+        affected_todos: todos.len(),
+    };
+    Ok((StatusCode::OK, Json(preview)).into_response())
+}
+
+/// What merging labels did.
+#[derive(Debug, Serialize)]
+pub struct MergedLabels {
+    id: i32,
+    /// How many todos carried one of the merged labels.
+    affected_todos: usize,
+}
+
+pub async fn merge_labels<T: LabelRepository>(
+    user: AuthUser,
     Path(id): Path<i32>,
     Extension(repository): Extension<Arc<T>>,
-) -> StatusCode {
-    repository
-        .delete(id)
-        .await
-        .map(|_| StatusCode::NO_CONTENT)
-        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    ValidatedJson(payload): ValidatedJson<MergeLabels>,
+) -> Result<impl IntoResponse, AppError> {
+    let affected_todos = repository.merge(user.id, id, payload.sources).await?;
+    Ok((StatusCode::OK, Json(MergedLabels { id, affected_todos })))
 }
@@ -0,0 +1,48 @@
+use axum::{
+    extract::Extension,
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::IntoResponse,
+};
+use std::{cmp::Reverse, sync::Arc};
+
+use crate::metrics::{render, DB_POOL_IDLE, DB_POOL_SIZE, TODOS, TODOS_BY_LABEL, TOP_LABELS};
+use crate::repositories::{health::HealthRepository, todo::TodoRepository};
+
+use super::AppError;
+
+/// Prometheus scrape target. Gauges are brought up to date on every scrape.
+pub async fn metrics<Health: HealthRepository, Todo: TodoRepository>(
+    Extension(health): Extension<Arc<Health>>,
+    Extension(todos): Extension<Arc<Todo>>,
+) -> Result<impl IntoResponse, AppError> {
+    // sqlx does not expose how many wait for a connection, so only size and idle are exported
+    if let Some(pool) = health.pool() {
+        DB_POOL_SIZE.set(pool.size.into());
+        DB_POOL_IDLE.set(pool.idle as i64);
+    }
+
+    let stats = todos.stats().await?;
+    TODOS.with_label_values(&["open"]).set(stats.open);
+    TODOS.with_label_values(&["completed"]).set(stats.completed);
+    // most carried first, ties by id so the series stay put between scrapes
+    let mut by_label: Vec<_> = stats.by_label.into_iter().collect();
+    by_label.sort_by_key(|&(id, todos)| (Reverse(todos), id));
+    TODOS_BY_LABEL.reset();
+    for (id, todos) in by_label.iter().take(TOP_LABELS) {
+        TODOS_BY_LABEL
+            .with_label_values(&[&id.to_string()])
+            .set(*todos);
+    }
+    let other = by_label
+        .iter()
+        .skip(TOP_LABELS)
+        .map(|(_, todos)| todos)
+        .sum();
+    TODOS_BY_LABEL.with_label_values(&["other"]).set(other);
+
+    Ok((
+        StatusCode::OK,
+        [(CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        render()?,
+    ))
+}
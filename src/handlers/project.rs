@@ -0,0 +1,81 @@
+use axum::{
+    extract::Extension,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use std::sync::Arc;
+
+use crate::auth::AuthUser;
+use crate::repositories::{
+    project::{CreateProject, ProjectRepository, UpdateProject},
+    todo::TodoRepository,
+};
+
+use super::{
+    todo::{list_todos, TodoQuery},
+    AppError, Path, Query, ValidatedJson,
+};
+
+pub async fn create_project<T: ProjectRepository>(
+    user: AuthUser,
+    Extension(repository): Extension<Arc<T>>,
+    ValidatedJson(payload): ValidatedJson<CreateProject>,
+) -> Result<impl IntoResponse, AppError> {
+    let project = repository.create(user.id, payload.name).await?;
+
+    Ok((StatusCode::CREATED, Json(project)))
+}
+
+pub async fn find_project<T: ProjectRepository>(
+    user: AuthUser,
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, AppError> {
+    let project = repository.find(user.id, id).await?;
+    Ok((StatusCode::OK, Json(project)))
+}
+
+pub async fn all_project<T: ProjectRepository>(
+    user: AuthUser,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, AppError> {
+    let projects = repository.all(user.id).await?;
+    Ok((StatusCode::OK, Json(projects)))
+}
+
+pub async fn update_project<T: ProjectRepository>(
+    user: AuthUser,
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    ValidatedJson(payload): ValidatedJson<UpdateProject>,
+) -> Result<impl IntoResponse, AppError> {
+    let project = repository.update(user.id, id, payload.name).await?;
+
+    Ok((StatusCode::OK, Json(project)))
+}
+
+pub async fn delete_project<T: ProjectRepository>(
+    user: AuthUser,
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<StatusCode, AppError> {
+    repository.delete(user.id, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `GET /todos` restricted to one project, with the same query parameters.
+pub async fn project_todos<P: ProjectRepository, T: TodoRepository>(
+    user: AuthUser,
+    Path(id): Path<i32>,
+    Query(query): Query<TodoQuery>,
+    Extension(projects): Extension<Arc<P>>,
+    Extension(todos): Extension<Arc<T>>,
+) -> Result<Response, AppError> {
+    projects.find(user.id, id).await?;
+    let view = query.view();
+    let mut query = query.into_list_query(Utc::now())?;
+    query.filter.project_id = Some(id);
+    list_todos(todos.as_ref(), user.id, query, view).await
+}
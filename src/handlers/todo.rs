@@ -1,58 +1,239 @@
-use crate::handlers::ValidatedJson;
-use crate::repositories::todo::{CreateTodo, TodoRepository, UpdateTodo};
+use crate::auth::AuthUser;
+use crate::handlers::{AppError, Path, Query, ValidatedJson};
+use crate::repositories::todo::{
+    CreateTodo, DeleteMode, DueWindow, MoveTodo, ReorderTodo, SortField, SortOrder, TodoCursor,
+    TodoFilter, TodoListQuery, TodoNode, TodoRepository, TodoTreePage, UpdateTodo,
+};
 use axum::{
-    extract::{Extension, Path},
+    extract::Extension,
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     Json,
 };
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::Deserialize;
 use std::sync::Arc;
 
+const MAX_DUE_WITHIN_DAYS: i64 = 366;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DueQuery {
+    Overdue,
+    Today,
+    Within,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoView {
+    #[default]
+    List,
+    /// Pages through top level todos with their subtasks nested below them.
+    Tree,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TodoQuery {
+    view: Option<TodoView>,
+    completed: Option<bool>,
+    /// Comma separated label ids, e.g. `labels=1,2`.
+    labels: Option<String>,
+    /// Case-insensitive substring of the todo text.
+    q: Option<String>,
+    sort: Option<SortField>,
+    order: Option<SortOrder>,
+    /// `next_cursor` of the previous page.
+    cursor: Option<String>,
+    limit: Option<i64>,
+    due: Option<DueQuery>,
+    /// Number of days ahead for `due=within`.
+    days: Option<i64>,
+    /// Offset from UTC in minutes that decides where "today" starts and ends.
+    utc_offset: Option<i32>,
+}
+
+impl TodoQuery {
+    pub fn view(&self) -> TodoView {
+        self.view.unwrap_or_default()
+    }
+
+    pub fn into_list_query(self, now: DateTime<Utc>) -> Result<TodoListQuery, AppError> {
+        let label_ids = match self.labels {
+            Some(labels) => labels
+                .split(',')
+                .map(|id| id.trim().parse::<i32>())
+                .collect::<Result<Vec<_>, _>>()
+                .or(Err(AppError::bad_request(
+                    "labels must be comma separated ids",
+                )))?,
+            None => vec![],
+        };
+        let due = match self.due {
+            None => None,
+            Some(DueQuery::Overdue) => Some(DueWindow::overdue(now)),
+            Some(DueQuery::Today) => {
+                let offset = self
+                    .utc_offset
+                    .unwrap_or(0)
+                    .checked_mul(60)
+                    .and_then(FixedOffset::east_opt)
+                    .ok_or(AppError::bad_request("utc_offset is out of range"))?;
+                Some(DueWindow::today(now.with_timezone(&offset)))
+            }
+            Some(DueQuery::Within) => {
+                let days = self
+                    .days
+                    .filter(|days| (0..=MAX_DUE_WITHIN_DAYS).contains(days))
+                    .ok_or(AppError::bad_request(format!(
+                        "due=within requires days between 0 and {}",
+                        MAX_DUE_WITHIN_DAYS
+                    )))?;
+                Some(DueWindow::within(now, days))
+            }
+        };
+        let sort = self.sort.unwrap_or_default();
+        let cursor = match self.cursor {
+            Some(cursor) => Some(
+                TodoCursor::decode(&cursor)
+                    .filter(|cursor| cursor.field() == sort)
+                    .ok_or(AppError::bad_request("cursor is invalid for this sort"))?,
+            ),
+            None => None,
+        };
+        let limit = self
+            .limit
+            .map(|limit| {
+                (1..=TodoListQuery::MAX_LIMIT)
+                    .contains(&limit)
+                    .then_some(limit)
+            })
+            .unwrap_or(Some(TodoListQuery::DEFAULT_LIMIT))
+            .ok_or(AppError::bad_request(format!(
+                "limit must be between 1 and {}",
+                TodoListQuery::MAX_LIMIT
+            )))?;
+
+        Ok(TodoListQuery {
+            filter: TodoFilter {
+                project_id: None,
+                completed: self.completed,
+                label_ids,
+                text: self.q.filter(|text| !text.is_empty()),
+                due,
+                roots_only: self.view == Some(TodoView::Tree),
+            },
+            sort,
+            order: self.order.unwrap_or(sort.default_order()),
+            cursor,
+            limit,
+        })
+    }
+}
+
 pub async fn create_todo<T: TodoRepository>(
+    user: AuthUser,
     Extension(repository): axum::Extension<Arc<T>>,
     ValidatedJson(payload): ValidatedJson<CreateTodo>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let todo = repository
-        .create(payload)
-        .await
-        .or(Err(StatusCode::NOT_FOUND))?;
+) -> Result<impl IntoResponse, AppError> {
+    let todo = repository.create(user.id, payload).await?;
     Ok((StatusCode::CREATED, Json(todo)))
 }
 
 pub async fn find_todo<T: TodoRepository>(
+    user: AuthUser,
     Path(id): Path<i32>,
     Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let todo = repository.find(id).await.or(Err(StatusCode::NOT_FOUND))?;
+) -> Result<impl IntoResponse, AppError> {
+    let todo = repository.find(user.id, id).await?;
     Ok((StatusCode::OK, Json(todo)))
 }
 
 pub async fn all_todo<T: TodoRepository>(
+    user: AuthUser,
+    Query(query): Query<TodoQuery>,
     Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let todo = repository.all().await.unwrap();
-    Ok((StatusCode::OK, Json(todo)))
+) -> Result<Response, AppError> {
+    let view = query.view();
+    let query = query.into_list_query(Utc::now())?;
+    list_todos(repository.as_ref(), user.id, query, view).await
+}
+
+/// Renders one page of `query` as a flat list or, for the tree view, with subtasks nested.
+pub async fn list_todos<T: TodoRepository>(
+    repository: &T,
+    owner_id: i32,
+    query: TodoListQuery,
+    view: TodoView,
+) -> Result<Response, AppError> {
+    let page = repository.list(owner_id, query).await?;
+    if view == TodoView::List {
+        return Ok((StatusCode::OK, Json(page)).into_response());
+    }
+
+    let ids = page.items.iter().map(|todo| todo.id).collect();
+    let descendants = repository.descendants(owner_id, ids).await?;
+    let tree = TodoTreePage {
+        items: TodoNode::build(page.items, descendants),
+        next_cursor: page.next_cursor,
+    };
+    Ok((StatusCode::OK, Json(tree)).into_response())
+}
+
+pub async fn children_todo<T: TodoRepository>(
+    user: AuthUser,
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, AppError> {
+    let todos = repository.children(user.id, id).await?;
+    Ok((StatusCode::OK, Json(todos)))
 }
 
 pub async fn update_todo<T: TodoRepository>(
+    user: AuthUser,
     Path(id): Path<i32>,
     Extension(repository): Extension<Arc<T>>,
     ValidatedJson(payload): ValidatedJson<UpdateTodo>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let todo = repository
-        .update(id, payload)
-        .await
-        .or(Err(StatusCode::NOT_FOUND))?;
+) -> Result<impl IntoResponse, AppError> {
+    let todo = repository.update(user.id, id, payload).await?;
     Ok((StatusCode::CREATED, Json(todo)))
 }
 
+pub async fn move_todo<T: TodoRepository>(
+    user: AuthUser,
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    ValidatedJson(payload): ValidatedJson<MoveTodo>,
+) -> Result<impl IntoResponse, AppError> {
+    let todo = repository
+        .move_to_project(user.id, id, payload.project_id)
+        .await?;
+    Ok((StatusCode::OK, Json(todo)))
+}
+
+pub async fn reorder_todo<T: TodoRepository>(
+    user: AuthUser,
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    ValidatedJson(payload): ValidatedJson<ReorderTodo>,
+) -> Result<impl IntoResponse, AppError> {
+    let todo = repository.reorder(user.id, id, payload.placement).await?;
+    Ok((StatusCode::OK, Json(todo)))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteTodoQuery {
+    mode: Option<DeleteMode>,
+}
+
 pub async fn delete_todo<T: TodoRepository>(
+    user: AuthUser,
     Path(id): Path<i32>,
+    Query(query): Query<DeleteTodoQuery>,
     Extension(repository): Extension<Arc<T>>,
-) -> StatusCode {
+) -> Result<StatusCode, AppError> {
     repository
-        .delete(id)
-        .await
-        .map(|_| StatusCode::NO_CONTENT)
-        .unwrap_or(StatusCode::NOT_FOUND)
+        .delete(user.id, id, query.mode.unwrap_or_default())
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
 }
@@ -0,0 +1,129 @@
+use axum::{
+    extract::Extension,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::auth::{
+    bearer_token, dummy_password_hash, generate_api_token, generate_token, hash_password,
+    hash_token, verify_password, AuthUser,
+};
+use crate::repositories::user::{
+    ApiToken, CreateApiToken, CreateUser, Login, User, UserRepository,
+};
+
+use super::{AppError, Path, ValidatedJson};
+
+const SESSION_TTL_DAYS: i64 = 30;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionToken {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A new API token; `token` is shown this once and never again.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatedApiToken {
+    pub token: String,
+    #[serde(flatten)]
+    pub api_token: ApiToken,
+}
+
+pub async fn register<T: UserRepository>(
+    Extension(repository): Extension<Arc<T>>,
+    ValidatedJson(payload): ValidatedJson<CreateUser>,
+) -> Result<impl IntoResponse, AppError> {
+    // argon2 is deliberately slow, keep it off the async workers
+    let password_hash = tokio::task::spawn_blocking(move || hash_password(&payload.password))
+        .await
+        .map_err(anyhow::Error::from)??;
+    let user = repository.create(payload.username, password_hash).await?;
+
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+pub async fn login<T: UserRepository>(
+    Extension(repository): Extension<Arc<T>>,
+    ValidatedJson(payload): ValidatedJson<Login>,
+) -> Result<impl IntoResponse, AppError> {
+    let invalid = || AppError::unauthorized("Invalid username or password");
+    let (user, password_hash) = match repository.credentials(payload.username).await? {
+        Some((user, password_hash)) => (Some(user), Some(password_hash)),
+        None => (None, None),
+    };
+    // an unknown username is checked against a dummy hash, taking as long as a known one
+    let verified = tokio::task::spawn_blocking(move || match &password_hash {
+        Some(password_hash) => verify_password(&payload.password, password_hash),
+        None => verify_password(&payload.password, dummy_password_hash()),
+    })
+    .await
+    .map_err(anyhow::Error::from)?;
+    let user = user.filter(|_| verified).ok_or_else(invalid)?;
+
+    let token = generate_token();
+    let expires_at = Utc::now() + Duration::days(SESSION_TTL_DAYS);
+    repository
+        .create_session(user.id, hash_token(&token), expires_at)
+        .await?;
+
+    Ok((StatusCode::OK, Json(SessionToken { token, expires_at })))
+}
+
+pub async fn logout<T: UserRepository>(
+    headers: HeaderMap,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<StatusCode, AppError> {
+    if let Some(token) = bearer_token(&headers) {
+        repository.delete_session(hash_token(token)).await?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn me(user: AuthUser) -> impl IntoResponse {
+    let user = User {
+        id: user.id,
+        username: user.username,
+    };
+    (StatusCode::OK, Json(user))
+}
+
+pub async fn create_token<T: UserRepository>(
+    user: AuthUser,
+    Extension(repository): Extension<Arc<T>>,
+    ValidatedJson(payload): ValidatedJson<CreateApiToken>,
+) -> Result<impl IntoResponse, AppError> {
+    if payload.expires_at.is_some_and(|at| at <= Utc::now()) {
+        return Err(AppError::bad_request("expires_at must be in the future"));
+    }
+    let token = generate_api_token();
+    let api_token = repository
+        .create_token(user.id, hash_token(&token), payload)
+        .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedApiToken { token, api_token }),
+    ))
+}
+
+pub async fn all_tokens<T: UserRepository>(
+    user: AuthUser,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, AppError> {
+    let tokens = repository.tokens(user.id).await?;
+    Ok((StatusCode::OK, Json(tokens)))
+}
+
+pub async fn delete_token<T: UserRepository>(
+    user: AuthUser,
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<StatusCode, AppError> {
+    repository.delete_token(user.id, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
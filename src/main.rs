@@ -1,72 +1,367 @@
+mod auth;
+#[cfg(test)]
+#[cfg(feature = "database-test")]
+mod bench;
+mod config;
 mod handlers;
+mod metrics;
+mod migrations;
 mod repositories;
+mod shutdown;
+mod trace;
 
-use crate::repositories::label::{LabelRepository, LabelRepositoryForDb};
-use crate::repositories::todo::{TodoRepository, TodoRepositoryForDb};
+use crate::repositories::file::{
+    FileStore, LabelRepositoryForFile, ProjectRepositoryForFile, TodoRepositoryForFile,
+    UserRepositoryForFile,
+};
+use crate::repositories::health::{
+    HealthRepository, HealthRepositoryForDb, HealthRepositoryForFile, HealthRepositoryForSqlite,
+};
+use crate::repositories::label::{LabelRepository, LabelRepositoryForDb, LabelRepositoryForSqlite};
+use crate::repositories::measured::Measured;
+use crate::repositories::project::{
+    ProjectRepository, ProjectRepositoryForDb, ProjectRepositoryForSqlite,
+};
+use crate::repositories::todo::{TodoRepository, TodoRepositoryForDb, TodoRepositoryForSqlite};
+use crate::repositories::user::{
+    Scope, UserRepository, UserRepositoryForDb, UserRepositoryForSqlite,
+};
 
+use auth::{authenticate, require_scope, require_session};
 use axum::{
-    extract::Extension,
-    http::HeaderValue,
-    routing::{delete, get, post},
+    extract::{DefaultBodyLimit, Extension},
+    middleware,
+    routing::{delete, get, post, put},
     Router,
 };
+use clap::Parser;
+use config::{Backend, Cli, Command, Config, MigrateAction};
 use dotenv::dotenv;
 use handlers::{
-    label::{all_label, create_label, delete_label},
-    todo::{all_todo, create_todo, delete_todo, find_todo, update_todo},
+    health::{healthz, readyz},
+    label::{all_label, create_label, delete_label, merge_labels, update_label},
+    metrics::metrics,
+    project::{
+        all_project, create_project, delete_project, find_project, project_todos, update_project,
+    },
+    todo::{
+        all_todo, children_todo, create_todo, delete_todo, find_todo, move_todo, reorder_todo,
+        update_todo,
+    },
+    user::{all_tokens, create_token, delete_token, login, logout, me, register},
+};
+use metrics::track_metrics;
+use migrations::Migrated;
+use shutdown::{track_requests, RequestTracker};
+use sqlx::{migrate::Migrate, PgPool, Pool, Postgres, Sqlite};
+use std::{
+    future::{Future, IntoFuture},
+    sync::Arc,
+    time::Duration,
 };
-use hyper::header::CONTENT_TYPE;
-use sqlx::PgPool;
-use std::{env, sync::Arc};
-use tower_http::cors::{Any, CorsLayer};
+use trace::trace_requests;
 
 #[tokio::main]
 async fn main() {
-    // loggingの初期化
-    // let log_level = env::var("RUST_LOG").unwrap_or("info".to_string());
-    // env::set_var("RUST_LOG", log_level);
-    tracing_subscriber::fmt::init();
     dotenv().ok();
+    let cli = Cli::parse();
+    let config = Config::load(&cli).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2)
+    });
+    config.log.init();
 
-    let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+    let database_url = config.database.url.as_deref().unwrap_or_default();
     tracing::debug!("start connect database...");
-    let pool = PgPool::connect(database_url)
+    match config.database.backend() {
+        Backend::Postgres => {
+            let pool = config
+                .database
+                .pool_options::<Postgres>()
+                .connect(database_url)
+                .await
+                .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+            match cli.command {
+                Some(Command::Migrate { action }) => return migrate(&pool, action).await,
+                Some(Command::Claim { username }) => return claim(&pool, &username).await,
+                None => {}
+            }
+            prepare_schema(&config, &pool).await;
+
+            let app = create_app(
+                &config,
+                Measured::new("todo", TodoRepositoryForDb::new(pool.clone()))
+                    .with_pool(pool.clone()),
+                Measured::new("label", LabelRepositoryForDb::new(pool.clone()))
+                    .with_pool(pool.clone()),
+                ProjectRepositoryForDb::new(pool.clone()),
+                UserRepositoryForDb::new(pool.clone()),
+                HealthRepositoryForDb::new(pool.clone()),
+            );
+            serve(&config, app, async move { pool.close().await }).await;
+        }
+        Backend::Sqlite => {
+            let options = config
+                .database
+                .sqlite_options()
+                .unwrap_or_else(|e| panic!("invalid sqlite url [{}]: {}", database_url, e));
+            let pool = config
+                .database
+                .pool_options::<Sqlite>()
+                .connect_with(options)
+                .await
+                .unwrap_or_else(|_| panic!("fail open database, url is [{}]", database_url));
+            match cli.command {
+                Some(Command::Migrate { action }) => return migrate(&pool, action).await,
+                Some(Command::Claim { .. }) => {
+                    println!(
+                        "only Postgres databases predate user accounts, there is nothing to claim"
+                    );
+                    return;
+                }
+                None => {}
+            }
+            prepare_schema(&config, &pool).await;
+
+            let app = create_app(
+                &config,
+                Measured::new("todo", TodoRepositoryForSqlite::new(pool.clone()))
+                    .with_pool(pool.clone()),
+                Measured::new("label", LabelRepositoryForSqlite::new(pool.clone()))
+                    .with_pool(pool.clone()),
+                ProjectRepositoryForSqlite::new(pool.clone()),
+                UserRepositoryForSqlite::new(pool.clone()),
+                HealthRepositoryForSqlite::new(pool.clone()),
+            );
+            serve(&config, app, async move { pool.close().await }).await;
+        }
+        Backend::File => {
+            match cli.command {
+                Some(Command::Migrate { .. }) => {
+                    println!("the file store keeps no schema, there is nothing to migrate");
+                    return;
+                }
+                Some(Command::Claim { .. }) => {
+                    println!(
+                        "only Postgres databases predate user accounts, there is nothing to claim"
+                    );
+                    return;
+                }
+                None => {}
+            }
+            let path = config.database.file_path();
+            let store =
+                FileStore::open(&path, config.database.snapshot_every).unwrap_or_else(|e| {
+                    tracing::error!("fail open {}: {:#}", path.display(), e);
+                    std::process::exit(1)
+                });
+
+            let app = create_app(
+                &config,
+                Measured::new("todo", TodoRepositoryForFile::new(store.clone())),
+                Measured::new("label", LabelRepositoryForFile::new(store.clone())),
+                ProjectRepositoryForFile::new(store.clone()),
+                UserRepositoryForFile::new(store.clone()),
+                HealthRepositoryForFile::new(store.clone()),
+            );
+            serve(&config, app, async move { store.close().await }).await;
+        }
+    }
+}
+
+/// `my_todo migrate <action>`
+async fn migrate<DB>(pool: &Pool<DB>, action: MigrateAction)
+where
+    DB: Migrated,
+    DB::Connection: Migrate,
+{
+    if let Err(e) = migrations::run(pool, action).await {
+        eprintln!("migrate failed: {:#}", e);
+        std::process::exit(1);
+    }
+}
+
+/// `my_todo claim <username>`
+async fn claim(pool: &PgPool, username: &str) {
+    match migrations::claim(pool, username).await {
+        Ok(Some(claimed)) => println!(
+            "{} now owns {} todos, {} labels and {} projects",
+            username, claimed.todos, claimed.labels, claimed.projects
+        ),
+        Ok(None) => println!("there is nothing to claim"),
+        Err(e) => {
+            eprintln!("claim failed: {:#}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Refuses to serve a schema newer than this binary, then applies or reports pending migrations.
+async fn prepare_schema<DB>(config: &Config, pool: &Pool<DB>)
+where
+    DB: Migrated,
+    DB::Connection: Migrate,
+{
+    let status = migrations::ensure_compatible(pool)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::error!("refuse to serve: {:#}", e);
+            std::process::exit(1)
+        });
+    if config.database.migrate_on_startup {
+        for version in migrations::up(pool).await.expect("fail migrate database") {
+            tracing::info!("applied migration {}", version);
+        }
+    } else if !status.pending.is_empty() {
+        tracing::warn!(
+            "migrations {:?} are pending, run `my_todo migrate up`",
+            status.pending
+        );
+    }
+}
+
+/// Serves `app` until a signal, then awaits `close` once the requests drained or the
+/// shutdown deadline passed. `close` must not act before it is polled, unlike `Pool::close`
+/// which shuts the pool on the call.
+async fn serve(config: &Config, app: Router, close: impl Future<Output = ()>) {
+    let tracker = RequestTracker::default();
+    let app = app.layer(middleware::from_fn_with_state(
+        tracker.clone(),
+        track_requests,
+    ));
+    let listener = tokio::net::TcpListener::bind(config.server.listen)
         .await
-        .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
-    let app = create_app(
-        TodoRepositoryForDb::new(pool.clone()),
-        LabelRepositoryForDb::new(pool.clone()),
+        .unwrap_or_else(|e| panic!("fail bind {}: {}", config.server.listen, e));
+    tracing::info!("listening on {}", config.server.listen);
+
+    // stop accepting on a signal, then give in-flight requests until the deadline
+    let (signalled_tx, signalled_rx) = tokio::sync::oneshot::channel();
+    let draining = tracker.clone();
+    let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+        let signal = shutdown::signal().await;
+        tracing::info!(
+            "{} received, draining {} in-flight requests",
+            signal,
+            draining.in_flight()
+        );
+        let _ = signalled_tx.send(());
+    });
+    let deadline = Duration::from_secs(config.server.shutdown_timeout_secs);
+    let deadline_passed = async {
+        match signalled_rx.await {
+            Ok(_) => tokio::time::sleep(deadline).await,
+            Err(_) => std::future::pending().await,
+        }
+    };
+    let abandoned = tokio::select! {
+        res = server.into_future() => {
+            res.expect("fail serve");
+            0
+        }
+        _ = deadline_passed => {
+            tracing::warn!(
+                "shutdown deadline of {:?} passed, abandoning {} requests",
+                deadline,
+                tracker.in_flight()
+            );
+            tracker.in_flight()
+        }
+    };
+
+    close.await;
+    tracing::info!(
+        "shutdown complete: {} requests served, {} abandoned",
+        tracker.completed(),
+        abandoned
     );
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
 }
 
-fn create_app<Todo: TodoRepository, Label: LabelRepository>(
+fn create_app<
+    Todo: TodoRepository,
+    Label: LabelRepository,
+    Project: ProjectRepository,
+    User: UserRepository,
+    Health: HealthRepository,
+>(
+    config: &Config,
     todo_repository: Todo,
     label_repository: Label,
+    project_repository: Project,
+    user_repository: User,
+    health_repository: Health,
 ) -> Router {
-    Router::new()
-        .route("/", get(root))
-        .route("/todos", post(create_todo::<Todo>).get(all_todo::<Todo>))
+    let scoped = |scope: Scope| middleware::from_fn_with_state(scope, require_scope);
+    // API tokens only reach the routes their scopes cover
+    let todos_read = Router::new()
+        .route("/todos", get(all_todo::<Todo>))
+        .route("/todos/:id", get(find_todo::<Todo>))
+        .route("/todos/:id/children", get(children_todo::<Todo>))
+        .route("/projects", get(all_project::<Project>))
+        .route("/projects/:id", get(find_project::<Project>))
+        .route("/projects/:id/todos", get(project_todos::<Project, Todo>))
+        .route_layer(scoped(Scope::TodosRead));
+    let todos_write = Router::new()
+        .route("/todos", post(create_todo::<Todo>))
         .route(
             "/todos/:id",
-            get(find_todo::<Todo>)
-                .delete(delete_todo::<Todo>)
-                .patch(update_todo::<Todo>),
+            delete(delete_todo::<Todo>).patch(update_todo::<Todo>),
         )
+        .route("/todos/:id/project", put(move_todo::<Todo>))
+        .route("/todos/:id/move", post(reorder_todo::<Todo>))
+        .route("/projects", post(create_project::<Project>))
         .route(
-            "/labels",
-            post(create_label::<Label>).get(all_label::<Label>),
+            "/projects/:id",
+            delete(delete_project::<Project>).patch(update_project::<Project>),
         )
-        .route("/labels/:id", delete(delete_label::<Label>))
+        .route_layer(scoped(Scope::TodosWrite));
+    let labels_read = Router::new()
+        .route("/labels", get(all_label::<Label>))
+        .route_layer(scoped(Scope::LabelsRead));
+    let labels_write = Router::new()
+        .route("/labels", post(create_label::<Label>))
+        .route(
+            "/labels/:id",
+            delete(delete_label::<Label>).patch(update_label::<Label>),
+        )
+        .route("/labels/:id/merge", post(merge_labels::<Label>))
+        .route_layer(scoped(Scope::LabelsWrite));
+    // managing tokens takes a session, a token can not mint or revoke tokens
+    let tokens = Router::new()
+        .route(
+            "/tokens",
+            post(create_token::<User>).get(all_tokens::<User>),
+        )
+        .route("/tokens/:id", delete(delete_token::<User>))
+        .route_layer(middleware::from_fn(require_session));
+
+    // everything in here needs a signed in user
+    let protected = Router::new()
+        .merge(todos_read)
+        .merge(todos_write)
+        .merge(labels_read)
+        .merge(labels_write)
+        .merge(tokens)
+        .route("/users/me", get(me))
+        .route("/logout", post(logout::<User>))
+        .route_layer(middleware::from_fn(authenticate::<User>));
+
+    Router::new()
+        .route("/", get(root))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz::<Health, Todo, Label, Project, User>))
+        .route("/metrics", get(metrics::<Health, Todo>))
+        .route("/users", post(register::<User>))
+        .route("/login", post(login::<User>))
+        .merge(protected)
         .layer(Extension(Arc::new(todo_repository)))
         .layer(Extension(Arc::new(label_repository)))
-        .layer(
-            CorsLayer::new()
-                .allow_origin("http://localhost:3001".parse::<HeaderValue>().unwrap())
-                .allow_methods(Any)
-                .allow_headers(vec![CONTENT_TYPE]),
-        )
+        .layer(Extension(Arc::new(project_repository)))
+        .layer(Extension(Arc::new(user_repository)))
+        .layer(Extension(Arc::new(health_repository)))
+        .layer(middleware::from_fn(track_metrics))
+        .layer(middleware::from_fn(trace_requests))
+        .layer(DefaultBodyLimit::max(config.server.body_limit))
+        .layer(config.cors.layer())
 }
 
 async fn root() -> &'static str {
@@ -78,8 +373,15 @@ mod test {
     use self::repositories::label::Label;
 
     use super::*;
+    use crate::handlers::health::Readiness;
+    use crate::repositories::health::test_utils::HealthRepositoryForMemory;
     use crate::repositories::label::test_utils::LabelRepositoryForMemory;
-    use crate::repositories::todo::{test_utils::TodoRepositoryForMemory, CreateTodo, TodoEntity};
+    use crate::repositories::project::{test_utils::ProjectRepositoryForMemory, Project};
+    use crate::repositories::todo::{
+        test_utils::TodoRepositoryForMemory, CreateTodo, Priority, TodoEntity, TodoPage,
+        TodoTreePage,
+    };
+    use crate::repositories::user::{test_utils::UserRepositoryForMemory, User};
 
     use axum::response::Response;
     use axum::{
@@ -89,10 +391,25 @@ mod test {
     };
     use tower::ServiceExt;
 
+    const USER_ID: i32 = 1;
+    const TEST_TOKEN: &str = "test-token";
+
+    /// Users where `USER_ID` is signed in with `TEST_TOKEN`.
+    fn signed_in_users() -> UserRepositoryForMemory {
+        UserRepositoryForMemory::new().with_session(
+            User {
+                id: USER_ID,
+                username: "tester".to_string(),
+            },
+            auth::hash_token(TEST_TOKEN),
+        )
+    }
+
     fn build_todo_req_with_json(path: &str, method: Method, json_body: String) -> Request<Body> {
         Request::builder()
             .uri(path)
             .method(method)
+            .header(header::AUTHORIZATION, format!("Bearer {}", TEST_TOKEN))
             .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
             .body(Body::from(json_body))
             .unwrap()
@@ -102,6 +419,7 @@ mod test {
         Request::builder()
             .uri(path)
             .method(method)
+            .header(header::AUTHORIZATION, format!("Bearer {}", TEST_TOKEN))
             .body(Body::empty())
             .unwrap()
     }
@@ -110,7 +428,7 @@ mod test {
         let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
         let body = String::from_utf8(bytes.to_vec()).unwrap();
         let todo = serde_json::from_str(&body)
-            .expect(&format!("cannot convert Todo instance. body: {}", body));
+            .unwrap_or_else(|_| panic!("cannot convert Todo instance. body: {}", body));
         todo
     }
 
@@ -118,10 +436,17 @@ mod test {
         let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
         let body = String::from_utf8(bytes.to_vec()).unwrap();
         let label = serde_json::from_str(&body)
-            .expect(&format!("cannot convert Label instance. body: {}", body));
+            .unwrap_or_else(|_| panic!("cannot convert Label instance. body: {}", body));
         label
     }
 
+    async fn res_to_error(res: Response) -> serde_json::Value {
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(bytes.to_vec()).unwrap();
+        serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("cannot convert error body. body: {}", body))
+    }
+
     fn labels_values_tuple() -> (Vec<i32>, Vec<Label>) {
         let id = 1;
         (
@@ -144,10 +469,17 @@ mod test {
             Method::POST,
             r#"{ "text": "should_return_created_todo", "labels": [1] }"#.to_string(),
         );
-        let res = create_app(todo_repository, label_repository)
-            .oneshot(req)
-            .await
-            .unwrap();
+        let res = create_app(
+            &Config::default(),
+            todo_repository,
+            label_repository,
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
         let todo = res_to_todo(res).await;
         assert_eq!(expected, todo);
     }
@@ -169,14 +501,24 @@ mod test {
         let todo_repository = TodoRepositoryForMemory::new(labels.clone());
         let label_repository = LabelRepositoryForMemory::new();
         todo_repository
-            .create(CreateTodo::new("should_find_todo".to_string(), label_id))
+            .create(
+                USER_ID,
+                CreateTodo::new("should_find_todo".to_string(), label_id),
+            )
             .await
             .expect("failed create todo");
         let req = build_todo_req_with_empty(Method::GET, "/todos/1");
-        let res = create_app(todo_repository, label_repository)
-            .oneshot(req)
-            .await
-            .unwrap();
+        let res = create_app(
+            &Config::default(),
+            todo_repository,
+            label_repository,
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
         let todo = res_to_todo(res).await;
         assert_eq!(expected, todo);
     }
@@ -188,22 +530,236 @@ mod test {
         let todo_repository = TodoRepositoryForMemory::new(labels.clone());
         let label_repository = LabelRepositoryForMemory::new();
         todo_repository
-            .create(CreateTodo::new(
-                "should_get_all_todos".to_string(),
-                label_id,
-            ))
+            .create(
+                USER_ID,
+                CreateTodo::new("should_get_all_todos".to_string(), label_id),
+            )
             .await
             .expect("failed create todo");
         let req = build_todo_req_with_empty(Method::GET, "/todos");
-        let res = create_app(todo_repository, label_repository)
-            .oneshot(req)
-            .await
-            .unwrap();
+        let res = create_app(
+            &Config::default(),
+            todo_repository,
+            label_repository,
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
         let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
         let body = String::from_utf8(bytes.to_vec()).unwrap();
-        let todo: Vec<TodoEntity> = serde_json::from_str(&body)
-            .expect(&format!("cannot convert Todo instance. body: {}", body));
-        assert_eq!(vec![expected], todo);
+        let page: TodoPage = serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("cannot convert Todo instance. body: {}", body));
+        assert_eq!(vec![expected], page.items);
+        assert_eq!(None, page.next_cursor);
+    }
+
+    #[tokio::test]
+    async fn should_paginate_todos() {
+        let todo_repository = TodoRepositoryForMemory::new(vec![]);
+        let label_repository = LabelRepositoryForMemory::new();
+        for text in ["first", "second", "third"] {
+            todo_repository
+                .create(USER_ID, CreateTodo::new(text.to_string(), vec![]))
+                .await
+                .expect("failed create todo");
+        }
+        let app = create_app(
+            &Config::default(),
+            todo_repository,
+            label_repository,
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        );
+
+        let req = build_todo_req_with_empty(Method::GET, "/todos?sort=text&order=asc&limit=2");
+        let res = app.clone().oneshot(req).await.unwrap();
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let page: TodoPage = serde_json::from_slice(&bytes).unwrap();
+        let texts: Vec<_> = page.items.iter().map(|todo| todo.text.as_str()).collect();
+        assert_eq!(vec!["first", "second"], texts);
+
+        let path = format!(
+            "/todos?sort=text&order=asc&limit=2&cursor={}",
+            page.next_cursor.unwrap()
+        );
+        let req = build_todo_req_with_empty(Method::GET, &path);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let page: TodoPage = serde_json::from_slice(&bytes).unwrap();
+        let texts: Vec<_> = page.items.iter().map(|todo| todo.text.as_str()).collect();
+        assert_eq!(vec!["third"], texts);
+        assert_eq!(None, page.next_cursor);
+
+        let req = build_todo_req_with_empty(Method::GET, "/todos?cursor=broken");
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_get_overdue_todos() {
+        let todo_repository = TodoRepositoryForMemory::new(vec![]);
+        let label_repository = LabelRepositoryForMemory::new();
+        let app = create_app(
+            &Config::default(),
+            todo_repository,
+            label_repository,
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        );
+        for (text, due_at) in [
+            ("overdue", "2000-01-01T00:00:00Z"),
+            ("upcoming", "2999-01-01T00:00:00Z"),
+        ] {
+            let req = build_todo_req_with_json(
+                "/todos",
+                Method::POST,
+                format!(
+                    r#"{{ "text": "{}", "labels": [], "due_at": "{}" }}"#,
+                    text, due_at
+                ),
+            );
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::CREATED, res.status());
+        }
+
+        let req = build_todo_req_with_empty(Method::GET, "/todos?due=overdue");
+        let res = app.clone().oneshot(req).await.unwrap();
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let page: TodoPage = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(1, page.items.len());
+        assert_eq!("overdue", page.items[0].text);
+
+        let req = build_todo_req_with_empty(Method::GET, "/todos?due=within");
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_sort_todos_smartly() {
+        let app = create_app(
+            &Config::default(),
+            TodoRepositoryForMemory::new(vec![]),
+            LabelRepositoryForMemory::new(),
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        );
+        for body in [
+            r#"{ "text": "someday", "labels": [] }"#,
+            r#"{ "text": "urgent", "labels": [], "priority": "urgent" }"#,
+            r#"{ "text": "high later", "labels": [], "priority": "high", "due_at": "2999-01-02T00:00:00Z" }"#,
+            r#"{ "text": "high soon", "labels": [], "priority": "high", "due_at": "2999-01-01T00:00:00Z" }"#,
+        ] {
+            let req = build_todo_req_with_json("/todos", Method::POST, body.to_string());
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::CREATED, res.status());
+        }
+        let req = build_todo_req_with_json(
+            "/todos/2",
+            Method::PATCH,
+            r#"{ "completed": true }"#.to_string(),
+        );
+        let todo = res_to_todo(app.clone().oneshot(req).await.unwrap()).await;
+        assert_eq!(Priority::Urgent, todo.priority);
+
+        // most pressing first unless the order says otherwise
+        for (path, expected) in [
+            (
+                "/todos?sort=smart",
+                vec!["high soon", "high later", "someday", "urgent"],
+            ),
+            (
+                "/todos?sort=smart&order=desc",
+                vec!["urgent", "someday", "high later", "high soon"],
+            ),
+        ] {
+            let req = build_todo_req_with_empty(Method::GET, path);
+            let res = app.clone().oneshot(req).await.unwrap();
+            let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+            let page: TodoPage = serde_json::from_slice(&bytes).unwrap();
+            let texts: Vec<_> = page.items.iter().map(|todo| todo.text.as_str()).collect();
+            assert_eq!(expected, texts);
+        }
+
+        let req = build_todo_req_with_json(
+            "/todos",
+            Method::POST,
+            r#"{ "text": "asap", "labels": [], "priority": "asap" }"#.to_string(),
+        );
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_reorder_todos() {
+        let todo_repository = TodoRepositoryForMemory::new(vec![]);
+        for text in ["first", "second", "third"] {
+            todo_repository
+                .create(USER_ID, CreateTodo::new(text.to_string(), vec![]))
+                .await
+                .expect("failed create todo");
+        }
+        todo_repository
+            .create(
+                USER_ID,
+                CreateTodo::new("elsewhere".to_string(), vec![]).in_project(2),
+            )
+            .await
+            .expect("failed create todo");
+        let app = create_app(
+            &Config::default(),
+            todo_repository,
+            LabelRepositoryForMemory::new(),
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        );
+
+        for (id, body, expected) in [
+            (3, r#"{ "before": 1 }"#, vec!["third", "first", "second"]),
+            (3, r#"{ "after": 2 }"#, vec!["first", "second", "third"]),
+            (1, r#"{ "index": 1 }"#, vec!["second", "first", "third"]),
+        ] {
+            let req = build_todo_req_with_json(
+                &format!("/todos/{}/move", id),
+                Method::POST,
+                body.to_string(),
+            );
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::OK, res.status());
+            assert_eq!(id, res_to_todo(res).await.id);
+
+            let req = build_todo_req_with_empty(Method::GET, "/projects/1/todos?sort=position");
+            let res = app.clone().oneshot(req).await.unwrap();
+            let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+            let page: TodoPage = serde_json::from_slice(&bytes).unwrap();
+            let texts: Vec<_> = page.items.iter().map(|todo| todo.text.as_str()).collect();
+            assert_eq!(expected, texts);
+        }
+
+        let req = build_todo_req_with_json(
+            "/todos/1/move",
+            Method::POST,
+            r#"{ "before": 4 }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+        let body = res_to_error(res).await;
+        assert_eq!("invalid_placement", body["code"]);
+        assert_eq!(4, body["details"]["todo_id"]);
+
+        let req = build_todo_req_with_json(
+            "/todos/1/move",
+            Method::POST,
+            r#"{ "below": 2 }"#.to_string(),
+        );
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
     }
 
     #[tokio::test]
@@ -213,7 +769,10 @@ mod test {
         let todo_repository = TodoRepositoryForMemory::new(labels.clone());
         let label_repository = LabelRepositoryForMemory::new();
         todo_repository
-            .create(CreateTodo::new("should_update_todo".to_string(), label_id))
+            .create(
+                USER_ID,
+                CreateTodo::new("should_update_todo".to_string(), label_id),
+            )
             .await
             .expect("failed create todo");
         let req = build_todo_req_with_json(
@@ -221,10 +780,17 @@ mod test {
             Method::PATCH,
             r#"{"id": 1, "text": "should_update_todo", "completed": false}"#.to_string(),
         );
-        let res = create_app(todo_repository, label_repository)
-            .oneshot(req)
-            .await
-            .unwrap();
+        let res = create_app(
+            &Config::default(),
+            todo_repository,
+            label_repository,
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
         let todo = res_to_todo(res).await;
         assert_eq!(expected, todo);
     }
@@ -235,14 +801,24 @@ mod test {
         let todo_repository = TodoRepositoryForMemory::new(labels.clone());
         let label_repository = LabelRepositoryForMemory::new();
         todo_repository
-            .create(CreateTodo::new("should_delete_todo".to_string(), label_id))
+            .create(
+                USER_ID,
+                CreateTodo::new("should_delete_todo".to_string(), label_id),
+            )
             .await
             .expect("failed delete todo");
         let req = build_todo_req_with_empty(Method::DELETE, "/todos/1");
-        let res = create_app(todo_repository, label_repository)
-            .oneshot(req)
-            .await
-            .unwrap();
+        let res = create_app(
+            &Config::default(),
+            todo_repository,
+            label_repository,
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
         assert_eq!(StatusCode::NO_CONTENT, res.status());
     }
 
@@ -256,10 +832,17 @@ mod test {
             Method::POST,
             r#"{ "name": "should_create_label" }"#.to_string(),
         );
-        let res = create_app(todo_repository, label_repository)
-            .oneshot(req)
-            .await
-            .unwrap();
+        let res = create_app(
+            &Config::default(),
+            todo_repository,
+            label_repository,
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
         let label = res_to_label(res).await;
         assert_eq!(expected, label);
     }
@@ -270,35 +853,909 @@ mod test {
         let todo_repository = TodoRepositoryForMemory::new(vec![expected.clone()]);
         let label_repository = LabelRepositoryForMemory::new();
         label_repository
-            .create("should_create_label".to_string())
+            .create(USER_ID, "should_create_label".to_string())
             .await
             .expect("failed all label");
         let req = build_todo_req_with_empty(Method::GET, "/labels");
-        let res = create_app(todo_repository, label_repository)
-            .oneshot(req)
-            .await
-            .unwrap();
+        let res = create_app(
+            &Config::default(),
+            todo_repository,
+            label_repository,
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
         let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
         let body = String::from_utf8(bytes.to_vec()).unwrap();
         let label: Vec<Label> = serde_json::from_str(&body)
-            .expect(&format!("cannot convert Label instance. body: {}", body));
+            .unwrap_or_else(|_| panic!("cannot convert Label instance. body: {}", body));
         assert_eq!(vec![expected], label);
     }
 
+    #[tokio::test]
+    async fn should_update_label() {
+        let expected = Label::new(1, "should_update_label".to_string());
+        let todo_repository = TodoRepositoryForMemory::new(vec![expected.clone()]);
+        let label_repository = LabelRepositoryForMemory::new();
+        label_repository
+            .create(USER_ID, "should_create_label".to_string())
+            .await
+            .expect("failed create label");
+        let req = build_todo_req_with_json(
+            "/labels/1",
+            Method::PATCH,
+            r#"{ "name": "should_update_label" }"#.to_string(),
+        );
+        let res = create_app(
+            &Config::default(),
+            todo_repository,
+            label_repository,
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let label = res_to_label(res).await;
+        assert_eq!(expected, label);
+    }
+
     #[tokio::test]
     async fn should_delete_label() {
         let label = Label::new(1, "should_create_label".to_string());
         let todo_repository = TodoRepositoryForMemory::new(vec![label.clone()]);
         let label_repository = LabelRepositoryForMemory::new();
         label_repository
-            .create("should_create_label".to_string())
+            .create(USER_ID, "should_create_label".to_string())
             .await
             .expect("failed all label");
         let req = build_todo_req_with_empty(Method::DELETE, "/labels/1");
-        let res = create_app(todo_repository, label_repository)
-            .oneshot(req)
+        let res = create_app(
+            &Config::default(),
+            todo_repository,
+            label_repository,
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_restrict_preview_and_detach_label_in_use() {
+        let label_repository = LabelRepositoryForMemory::new();
+        let todo_repository = TodoRepositoryForMemory::with_labels(label_repository.clone());
+        let label = label_repository
+            .create(USER_ID, "in use".to_string())
+            .await
+            .unwrap();
+        let todo = todo_repository
+            .create(
+                USER_ID,
+                CreateTodo::new("labelled".to_string(), vec![label.id]),
+            )
             .await
             .unwrap();
+        let app = create_app(
+            &Config::default(),
+            todo_repository,
+            label_repository,
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        );
+        let path = format!("/labels/{}", label.id);
+
+        for query in ["?mode=restrict", "?mode=restrict&dry_run=true"] {
+            let req = build_todo_req_with_empty(Method::DELETE, &format!("{path}{query}"));
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::CONFLICT, res.status());
+            let error = res_to_error(res).await;
+            assert_eq!("in_use", error["code"]);
+            assert_eq!(serde_json::json!([todo.id]), error["details"]["todos"]);
+        }
+
+        let req = build_todo_req_with_empty(Method::DELETE, &format!("{path}?dry_run=true"));
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let preview = res_to_error(res).await;
+        assert_eq!(serde_json::json!(1), preview["affected_todos"]);
+
+        let req = build_todo_req_with_empty(Method::DELETE, &path);
+        let res = app.clone().oneshot(req).await.unwrap();
         assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let req = build_todo_req_with_empty(Method::GET, &format!("/todos/{}", todo.id));
+        let res = app.oneshot(req).await.unwrap();
+        assert!(res_to_todo(res).await.labels.is_empty());
+    }
+
+    #[tokio::test]
+    async fn should_merge_labels() {
+        let label_repository = LabelRepositoryForMemory::new();
+        let todo_repository = TodoRepositoryForMemory::with_labels(label_repository.clone());
+        let mut ids = Vec::new();
+        for name in ["bug", "buggy", "bugs"] {
+            let label = label_repository
+                .create(USER_ID, name.to_string())
+                .await
+                .unwrap();
+            ids.push(label.id);
+        }
+        for labels in [vec![ids[1]], vec![ids[0], ids[2]], vec![]] {
+            todo_repository
+                .create(USER_ID, CreateTodo::new("todo".to_string(), labels))
+                .await
+                .unwrap();
+        }
+        let app = create_app(
+            &Config::default(),
+            todo_repository,
+            label_repository,
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        );
+        let path = format!("/labels/{}/merge", ids[0]);
+
+        let req = build_todo_req_with_json(&path, Method::POST, r#"{ "sources": [] }"#.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+
+        let req = build_todo_req_with_json(
+            &path,
+            Method::POST,
+            format!(r#"{{ "sources": [{}, {}] }}"#, ids[1], ids[2]),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let merged = res_to_error(res).await;
+        assert_eq!(serde_json::json!(2), merged["affected_todos"]);
+
+        let req = build_todo_req_with_empty(Method::GET, "/labels");
+        let res = app.oneshot(req).await.unwrap();
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let labels: Vec<Label> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(vec![Label::new(ids[0], "bug".to_string())], labels);
+    }
+
+    #[tokio::test]
+    async fn should_return_not_found_error() {
+        let todo_repository = TodoRepositoryForMemory::new(vec![]);
+        let label_repository = LabelRepositoryForMemory::new();
+        let app = create_app(
+            &Config::default(),
+            todo_repository,
+            label_repository,
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        );
+
+        let req = build_todo_req_with_empty(Method::GET, "/todos/99");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        let error = res_to_error(res).await;
+        assert_eq!("not_found", error["code"]);
+        assert_eq!(99, error["details"]["id"]);
+
+        let req = build_todo_req_with_empty(Method::DELETE, "/labels/99");
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_return_conflict_on_duplicate_label() {
+        let todo_repository = TodoRepositoryForMemory::new(vec![]);
+        let label_repository = LabelRepositoryForMemory::new();
+        label_repository
+            .create(USER_ID, "duplicate".to_string())
+            .await
+            .expect("failed create label");
+        let req = build_todo_req_with_json(
+            "/labels",
+            Method::POST,
+            r#"{ "name": "duplicate" }"#.to_string(),
+        );
+        let res = create_app(
+            &Config::default(),
+            todo_repository,
+            label_repository,
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+        let error = res_to_error(res).await;
+        assert_eq!("duplicate", error["code"]);
+        assert_eq!(1, error["details"]["id"]);
+    }
+
+    #[tokio::test]
+    async fn should_reject_unknown_labels() {
+        let (label_id, labels) = labels_values_tuple();
+        let todo_repository = TodoRepositoryForMemory::new(labels);
+        let todo = todo_repository
+            .create(USER_ID, CreateTodo::new("todo".to_string(), label_id))
+            .await
+            .unwrap();
+        let app = create_app(
+            &Config::default(),
+            todo_repository,
+            LabelRepositoryForMemory::new(),
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        );
+        let expected = serde_json::json!(["Label 7 does not exist", "Label 9 does not exist"]);
+
+        for (path, method, body) in [
+            (
+                "/todos".to_string(),
+                Method::POST,
+                r#"{ "text": "todo", "labels": [7, 1, 9] }"#,
+            ),
+            (
+                format!("/todos/{}", todo.id),
+                Method::PATCH,
+                r#"{ "labels": [7, 1, 9] }"#,
+            ),
+        ] {
+            let req = build_todo_req_with_json(&path, method, body.to_string());
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+            let error = res_to_error(res).await;
+            assert_eq!("validation_error", error["code"]);
+            assert_eq!(expected, error["details"]["labels"]);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_return_validation_error() {
+        let todo_repository = TodoRepositoryForMemory::new(vec![]);
+        let label_repository = LabelRepositoryForMemory::new();
+        let app = create_app(
+            &Config::default(),
+            todo_repository,
+            label_repository,
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        );
+
+        let req = build_todo_req_with_json(
+            "/todos",
+            Method::POST,
+            r#"{ "text": "", "labels": [] }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+        let error = res_to_error(res).await;
+        assert_eq!("validation_error", error["code"]);
+        assert_eq!("Can not be empty", error["details"]["text"][0]);
+
+        let req = build_todo_req_with_json("/todos", Method::POST, "{".to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let error = res_to_error(res).await;
+        assert_eq!("invalid_json", error["code"]);
+
+        let req = build_todo_req_with_empty(Method::GET, "/todos/one");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let error = res_to_error(res).await;
+        assert_eq!("invalid_path", error["code"]);
+
+        let req = build_todo_req_with_empty(Method::GET, "/todos?sort=size");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let error = res_to_error(res).await;
+        assert_eq!("invalid_query", error["code"]);
+
+        let req = build_todo_req_with_empty(Method::DELETE, "/labels/1?mode=later");
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let error = res_to_error(res).await;
+        assert_eq!("invalid_query", error["code"]);
+    }
+
+    #[tokio::test]
+    async fn should_handle_subtasks() {
+        let todo_repository = TodoRepositoryForMemory::new(vec![]);
+        let label_repository = LabelRepositoryForMemory::new();
+        let app = create_app(
+            &Config::default(),
+            todo_repository,
+            label_repository,
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        );
+        for body in [
+            r#"{ "text": "parent", "labels": [] }"#,
+            r#"{ "text": "child", "labels": [], "parent_id": 1 }"#,
+            r#"{ "text": "grandchild", "labels": [], "parent_id": 2 }"#,
+        ] {
+            let req = build_todo_req_with_json("/todos", Method::POST, body.to_string());
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::CREATED, res.status());
+        }
+
+        let req = build_todo_req_with_empty(Method::GET, "/todos/1/children");
+        let res = app.clone().oneshot(req).await.unwrap();
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let children: Vec<TodoEntity> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            vec![2],
+            children.iter().map(|todo| todo.id).collect::<Vec<_>>()
+        );
+
+        let req = build_todo_req_with_empty(Method::GET, "/todos?view=tree");
+        let res = app.clone().oneshot(req).await.unwrap();
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let tree: TodoTreePage = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(1, tree.items.len());
+        assert_eq!("parent", tree.items[0].todo.text);
+        assert_eq!(
+            "grandchild",
+            tree.items[0].children[0].children[0].todo.text
+        );
+
+        let req = build_todo_req_with_empty(Method::DELETE, "/todos/1");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+        let error = res_to_error(res).await;
+        assert_eq!(serde_json::json!([2]), error["details"]["children"]);
+
+        let req = build_todo_req_with_empty(Method::DELETE, "/todos/1?mode=cascade");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let req = build_todo_req_with_empty(Method::GET, "/todos/3");
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_handle_projects() {
+        let todo_repository = TodoRepositoryForMemory::new(vec![]);
+        let project_repository = ProjectRepositoryForMemory::with_todos(todo_repository.todos());
+        let app = create_app(
+            &Config::default(),
+            todo_repository,
+            LabelRepositoryForMemory::new(),
+            project_repository,
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        );
+        let req = build_todo_req_with_json(
+            "/projects",
+            Method::POST,
+            r#"{ "name": "work" }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let project: Project = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(Project::new(2, "work".to_string()), project);
+
+        let req = build_todo_req_with_json(
+            "/projects/2",
+            Method::PATCH,
+            r#"{ "name": "Inbox" }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+
+        for body in [
+            r#"{ "text": "inbox todo", "labels": [] }"#,
+            r#"{ "text": "work todo", "labels": [], "project_id": 2 }"#,
+        ] {
+            let req = build_todo_req_with_json("/todos", Method::POST, body.to_string());
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::CREATED, res.status());
+        }
+
+        let req = build_todo_req_with_json(
+            "/todos/1/project",
+            Method::PUT,
+            r#"{ "project_id": 2 }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!(2, res_to_todo(res).await.project_id);
+
+        let req = build_todo_req_with_empty(Method::GET, "/projects/2/todos?sort=text&order=asc");
+        let res = app.clone().oneshot(req).await.unwrap();
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let page: TodoPage = serde_json::from_slice(&bytes).unwrap();
+        let texts: Vec<&str> = page.items.iter().map(|todo| todo.text.as_str()).collect();
+        assert_eq!(vec!["inbox todo", "work todo"], texts);
+        // the moved todo goes last
+        let req = build_todo_req_with_empty(Method::GET, "/projects/2/todos?sort=position");
+        let res = app.clone().oneshot(req).await.unwrap();
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let page: TodoPage = serde_json::from_slice(&bytes).unwrap();
+        let texts: Vec<&str> = page.items.iter().map(|todo| todo.text.as_str()).collect();
+        assert_eq!(vec!["work todo", "inbox todo"], texts);
+
+        let req = build_todo_req_with_empty(Method::GET, "/projects/3/todos");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        let req = build_todo_req_with_empty(Method::DELETE, "/projects/1");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+        assert_eq!("protected", res_to_error(res).await["code"]);
+
+        let req = build_todo_req_with_empty(Method::DELETE, "/projects/2");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+        let error = res_to_error(res).await;
+        assert_eq!("not_empty", error["code"]);
+        assert_eq!(serde_json::json!([1, 2]), error["details"]["todos"]);
+
+        for id in [1, 2] {
+            let req = build_todo_req_with_empty(Method::DELETE, &format!("/todos/{}", id));
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::NO_CONTENT, res.status());
+        }
+        let req = build_todo_req_with_empty(Method::DELETE, "/projects/2");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let req = build_todo_req_with_empty(Method::GET, "/projects");
+        let res = app.oneshot(req).await.unwrap();
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let projects: Vec<Project> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(vec![Project::new(1, "Inbox".to_string())], projects);
+    }
+
+    #[tokio::test]
+    async fn should_require_sign_in() {
+        let app = create_app(
+            &Config::default(),
+            TodoRepositoryForMemory::new(vec![]),
+            LabelRepositoryForMemory::new(),
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        );
+
+        let req = Request::builder()
+            .uri("/todos")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+        assert_eq!("unauthorized", res_to_error(res).await["code"]);
+
+        let req = Request::builder()
+            .uri("/todos")
+            .header(header::AUTHORIZATION, "Bearer wrong-token")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_register_login_and_logout() {
+        let app = create_app(
+            &Config::default(),
+            TodoRepositoryForMemory::new(vec![]),
+            LabelRepositoryForMemory::new(),
+            ProjectRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            HealthRepositoryForMemory::new(),
+        );
+        let credentials = r#"{ "username": "alice", "password": "correct horse" }"#;
+
+        let req = build_todo_req_with_json("/users", Method::POST, credentials.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let req = build_todo_req_with_json("/users", Method::POST, credentials.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+
+        let req = build_todo_req_with_json(
+            "/login",
+            Method::POST,
+            r#"{ "username": "alice", "password": "wrong horse" }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+
+        let req = build_todo_req_with_json("/login", Method::POST, credentials.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let session: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let bearer = format!("Bearer {}", session["token"].as_str().unwrap());
+
+        let req = Request::builder()
+            .uri("/users/me")
+            .header(header::AUTHORIZATION, &bearer)
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let user: User = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!("alice", user.username);
+
+        let req = Request::builder()
+            .uri("/logout")
+            .method(Method::POST)
+            .header(header::AUTHORIZATION, &bearer)
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+
+        let req = Request::builder()
+            .uri("/users/me")
+            .header(header::AUTHORIZATION, &bearer)
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_hide_todos_of_other_users() {
+        let todo_repository = TodoRepositoryForMemory::new(vec![]);
+        todo_repository
+            .create(USER_ID + 1, CreateTodo::new("not mine".to_string(), vec![]))
+            .await
+            .expect("failed create todo");
+        let app = create_app(
+            &Config::default(),
+            todo_repository,
+            LabelRepositoryForMemory::new(),
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        );
+
+        let req = build_todo_req_with_empty(Method::GET, "/todos/1");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        let req = build_todo_req_with_empty(Method::GET, "/todos");
+        let res = app.oneshot(req).await.unwrap();
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let page: TodoPage = serde_json::from_slice(&bytes).unwrap();
+        assert!(page.items.is_empty());
+    }
+
+    #[tokio::test]
+    async fn should_enforce_api_token_scopes() {
+        let app = create_app(
+            &Config::default(),
+            TodoRepositoryForMemory::new(vec![]),
+            LabelRepositoryForMemory::new(),
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        );
+        let with_token = |method: Method, path: &str, token: &str, json_body: &str| {
+            Request::builder()
+                .uri(path)
+                .method(method)
+                .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(json_body.to_string()))
+                .unwrap()
+        };
+
+        let req = build_todo_req_with_json(
+            "/tokens",
+            Method::POST,
+            r#"{ "name": "ci", "scopes": ["todos:read"] }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let created: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let token = created["token"].as_str().unwrap().to_string();
+        assert_eq!(serde_json::json!(["todos:read"]), created["scopes"]);
+
+        let req = with_token(Method::GET, "/todos", &token, "");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        let req = with_token(
+            Method::POST,
+            "/todos",
+            &token,
+            r#"{ "text": "from ci", "labels": [] }"#,
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, res.status());
+        let error = res_to_error(res).await;
+        assert_eq!("forbidden", error["code"]);
+        assert_eq!("todos:write", error["details"]["scope"]);
+
+        let req = with_token(Method::GET, "/labels", &token, "");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, res.status());
+
+        let req = with_token(
+            Method::POST,
+            "/tokens",
+            &token,
+            r#"{ "name": "escalate", "scopes": ["labels:write"] }"#,
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, res.status());
+
+        let req = build_todo_req_with_empty(Method::GET, "/tokens");
+        let res = app.clone().oneshot(req).await.unwrap();
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let tokens: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(1, tokens.as_array().unwrap().len());
+        assert!(tokens[0]["last_used_at"].is_string());
+        assert!(tokens[0].get("token").is_none());
+
+        let path = format!("/tokens/{}", created["id"]);
+        let req = build_todo_req_with_empty(Method::DELETE, &path);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let req = with_token(Method::GET, "/todos", &token, "");
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_report_health_and_readiness() {
+        let app = |health| {
+            create_app(
+                &Config::default(),
+                TodoRepositoryForMemory::new(vec![]),
+                LabelRepositoryForMemory::new(),
+                ProjectRepositoryForMemory::new(),
+                UserRepositoryForMemory::new(),
+                health,
+            )
+        };
+        let probe = |path: &str| Request::builder().uri(path).body(Body::empty()).unwrap();
+
+        let res = app(HealthRepositoryForMemory::new())
+            .oneshot(probe("/healthz"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        let res = app(HealthRepositoryForMemory::new())
+            .oneshot(probe("/readyz"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let readiness: Readiness = serde_json::from_slice(&bytes).unwrap();
+        assert!(readiness.ready);
+        assert_eq!(
+            vec!["database", "migrations", "repositories"],
+            readiness.checks.keys().collect::<Vec<_>>()
+        );
+
+        let res = app(HealthRepositoryForMemory::failing("connection refused"))
+            .oneshot(probe("/readyz"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, res.status());
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let readiness: Readiness = serde_json::from_slice(&bytes).unwrap();
+        assert!(!readiness.ready);
+        let database = &readiness.checks["database"];
+        assert!(!database.ok);
+        // the cause is only logged
+        assert_eq!(Some("failed"), database.error.as_deref());
+        assert!(readiness.checks["repositories"].ok);
+    }
+
+    #[tokio::test]
+    async fn should_export_metrics() {
+        let (label_ids, labels) = labels_values_tuple();
+        let todo_repository = TodoRepositoryForMemory::new(labels);
+        todo_repository
+            .create(USER_ID, CreateTodo::new("measured".to_string(), label_ids))
+            .await
+            .expect("failed create todo");
+        let pool = sqlx::SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let app = create_app(
+            &Config::default(),
+            // any pool will do to time its checkouts
+            Measured::new("todo", todo_repository).with_pool(pool),
+            LabelRepositoryForMemory::new(),
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        );
+
+        let req = build_todo_req_with_empty(Method::GET, "/todos/1");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        let req = Request::builder()
+            .uri("/metrics")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(bytes.to_vec()).unwrap();
+        assert!(
+            body.contains(r#"http_requests_total{method="GET",route="/todos/:id",status="2xx"}"#)
+        );
+        assert!(body
+            .contains(r#"http_request_duration_seconds_bucket{method="GET",route="/todos/:id""#));
+        assert!(body.contains(
+            r#"repository_query_duration_seconds_count{method="find",repository="todo"}"#
+        ));
+        assert!(body.contains(r#"db_pool_acquire_duration_seconds_count{repository="todo"}"#));
+        assert!(body.contains(r#"todos{state="open"}"#));
+        assert!(body.contains(r#"todos_by_label{label="1"} 1"#));
+        assert!(body.contains(r#"todos_by_label{label="other"} 0"#));
+        assert!(!body.contains("label_id"));
+    }
+
+    #[tokio::test]
+    async fn should_return_request_id() {
+        let app = create_app(
+            &Config::default(),
+            TodoRepositoryForMemory::new(vec![]),
+            LabelRepositoryForMemory::new(),
+            ProjectRepositoryForMemory::new(),
+            signed_in_users(),
+            HealthRepositoryForMemory::new(),
+        );
+
+        let req = Request::builder()
+            .uri("/todos")
+            .header("x-request-id", "deploy-42")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+        assert_eq!("deploy-42", res.headers()["x-request-id"]);
+
+        let req = build_todo_req_with_empty(Method::GET, "/todos");
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!(32, res.headers()["x-request-id"].len());
+    }
+
+    #[tokio::test]
+    async fn should_serve_from_sqlite() {
+        let pool = crate::migrations::test_utils::sqlite_pool().await;
+        let app = create_app(
+            &Config::default(),
+            TodoRepositoryForSqlite::new(pool.clone()),
+            LabelRepositoryForSqlite::new(pool.clone()),
+            ProjectRepositoryForSqlite::new(pool.clone()),
+            UserRepositoryForSqlite::new(pool.clone()),
+            HealthRepositoryForSqlite::new(pool),
+        );
+        let credentials = r#"{ "username": "alice", "password": "correct horse" }"#;
+        let req = build_todo_req_with_json("/users", Method::POST, credentials.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let req = build_todo_req_with_json("/login", Method::POST, credentials.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let session: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let bearer = format!("Bearer {}", session["token"].as_str().unwrap());
+        let request = |method: Method, path: &str, json_body: &str| {
+            Request::builder()
+                .uri(path)
+                .method(method)
+                .header(header::AUTHORIZATION, &bearer)
+                .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(json_body.to_string()))
+                .unwrap()
+        };
+
+        let req = request(Method::POST, "/labels", r#"{ "name": "home" }"#);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let label = res_to_label(res).await;
+        let req = request(
+            Method::POST,
+            "/todos",
+            &format!(r#"{{ "text": "water plants", "labels": [{}] }}"#, label.id),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let todo = res_to_todo(res).await;
+        assert_eq!(vec![label], todo.labels);
+
+        let req = request(Method::GET, "/todos", "");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let page: TodoPage = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(vec![todo], page.items);
+
+        let req = Request::builder()
+            .uri("/readyz")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_serve_from_file_store_across_restarts() {
+        let dir = crate::repositories::file::test_utils::TempDir::new("serve");
+        let app_on = |store: FileStore| {
+            create_app(
+                &Config::default(),
+                TodoRepositoryForFile::new(store.clone()),
+                LabelRepositoryForFile::new(store.clone()),
+                ProjectRepositoryForFile::new(store.clone()),
+                UserRepositoryForFile::new(store.clone()),
+                HealthRepositoryForFile::new(store),
+            )
+        };
+        let app = app_on(FileStore::open(&dir, 100).unwrap());
+        let credentials = r#"{ "username": "alice", "password": "correct horse" }"#;
+        let req = build_todo_req_with_json("/users", Method::POST, credentials.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let req = build_todo_req_with_json("/login", Method::POST, credentials.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let session: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let bearer = format!("Bearer {}", session["token"].as_str().unwrap());
+        let request = |method: Method, path: &str, json_body: &str| {
+            Request::builder()
+                .uri(path)
+                .method(method)
+                .header(header::AUTHORIZATION, &bearer)
+                .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(json_body.to_string()))
+                .unwrap()
+        };
+
+        let req = request(Method::POST, "/labels", r#"{ "name": "home" }"#);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let label = res_to_label(res).await;
+        let req = request(
+            Method::POST,
+            "/todos",
+            &format!(r#"{{ "text": "water plants", "labels": [{}] }}"#, label.id),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let todo = res_to_todo(res).await;
+        assert_eq!(vec![label], todo.labels);
+
+        // the session and the todo outlive the process
+        drop(app);
+        let app = app_on(FileStore::open(&dir, 100).unwrap());
+        let req = request(Method::GET, "/todos", "");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let page: TodoPage = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(vec![todo], page.items);
+
+        let req = Request::builder()
+            .uri("/readyz")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
     }
 }
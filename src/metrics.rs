@@ -0,0 +1,148 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use prometheus::{
+    exponential_buckets, register_histogram_vec, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, TextEncoder,
+};
+use std::{future::Future, sync::LazyLock};
+use tracing::Instrument;
+
+pub static HTTP_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "http_requests_total",
+        "Requests served, by route and status class.",
+        &["method", "route", "status"]
+    )
+    .unwrap()
+});
+
+pub static HTTP_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "http_request_duration_seconds",
+        "Time spent serving a request, by route.",
+        &["method", "route"]
+    )
+    .unwrap()
+});
+
+pub static REPOSITORY_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "repository_query_duration_seconds",
+        "Time spent in a repository method.",
+        &["repository", "method"]
+    )
+    .unwrap()
+});
+
+pub static REPOSITORY_IN_FLIGHT: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "repository_calls_in_flight",
+        "Measured repository calls currently running."
+    )
+    .unwrap()
+});
+
+pub static DB_POOL_ACQUIRE_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "db_pool_acquire_duration_seconds",
+        "Time a repository call waited for a pooled connection.",
+        &["repository"],
+        // handing out an idle connection takes well below the default 5ms bucket
+        exponential_buckets(0.0001, 4.0, 9).unwrap()
+    )
+    .unwrap()
+});
+
+pub static DB_POOL_SIZE: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("db_pool_size", "Open connections, idle or in use.").unwrap()
+});
+
+pub static DB_POOL_IDLE: LazyLock<IntGauge> =
+    LazyLock::new(|| register_int_gauge!("db_pool_idle", "Open connections not in use.").unwrap());
+
+pub static TODOS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!("todos", "Todos of every owner, by state.", &["state"]).unwrap()
+});
+
+/// Labels past this many, by todos carried, share one series, which keeps the series bounded.
+pub const TOP_LABELS: usize = 20;
+
+pub static TODOS_BY_LABEL: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "todos_by_label",
+        "Todos carrying a label, for the 20 labels carried most; `other` sums the rest.",
+        &["label"]
+    )
+    .unwrap()
+});
+
+/// Middleware recording count, latency and status class of every routed request.
+pub async fn track_metrics(req: Request, next: Next) -> Response {
+    let method = req.method().to_string();
+    // the route template keeps ids out of the label values
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |path| path.as_str())
+        .to_string();
+
+    let timer = HTTP_REQUEST_DURATION
+        .with_label_values(&[&method, &route])
+        .start_timer();
+    let res = next.run(req).await;
+    timer.observe_duration();
+
+    let status = format!("{}xx", res.status().as_u16() / 100);
+    HTTP_REQUESTS
+        .with_label_values(&[&method, &route, &status])
+        .inc();
+    res
+}
+
+/// Runs one repository method, recording its latency under `repository` and `method`.
+/// The call runs in a span below the request span, so its SQL is logged with the request id.
+pub async fn measure<T>(
+    repository: &str,
+    method: &str,
+    call: impl Future<Output = anyhow::Result<T>>,
+) -> anyhow::Result<T> {
+    let _in_flight = InFlight::start();
+    let span = tracing::debug_span!("repository", repository, method);
+    let timer = REPOSITORY_DURATION
+        .with_label_values(&[repository, method])
+        .start_timer();
+    let res = call.instrument(span.clone()).await;
+    let elapsed = timer.stop_and_record();
+    span.in_scope(|| {
+        tracing::debug!(
+            elapsed_ms = elapsed * 1000.0,
+            ok = res.is_ok(),
+            "repository call finished"
+        )
+    });
+    res
+}
+
+/// Counts a repository call as in flight until dropped, even when the caller goes away.
+struct InFlight;
+
+impl InFlight {
+    fn start() -> Self {
+        REPOSITORY_IN_FLIGHT.inc();
+        Self
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        REPOSITORY_IN_FLIGHT.dec();
+    }
+}
+
+/// Everything registered so far, in the Prometheus text format.
+pub fn render() -> anyhow::Result<String> {
+    Ok(TextEncoder::new().encode_to_string(&prometheus::gather())?)
+}
@@ -0,0 +1,507 @@
+use crate::config::MigrateAction;
+use crate::repositories::{is_unique_violation, unit_of_work};
+use anyhow::{bail, Context};
+use sqlx::{
+    database::HasArguments,
+    migrate::{Migrate, Migrator},
+    ColumnIndex, Database, Decode, Executor, IntoArguments, PgPool, Pool, Postgres, Sqlite, Type,
+};
+use std::collections::HashSet;
+
+/// The migrations of `migrations/`, built into the binary.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// The migrations of `migrations/sqlite/`, a schema of its own for the SQLite backend.
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+/// A database with its own set of embedded migrations.
+pub trait Migrated: Database {
+    /// Whether the table sqlx records the applied migrations in exists.
+    const HAS_MIGRATIONS_TABLE: &'static str;
+
+    fn migrator() -> &'static Migrator;
+}
+
+impl Migrated for Postgres {
+    const HAS_MIGRATIONS_TABLE: &'static str = "SELECT to_regclass('_sqlx_migrations') IS NOT NULL";
+
+    fn migrator() -> &'static Migrator {
+        &MIGRATOR
+    }
+}
+
+impl Migrated for Sqlite {
+    const HAS_MIGRATIONS_TABLE: &'static str =
+        "SELECT EXISTS ( SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations' )";
+
+    fn migrator() -> &'static Migrator {
+        &SQLITE_MIGRATOR
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaStatus {
+    pub applied: Vec<i64>,
+    pub pending: Vec<i64>,
+    /// Applied to the database but unknown to this binary, i.e. the schema is newer.
+    pub unknown: Vec<i64>,
+}
+
+pub async fn status<DB>(pool: &Pool<DB>) -> anyhow::Result<SchemaStatus>
+where
+    DB: Migrated,
+    DB::Connection: Migrate,
+{
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied_versions = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+    Ok(status_of::<DB>(applied_versions))
+}
+
+/// `status` without creating the migrations table, `None` while it does not exist, so
+/// that probes only ever read.
+pub async fn peek_status<DB>(pool: &Pool<DB>) -> anyhow::Result<Option<SchemaStatus>>
+where
+    DB: Migrated,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'r> bool: Decode<'r, DB> + Type<DB>,
+    for<'r> i64: Decode<'r, DB> + Type<DB>,
+    usize: ColumnIndex<DB::Row>,
+{
+    let mut conn = pool.acquire().await?;
+    if !sqlx::query_scalar::<DB, bool>(DB::HAS_MIGRATIONS_TABLE)
+        .fetch_one(&mut *conn)
+        .await?
+    {
+        return Ok(None);
+    }
+    let applied_versions = sqlx::query_scalar::<DB, i64>("SELECT version FROM _sqlx_migrations")
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(Some(status_of::<DB>(applied_versions)))
+}
+
+fn status_of<DB: Migrated>(mut applied_versions: Vec<i64>) -> SchemaStatus {
+    applied_versions.sort_unstable();
+    let known = DB::migrator()
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| migration.version)
+        .collect::<HashSet<_>>();
+
+    let (applied, unknown) = applied_versions
+        .iter()
+        .partition(|version| known.contains(version));
+    let mut pending = known
+        .into_iter()
+        .filter(|version| !applied_versions.contains(version))
+        .collect::<Vec<_>>();
+    pending.sort_unstable();
+
+    SchemaStatus {
+        applied,
+        pending,
+        unknown,
+    }
+}
+
+/// Fails when the database carries migrations this binary does not know.
+pub async fn ensure_compatible<DB>(pool: &Pool<DB>) -> anyhow::Result<SchemaStatus>
+where
+    DB: Migrated,
+    DB::Connection: Migrate,
+{
+    let status = status(pool).await?;
+    if !status.unknown.is_empty() {
+        bail!(
+            "database schema is newer than this binary, unknown migrations {:?}",
+            status.unknown
+        );
+    }
+    Ok(status)
+}
+
+/// Applies every pending migration, returning their versions.
+pub async fn up<DB>(pool: &Pool<DB>) -> anyhow::Result<Vec<i64>>
+where
+    DB: Migrated,
+    DB::Connection: Migrate,
+{
+    let status = ensure_compatible(pool).await?;
+    DB::migrator().run(pool).await?;
+    Ok(status.pending)
+}
+
+/// Reverts the latest applied migration, returning its version.
+pub async fn down<DB>(pool: &Pool<DB>) -> anyhow::Result<Option<i64>>
+where
+    DB: Migrated,
+    DB::Connection: Migrate,
+{
+    let status = ensure_compatible(pool).await?;
+    let mut applied = status.applied.iter().rev();
+    let Some(&latest) = applied.next() else {
+        return Ok(None);
+    };
+    let target = applied.next().copied().unwrap_or(0);
+    DB::migrator().undo(pool, target).await?;
+    Ok(Some(latest))
+}
+
+/// `my_todo migrate <action>`
+pub async fn run<DB>(pool: &Pool<DB>, action: MigrateAction) -> anyhow::Result<()>
+where
+    DB: Migrated,
+    DB::Connection: Migrate,
+{
+    let describe = |version| describe(DB::migrator(), version);
+    match action {
+        MigrateAction::Up => {
+            let applied = up(pool).await?;
+            if applied.is_empty() {
+                println!("schema is up to date");
+            }
+            for version in applied {
+                println!("applied {}", describe(version));
+            }
+        }
+        MigrateAction::Down => match down(pool).await? {
+            Some(version) => println!("reverted {}", describe(version)),
+            None => println!("nothing to revert"),
+        },
+        MigrateAction::Status => {
+            let status = status(pool).await?;
+            for version in status.applied {
+                println!("applied  {}", describe(version));
+            }
+            for version in status.pending {
+                println!("pending  {}", describe(version));
+            }
+            for version in status.unknown {
+                println!("unknown  {} (newer than this binary)", version);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Username of the placeholder account `20240429081522_users` hands the rows that predate
+/// user accounts to, shorter than any name registration accepts.
+pub const LEGACY_OWNER: &str = "~";
+
+/// How many rows `claim` handed over.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Claimed {
+    pub todos: u64,
+    pub labels: u64,
+    pub projects: u64,
+}
+
+/// Hands every todo, label and project of `LEGACY_OWNER` to `username` and removes the
+/// placeholder account, `None` when there is none left. Only Postgres databases predate
+/// user accounts.
+pub async fn claim(pool: &PgPool, username: &str) -> anyhow::Result<Option<Claimed>> {
+    let username = username.to_string();
+    unit_of_work(pool, |conn| {
+        Box::pin(async move {
+            let owners = sqlx::query_as::<_, (i32, String)>(
+                "SELECT id, username FROM users WHERE username = $1 OR username = $2",
+            )
+            .bind(LEGACY_OWNER)
+            .bind(&username)
+            .fetch_all(&mut *conn)
+            .await?;
+            let find = |name: &str| owners.iter().find(|(_, n)| n == name).map(|(id, _)| *id);
+            let Some(legacy_id) = find(LEGACY_OWNER) else {
+                return Ok(None);
+            };
+            let Some(owner_id) = find(&username).filter(|_| username != LEGACY_OWNER) else {
+                bail!("user {} does not exist", username);
+            };
+
+            let mut claimed = Claimed::default();
+            for (table, count) in [
+                ("todos", &mut claimed.todos),
+                ("labels", &mut claimed.labels),
+                ("projects", &mut claimed.projects),
+            ] {
+                *count = sqlx::query(&format!(
+                    "UPDATE {} SET owner_id = $1 WHERE owner_id = $2",
+                    table
+                ))
+                .bind(owner_id)
+                .bind(legacy_id)
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| {
+                    if is_unique_violation(&e) {
+                        anyhow::anyhow!(
+                            "{} already has {} named like claimed ones, rename them first",
+                            username,
+                            table
+                        )
+                    } else {
+                        e.into()
+                    }
+                })?
+                .rows_affected();
+            }
+            sqlx::query("DELETE FROM users WHERE id = $1")
+                .bind(legacy_id)
+                .execute(&mut *conn)
+                .await
+                .context("fail remove the placeholder owner")?;
+            Ok(Some(claimed))
+        })
+    })
+    .await
+}
+
+fn describe(migrator: &Migrator, version: i64) -> String {
+    migrator
+        .iter()
+        .find(|migration| migration.version == version)
+        .map_or(version.to_string(), |migration| {
+            format!("{} {}", version, migration.description)
+        })
+}
+
+#[cfg(test)]
+#[cfg(feature = "database-test")]
+mod test {
+    use super::*;
+    use dotenv::dotenv;
+    use sqlx::PgPool;
+    use std::env;
+
+    #[tokio::test]
+    async fn up_leaves_schema_current() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+
+        up(&pool).await.expect("[up] returned Err");
+        let status = ensure_compatible(&pool)
+            .await
+            .expect("[ensure_compatible] returned Err");
+        assert!(status.pending.is_empty());
+        assert!(status.unknown.is_empty());
+        assert_eq!(
+            MIGRATOR
+                .iter()
+                .filter(|m| m.migration_type.is_up_migration())
+                .count(),
+            status.applied.len()
+        );
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "database-test")]
+mod legacy_test {
+    use super::*;
+    use dotenv::dotenv;
+    use sqlx::postgres::PgConnectOptions;
+    use std::env;
+
+    const DATABASE: &str = "todos_legacy_test";
+
+    #[tokio::test]
+    async fn rows_before_users_wait_for_a_claim() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let admin = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        for statement in [
+            format!("DROP DATABASE IF EXISTS {} WITH ( FORCE )", DATABASE),
+            format!("CREATE DATABASE {}", DATABASE),
+        ] {
+            sqlx::query(&statement).execute(&admin).await.unwrap();
+        }
+        let options = database_url
+            .parse::<PgConnectOptions>()
+            .unwrap()
+            .database(DATABASE);
+        let pool = PgPool::connect_with(options).await.unwrap();
+
+        // a deployment from before user accounts
+        let mut conn = pool.acquire().await.unwrap();
+        conn.ensure_migrations_table().await.unwrap();
+        for migration in MIGRATOR
+            .iter()
+            .filter(|m| m.migration_type.is_up_migration() && m.version < 20240429081522)
+        {
+            conn.apply(migration).await.unwrap();
+        }
+        for statement in [
+            "INSERT INTO projects ( name ) VALUES ( 'old project' )",
+            "INSERT INTO labels ( name ) VALUES ( 'old label' )",
+            "INSERT INTO todos ( text, project_id ) VALUES ( 'old todo', 2 )",
+        ] {
+            sqlx::query(statement).execute(&mut *conn).await.unwrap();
+        }
+        drop(conn);
+
+        up(&pool).await.expect("[up] returned Err");
+        let owners = sqlx::query_scalar::<_, String>(
+            r#"
+                SELECT users.username FROM users
+                JOIN todos ON todos.owner_id = users.id
+                UNION ALL
+                SELECT users.username FROM users
+                JOIN labels ON labels.owner_id = users.id
+                UNION ALL
+                SELECT users.username FROM users
+                JOIN projects ON projects.owner_id = users.id
+            "#,
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(vec![LEGACY_OWNER; 3], owners);
+        let err = sqlx::query("INSERT INTO todos ( text, project_id ) VALUES ( 'new todo', 1 )")
+            .execute(&pool)
+            .await
+            .expect_err("a todo without owner was stored");
+        // not_null_violation
+        assert!(matches!(err, sqlx::Error::Database(db) if db.code().as_deref() == Some("23502")));
+
+        assert!(claim(&pool, "alice").await.is_err());
+        sqlx::query("INSERT INTO users ( username, password_hash ) VALUES ( 'alice', '' )")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let claimed = claim(&pool, "alice").await.expect("[claim] returned Err");
+        assert_eq!(
+            Some(Claimed {
+                todos: 1,
+                labels: 1,
+                projects: 1,
+            }),
+            claimed
+        );
+        assert_eq!(None, claim(&pool, "alice").await.unwrap());
+        let usernames = sqlx::query_scalar::<_, String>("SELECT username FROM users")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(vec!["alice"], usernames);
+
+        pool.close().await;
+        sqlx::query(&format!("DROP DATABASE {} WITH ( FORCE )", DATABASE))
+            .execute(&admin)
+            .await
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod sqlite_test {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn up_and_down_round_trip() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+
+        let applied = up(&pool).await.expect("[up] returned Err");
+        assert_eq!(
+            SQLITE_MIGRATOR
+                .iter()
+                .filter(|m| m.migration_type.is_up_migration())
+                .count(),
+            applied.len()
+        );
+        let inbox = sqlx::query_scalar::<_, String>("SELECT name FROM projects WHERE id = 1")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!("Inbox", inbox);
+
+        while down(&pool).await.expect("[down] returned Err").is_some() {}
+        let status = status(&pool).await.unwrap();
+        assert!(status.applied.is_empty());
+        assert_eq!(applied, status.pending);
+    }
+
+    #[tokio::test]
+    async fn label_integrity_merges_duplicates() {
+        let pool = test_utils::sqlite_pool().await;
+        assert_eq!(Some(20240603090000), down(&pool).await.unwrap());
+        assert_eq!(Some(20240527090000), down(&pool).await.unwrap());
+        assert_eq!(Some(20240520090000), down(&pool).await.unwrap());
+        for statement in [
+            "INSERT INTO users ( id, username, password_hash ) VALUES ( 1, 'a', '' ), ( 2, 'b', '' )",
+            "INSERT INTO labels ( id, name, owner_id ) VALUES ( 1, 'bug', 1 ), ( 2, 'Bug', 1 ), ( 3, 'Bug', 2 )",
+            "INSERT INTO todos ( id, text, project_id, owner_id ) VALUES ( 1, 'todo', 1, 1 )",
+            "INSERT INTO todo_labels ( todo_id, label_id ) VALUES ( 1, 2 ), ( 1, 1 ), ( 1, 2 )",
+        ] {
+            sqlx::query(statement).execute(&pool).await.unwrap();
+        }
+
+        up(&pool).await.expect("[up] returned Err");
+        let labels = sqlx::query_as::<_, (i32, String)>("SELECT id, name FROM labels ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(vec![(1, "bug".to_string()), (3, "Bug".to_string())], labels);
+        let pairs = sqlx::query_as::<_, (i32, i32)>(
+            "SELECT todo_id, label_id FROM todo_labels ORDER BY id",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(vec![(1, 1)], pairs);
+
+        for statement in [
+            "INSERT INTO labels ( name, owner_id ) VALUES ( 'BUG', 1 )",
+            "INSERT INTO todo_labels ( todo_id, label_id ) VALUES ( 1, 1 )",
+        ] {
+            let err = sqlx::query(statement).execute(&pool).await.unwrap_err();
+            assert!(crate::repositories::is_unique_violation(&err), "{err:?}");
+        }
+        sqlx::query("DELETE FROM todos WHERE id = 1")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let pairs = sqlx::query_scalar::<_, i64>("SELECT count(*) FROM todo_labels")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(0, pairs);
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+
+    /// A fresh in-memory SQLite database with the schema applied.
+    /// Every connection to `sqlite::memory:` opens a database of its own,
+    /// so the pool keeps exactly one for good.
+    pub async fn sqlite_pool() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await
+            .expect("fail open sqlite database");
+        up(&pool).await.expect("fail migrate sqlite database");
+        pool
+    }
+}
@@ -1,14 +1,189 @@
+pub mod file;
+pub mod health;
 pub mod label;
+pub mod measured;
+pub mod project;
 pub mod todo;
+pub mod user;
 
+use sqlx::{Database, Encode, Executor, Pool, Postgres, QueryBuilder, Sqlite, Type};
+use std::{future::Future, pin::Pin};
 use thiserror::Error;
 
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
 #[derive(Debug, Error)]
-enum RepositoryError {
+pub enum RepositoryError {
     #[error("Unexpected Error: [{0}]")]
     Unexpected(String),
     #[error("NotFound, id is {0}")]
     NotFound(i32),
     #[error("Duplicate data, id is {0}")]
     Duplicate(i32),
+    #[error("Todo {id} still has subtasks")]
+    HasChildren { id: i32, children: Vec<i32> },
+    #[error("Todo {0} can not be used as the parent")]
+    InvalidParent(i32),
+    #[error("Project {0} does not exist")]
+    InvalidProject(i32),
+    #[error("Project {id} still has todos")]
+    NotEmpty { id: i32, todos: Vec<i32> },
+    #[error("Project {0} can not be deleted")]
+    Protected(i32),
+    #[error("Label {id} is still attached to todos")]
+    InUse { id: i32, todos: Vec<i32> },
+    #[error("Labels {0:?} do not exist")]
+    UnknownLabels(Vec<i32>),
+    #[error("Todo {0} is not in the same project")]
+    InvalidPlacement(i32),
+}
+
+/// Whether `e` is the violation of a unique constraint.
+pub(crate) fn is_unique_violation(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(db) if db.is_unique_violation())
+}
+
+/// Where the SQL of the Postgres and SQLite repositories differs, so that the queries they
+/// have in common are written once for both.
+pub trait Dialect: Database {
+    /// A list of ids as bound to a query.
+    type Ids: for<'q> Encode<'q, Self> + Type<Self> + Send + 'static;
+
+    /// Locks the rows a `SELECT` reads until the transaction ends, where rows can be locked.
+    const FOR_UPDATE: &'static str;
+
+    /// Run right after `BEGIN` when the transaction must hold the write lock before it reads.
+    const BEGIN_WRITE: Option<&'static str>;
+
+    /// `LIKE` ignoring the case of letters, as far as the database folds it.
+    const ILIKE: &'static str;
+
+    /// Follows a text expression to compare it bytewise.
+    const BYTEWISE: &'static str;
+
+    /// A timestamp after every date.
+    const INFINITY: &'static str;
+
+    fn ids(ids: &[i32]) -> Self::Ids;
+
+    /// Whether `column` is one of the ids bound at `param`.
+    fn in_ids(column: &str, param: &str) -> String;
+
+    /// `in_ids` with `ids` bound through `builder`.
+    fn push_in_ids(builder: &mut QueryBuilder<'_, Self>, column: &str, ids: &[i32]) {
+        // the placeholder is only known once the ids are bound
+        let sql = Self::in_ids(column, "\0");
+        let (before, after) = sql.split_once('\0').unwrap();
+        builder.push(before).push_bind(Self::ids(ids)).push(after);
+    }
+
+    /// The ids bound at `param` as the rows of `alias ( id, n )`, numbered in their order.
+    fn ids_table(param: &str, alias: &str) -> String;
+
+    /// Whether the boolean `column` holds for every row, NULL without rows.
+    fn bool_and(column: &str) -> String;
+}
+
+impl Dialect for Postgres {
+    type Ids = Vec<i32>;
+
+    const FOR_UPDATE: &'static str = " FOR UPDATE";
+
+    const BEGIN_WRITE: Option<&'static str> = None;
+
+    const ILIKE: &'static str = "ILIKE";
+
+    const BYTEWISE: &'static str = r#" COLLATE "C""#;
+
+    const INFINITY: &'static str = "'infinity'::timestamptz";
+
+    fn ids(ids: &[i32]) -> Self::Ids {
+        ids.to_vec()
+    }
+
+    fn in_ids(column: &str, param: &str) -> String {
+        format!("{} = ANY ( {} )", column, param)
+    }
+
+    fn ids_table(param: &str, alias: &str) -> String {
+        format!(
+            "UNNEST ( {} ) WITH ORDINALITY AS {} ( id, n )",
+            param, alias
+        )
+    }
+
+    fn bool_and(column: &str) -> String {
+        format!("bool_and({})", column)
+    }
+}
+
+/// SQLite has no row locks, only one writer for the whole database, and `json_each` over a
+/// JSON array stands in for Postgres arrays.
+impl Dialect for Sqlite {
+    type Ids = String;
+
+    const FOR_UPDATE: &'static str = "";
+
+    // sqlx only begins deferred transactions, which take no lock until their first write.
+    // One that has read by then fails with SQLITE_BUSY once another writer got in between,
+    // whereas an immediate one waits out the busy timeout for the writer lock up front.
+    const BEGIN_WRITE: Option<&'static str> = Some("COMMIT; BEGIN IMMEDIATE");
+
+    // only ASCII letters are folded
+    const ILIKE: &'static str = "LIKE";
+
+    // the default BINARY collation already compares bytewise
+    const BYTEWISE: &'static str = "";
+
+    // timestamps are RFC 3339 text, which starts with a digit
+    const INFINITY: &'static str = "'infinity'";
+
+    fn ids(ids: &[i32]) -> Self::Ids {
+        serde_json::to_string(ids).unwrap()
+    }
+
+    fn in_ids(column: &str, param: &str) -> String {
+        format!(
+            "{} IN ( SELECT value FROM json_each ( {} ) )",
+            column, param
+        )
+    }
+
+    fn ids_table(param: &str, alias: &str) -> String {
+        format!(
+            "( SELECT value AS id, key AS n FROM json_each ( {} ) ) AS {}",
+            param, alias
+        )
+    }
+
+    // booleans are stored as 0 and 1
+    fn bool_and(column: &str) -> String {
+        format!("MIN({})", column)
+    }
+}
+
+/// Runs `work` on one transaction and returns what it produced.
+/// The transaction is committed only when `work` and the commit itself succeed,
+/// so a repository operation made of several statements is applied entirely or not at all.
+pub async fn unit_of_work<DB, R, F>(pool: &Pool<DB>, work: F) -> anyhow::Result<R>
+where
+    DB: Dialect,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    R: Send,
+    F: for<'c> FnOnce(&'c mut DB::Connection) -> BoxFuture<'c, anyhow::Result<R>> + Send,
+{
+    let mut tx = pool.begin().await?;
+    if let Some(begin_write) = DB::BEGIN_WRITE {
+        tx.execute(begin_write).await?;
+    }
+    match work(&mut tx).await {
+        Ok(result) => {
+            tx.commit().await?;
+            Ok(result)
+        }
+        Err(e) => {
+            tx.rollback().await?;
+            Err(e)
+        }
+    }
 }
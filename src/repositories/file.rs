@@ -0,0 +1,780 @@
+use crate::repositories::{project::DEFAULT_PROJECT_ID, todo::Priority, user::ApiToken};
+use anyhow::{anyhow, bail, ensure, Context};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, MutexGuard, OnceLock, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+};
+
+mod label;
+mod project;
+mod todo;
+mod user;
+
+pub use label::LabelRepositoryForFile;
+pub use project::ProjectRepositoryForFile;
+pub use todo::TodoRepositoryForFile;
+pub use user::UserRepositoryForFile;
+
+const JOURNAL: &str = "journal.jsonl";
+const SNAPSHOT: &str = "snapshot.json";
+const SNAPSHOT_TMP: &str = "snapshot.json.tmp";
+/// Layout of `State` in snapshots and journal records.
+const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserRow {
+    pub id: i32,
+    pub username: String,
+    pub password_hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionRow {
+    pub token_hash: String,
+    pub user_id: i32,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenRow {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub token: ApiToken,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectRow {
+    pub id: i32,
+    pub name: String,
+    /// `None` for the default project.
+    pub owner_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LabelRow {
+    pub id: i32,
+    pub name: String,
+    pub owner_id: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TodoRow {
+    pub id: i32,
+    pub text: String,
+    pub completed: bool,
+    /// Missing from stores written before priorities existed.
+    #[serde(default)]
+    pub priority: Priority,
+    pub due_at: Option<DateTime<Utc>>,
+    pub parent_id: Option<i32>,
+    pub auto_complete: bool,
+    pub project_id: i32,
+    pub owner_id: i32,
+    /// Missing from stores written before manual ordering, where ties fall back to the id.
+    #[serde(default)]
+    pub position: i64,
+    /// Label ids in the order they were given.
+    pub labels: Vec<i32>,
+}
+
+/// Highest id handed out per table. Ids are never reused, even once the row is gone.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ids {
+    pub user: i32,
+    pub token: i32,
+    pub project: i32,
+    pub label: i32,
+    pub todo: i32,
+}
+
+/// Every row of the store, keyed like the primary keys of the SQL schema.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct State {
+    pub users: BTreeMap<i32, UserRow>,
+    pub sessions: BTreeMap<String, SessionRow>,
+    pub tokens: BTreeMap<i32, TokenRow>,
+    pub projects: BTreeMap<i32, ProjectRow>,
+    pub labels: BTreeMap<i32, LabelRow>,
+    pub todos: BTreeMap<i32, TodoRow>,
+    pub ids: Ids,
+}
+
+impl State {
+    /// An empty store holding only the default project, like a freshly migrated database.
+    fn new() -> Self {
+        let inbox = ProjectRow {
+            id: DEFAULT_PROJECT_ID,
+            name: "Inbox".to_string(),
+            owner_id: None,
+        };
+        Self {
+            users: BTreeMap::new(),
+            sessions: BTreeMap::new(),
+            tokens: BTreeMap::new(),
+            projects: BTreeMap::from([(inbox.id, inbox)]),
+            labels: BTreeMap::new(),
+            todos: BTreeMap::new(),
+            ids: Ids {
+                project: DEFAULT_PROJECT_ID,
+                ..Ids::default()
+            },
+        }
+    }
+
+    /// Applies `change` and returns the change undoing it, if it changed anything.
+    fn apply(&mut self, change: Change) -> Option<Change> {
+        match change {
+            Change::PutUser(row) => {
+                self.ids.user = self.ids.user.max(row.id);
+                put(
+                    &mut self.users,
+                    row.id,
+                    row,
+                    Change::PutUser,
+                    Change::DeleteUser,
+                )
+            }
+            Change::DeleteUser(id) => delete(&mut self.users, id, Change::PutUser),
+            Change::PutSession(row) => put(
+                &mut self.sessions,
+                row.token_hash.clone(),
+                row,
+                Change::PutSession,
+                Change::DeleteSession,
+            ),
+            Change::DeleteSession(token_hash) => {
+                delete(&mut self.sessions, token_hash, Change::PutSession)
+            }
+            Change::PutToken(row) => {
+                self.ids.token = self.ids.token.max(row.token.id);
+                put(
+                    &mut self.tokens,
+                    row.token.id,
+                    row,
+                    Change::PutToken,
+                    Change::DeleteToken,
+                )
+            }
+            Change::DeleteToken(id) => delete(&mut self.tokens, id, Change::PutToken),
+            Change::PutProject(row) => {
+                self.ids.project = self.ids.project.max(row.id);
+                put(
+                    &mut self.projects,
+                    row.id,
+                    row,
+                    Change::PutProject,
+                    Change::DeleteProject,
+                )
+            }
+            Change::DeleteProject(id) => delete(&mut self.projects, id, Change::PutProject),
+            Change::PutLabel(row) => {
+                self.ids.label = self.ids.label.max(row.id);
+                put(
+                    &mut self.labels,
+                    row.id,
+                    row,
+                    Change::PutLabel,
+                    Change::DeleteLabel,
+                )
+            }
+            Change::DeleteLabel(id) => delete(&mut self.labels, id, Change::PutLabel),
+            Change::PutTodo(row) => {
+                self.ids.todo = self.ids.todo.max(row.id);
+                put(
+                    &mut self.todos,
+                    row.id,
+                    row,
+                    Change::PutTodo,
+                    Change::DeleteTodo,
+                )
+            }
+            Change::DeleteTodo(id) => delete(&mut self.todos, id, Change::PutTodo),
+        }
+    }
+}
+
+fn put<K: Ord + Clone, V>(
+    rows: &mut BTreeMap<K, V>,
+    key: K,
+    row: V,
+    put: fn(V) -> Change,
+    delete: fn(K) -> Change,
+) -> Option<Change> {
+    match rows.insert(key.clone(), row) {
+        Some(old) => Some(put(old)),
+        None => Some(delete(key)),
+    }
+}
+
+fn delete<K: Ord, V>(rows: &mut BTreeMap<K, V>, key: K, put: fn(V) -> Change) -> Option<Change> {
+    rows.remove(&key).map(put)
+}
+
+/// One row written or removed; a journal record holds the changes of one write.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    PutUser(UserRow),
+    DeleteUser(i32),
+    PutSession(SessionRow),
+    DeleteSession(String),
+    PutToken(TokenRow),
+    DeleteToken(i32),
+    PutProject(ProjectRow),
+    DeleteProject(i32),
+    PutLabel(LabelRow),
+    DeleteLabel(i32),
+    PutTodo(TodoRow),
+    DeleteTodo(i32),
+}
+
+/// A line of the journal.
+#[derive(Debug, Serialize, Deserialize)]
+struct Record<C> {
+    seq: u64,
+    changes: C,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Snapshot<S> {
+    version: u32,
+    /// Last journal record contained in `state`.
+    seq: u64,
+    state: S,
+}
+
+/// The changes of one write, applied to the state as they are made so that the write
+/// reads its own changes, and undone once the write is over, even when it panics.
+/// Only the changes that reached the journal are applied again.
+pub struct Tx<'a> {
+    state: &'a mut State,
+    changes: Vec<Change>,
+    undo: Vec<Change>,
+    ids: Ids,
+}
+
+impl<'a> Tx<'a> {
+    fn new(state: &'a mut State) -> Self {
+        let ids = state.ids;
+        Self {
+            state,
+            changes: vec![],
+            undo: vec![],
+            ids,
+        }
+    }
+
+    pub fn state(&self) -> &State {
+        self.state
+    }
+
+    pub fn apply(&mut self, change: Change) {
+        self.changes.push(change.clone());
+        self.undo.extend(self.state.apply(change));
+    }
+}
+
+impl Drop for Tx<'_> {
+    fn drop(&mut self) {
+        for change in self.undo.drain(..).rev() {
+            self.state.apply(change);
+        }
+        self.state.ids = self.ids;
+    }
+}
+
+/// Store for a single process that keeps every row in memory and persists them to a
+/// directory, so the server runs without a database.
+///
+/// Every write appends one line to `journal.jsonl` and fsyncs it before it is acknowledged.
+/// Every `snapshot_every` records the whole state goes to `snapshot.json` and the journal
+/// starts over. Opening loads the snapshot and replays the journal on top of it, so a killed
+/// process loses no acknowledged write; a record cut short by the kill was never acknowledged
+/// and is dropped.
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    inner: Arc<Inner>,
+}
+
+/// Reads share the state, writes take turns on the journal and only lock the state to work
+/// out their changes and to apply them once they are durable.
+#[derive(Debug)]
+struct Inner {
+    state: RwLock<State>,
+    journal: Mutex<Journal>,
+    /// Set once appending to the journal failed, after which the file may hold a partial
+    /// record and every write is refused until a restart recovers it.
+    failed: OnceLock<String>,
+}
+
+#[derive(Debug)]
+struct Journal {
+    dir: PathBuf,
+    file: File,
+    /// Last record written to the journal or contained in the snapshot.
+    seq: u64,
+    /// Records in the journal since the last snapshot.
+    journaled: u64,
+    snapshot_every: u64,
+}
+
+impl FileStore {
+    /// Opens or creates the store in `dir`, which no other process may have open.
+    pub fn open(dir: impl AsRef<Path>, snapshot_every: u64) -> anyhow::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).with_context(|| format!("fail create {}", dir.display()))?;
+        let mut journal = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(dir.join(JOURNAL))
+            .with_context(|| format!("fail open the journal in {}", dir.display()))?;
+        journal
+            .try_lock()
+            .map_err(|_| anyhow!("{} is in use by another process", dir.display()))?;
+        sync_dir(&dir)?;
+
+        let (mut state, mut seq) = match fs::read(dir.join(SNAPSHOT)) {
+            Ok(bytes) => {
+                let snapshot: Snapshot<State> =
+                    serde_json::from_slice(&bytes).context("the snapshot is corrupt")?;
+                ensure!(
+                    snapshot.version == VERSION,
+                    "the snapshot has version {}, this binary reads {}",
+                    snapshot.version,
+                    VERSION
+                );
+                (snapshot.state, snapshot.seq)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (State::new(), 0),
+            Err(e) => return Err(e).context("fail read the snapshot"),
+        };
+        let snapshot_seq = seq;
+
+        let mut bytes = vec![];
+        journal.read_to_end(&mut bytes)?;
+        let mut valid = 0;
+        let mut journaled = 0;
+        let mut lines = bytes.split_inclusive(|byte| *byte == b'\n').peekable();
+        while let Some(line) = lines.next() {
+            let record = match line
+                .strip_suffix(b"\n")
+                .map(serde_json::from_slice::<Record<Vec<Change>>>)
+            {
+                Some(Ok(record)) => record,
+                // the append a crash interrupted, its write was never acknowledged
+                _ if lines.peek().is_none() => break,
+                _ => bail!("the journal is corrupt at byte {}", valid),
+            };
+            valid += line.len();
+            journaled += 1;
+            // left behind when truncating after a snapshot failed
+            if record.seq <= seq {
+                continue;
+            }
+            ensure!(
+                record.seq == seq + 1,
+                "the journal skips from record {} to {}",
+                seq,
+                record.seq
+            );
+            for change in record.changes {
+                state.apply(change);
+            }
+            seq = record.seq;
+        }
+        if valid < bytes.len() {
+            tracing::warn!(
+                "dropping {} bytes of an interrupted write from the journal",
+                bytes.len() - valid
+            );
+            journal.set_len(valid as u64)?;
+            journal.sync_all()?;
+        }
+        tracing::info!(
+            "opened {}: snapshot at record {}, replayed {} records",
+            dir.display(),
+            snapshot_seq,
+            seq - snapshot_seq
+        );
+
+        let inner = Inner {
+            state: RwLock::new(state),
+            journal: Mutex::new(Journal {
+                dir,
+                file: journal,
+                seq,
+                journaled,
+                snapshot_every,
+            }),
+            failed: OnceLock::new(),
+        };
+        {
+            let mut journal = inner.journal();
+            if journal.journaled >= journal.snapshot_every {
+                inner.snapshot(&mut journal)?;
+            }
+        }
+        Ok(Self {
+            inner: Arc::new(inner),
+        })
+    }
+
+    pub fn read<R>(&self, read: impl FnOnce(&State) -> R) -> R {
+        read(&self.inner.state())
+    }
+
+    /// Runs `work` and makes its changes durable; when `work` fails nothing is kept.
+    /// Runs on the blocking pool since it waits for the disk.
+    pub async fn write<R, F>(&self, work: F) -> anyhow::Result<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut Tx<'_>) -> anyhow::Result<R> + Send + 'static,
+    {
+        let inner = self.inner.clone();
+        tokio::task::spawn_blocking(move || inner.write(work)).await?
+    }
+
+    /// Fails once a write could not reach the disk.
+    pub fn check(&self) -> anyhow::Result<()> {
+        match self.inner.failed.get() {
+            Some(error) => bail!("the journal failed: {}", error),
+            None => Ok(()),
+        }
+    }
+
+    /// Takes a snapshot so that the next start has no journal to replay.
+    pub async fn close(&self) {
+        let inner = self.inner.clone();
+        let res = tokio::task::spawn_blocking(move || {
+            let mut journal = inner.journal();
+            match journal.journaled > 0 && inner.failed.get().is_none() {
+                true => inner.snapshot(&mut journal),
+                false => Ok(()),
+            }
+        })
+        .await;
+        match res {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => tracing::warn!("fail snapshot on close: {:#}", e),
+            Err(e) => tracing::warn!("fail snapshot on close: {}", e),
+        }
+    }
+}
+
+// Every write undoes its changes through its `Tx` however it ends, so a panic cannot leave
+// the state half written and a poisoned lock is safe to go on with.
+impl Inner {
+    fn state(&self) -> RwLockReadGuard<'_, State> {
+        self.state.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn state_mut(&self) -> RwLockWriteGuard<'_, State> {
+        self.state.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn journal(&self) -> MutexGuard<'_, Journal> {
+        self.journal.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write<R>(&self, work: impl FnOnce(&mut Tx<'_>) -> anyhow::Result<R>) -> anyhow::Result<R> {
+        let mut journal = self.journal();
+        if let Some(error) = self.failed.get() {
+            bail!(
+                "the store takes no writes after the journal failed: {}",
+                error
+            );
+        }
+        let (result, changes) = {
+            let mut state = self.state_mut();
+            let mut tx = Tx::new(&mut state);
+            let result = work(&mut tx)?;
+            (result, std::mem::take(&mut tx.changes))
+        };
+        if changes.is_empty() {
+            return Ok(result);
+        }
+
+        let mut line = serde_json::to_vec(&Record {
+            seq: journal.seq + 1,
+            changes: &changes,
+        })?;
+        line.push(b'\n');
+        // reads go on meanwhile and do not see the changes before they are durable
+        if let Err(e) = journal.append(&line) {
+            self.failed.get_or_init(|| e.to_string());
+            return Err(anyhow!(e).context("fail append to the journal"));
+        }
+        let mut state = self.state_mut();
+        for change in changes {
+            state.apply(change);
+        }
+        drop(state);
+
+        if journal.journaled >= journal.snapshot_every {
+            // the record is durable already, the next write tries again
+            if let Err(e) = self.snapshot(&mut journal) {
+                tracing::warn!("fail snapshot {}: {:#}", journal.dir.display(), e);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Snapshots the state as of the last record of `journal`, which no write can add to
+    /// while it is locked.
+    fn snapshot(&self, journal: &mut Journal) -> anyhow::Result<()> {
+        let snapshot = serde_json::to_vec(&Snapshot {
+            version: VERSION,
+            seq: journal.seq,
+            state: &*self.state(),
+        })?;
+        journal.snapshot(&snapshot)
+    }
+}
+
+impl Journal {
+    /// Appends the next record and waits until it is on disk.
+    fn append(&mut self, line: &[u8]) -> io::Result<()> {
+        self.file.write_all(line)?;
+        self.file.sync_data()?;
+        self.seq += 1;
+        self.journaled += 1;
+        Ok(())
+    }
+
+    /// Replaces the snapshot atomically, then empties the journal it now contains.
+    fn snapshot(&mut self, snapshot: &[u8]) -> anyhow::Result<()> {
+        let tmp = self.dir.join(SNAPSHOT_TMP);
+        let mut file = File::create(&tmp)?;
+        file.write_all(snapshot)?;
+        file.sync_all()?;
+        fs::rename(&tmp, self.dir.join(SNAPSHOT))?;
+        sync_dir(&self.dir)?;
+
+        self.file.set_len(0)?;
+        self.file.sync_all()?;
+        self.journaled = 0;
+        Ok(())
+    }
+}
+
+/// Makes the files created or renamed in `dir` durable.
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(test)]
+mod test {
+    use super::test_utils::TempDir;
+    use super::*;
+
+    fn label(id: i32, name: &str) -> Change {
+        Change::PutLabel(LabelRow {
+            id,
+            name: name.to_string(),
+            owner_id: 1,
+        })
+    }
+
+    async fn put_label(store: &FileStore, name: &str) -> i32 {
+        let name = name.to_string();
+        store
+            .write(move |tx| {
+                let id = tx.state().ids.label + 1;
+                tx.apply(label(id, &name));
+                Ok(id)
+            })
+            .await
+            .expect("[write] returned Err")
+    }
+
+    #[tokio::test]
+    async fn replays_journal_after_crash() {
+        let dir = TempDir::new("replay");
+        let store = FileStore::open(&dir, 100).unwrap();
+        let first = put_label(&store, "first").await;
+        let second = put_label(&store, "second").await;
+        store
+            .write(move |tx| {
+                tx.apply(Change::DeleteLabel(first));
+                Ok(())
+            })
+            .await
+            .unwrap();
+        let expected = store.read(State::clone);
+        // dropped without closing, like a killed process
+        drop(store);
+        assert!(!dir.join(SNAPSHOT).exists());
+
+        let store = FileStore::open(&dir, 100).unwrap();
+        assert_eq!(expected, store.read(State::clone));
+        assert_eq!(
+            vec![second],
+            store.read(|state| state.labels.keys().copied().collect::<Vec<_>>())
+        );
+        // the id of the deleted label is not handed out again
+        assert_eq!(second + 1, put_label(&store, "third").await);
+    }
+
+    #[tokio::test]
+    async fn drops_interrupted_write_and_rejects_corruption() {
+        let dir = TempDir::new("torn");
+        let store = FileStore::open(&dir, 100).unwrap();
+        put_label(&store, "first").await;
+        drop(store);
+        let journal = dir.join(JOURNAL);
+        let intact = fs::read(&journal).unwrap();
+        let mut torn = intact.clone();
+        torn.extend_from_slice(br#"{"seq":2,"changes":[{"put_la"#);
+        fs::write(&journal, &torn).unwrap();
+
+        let store = FileStore::open(&dir, 100).unwrap();
+        assert_eq!(1, store.read(|state| state.labels.len()));
+        assert_eq!(intact, fs::read(&journal).unwrap());
+        put_label(&store, "second").await;
+        drop(store);
+
+        let mut corrupt = b"garbage\n".to_vec();
+        corrupt.extend_from_slice(&fs::read(&journal).unwrap());
+        fs::write(&journal, &corrupt).unwrap();
+        let res = FileStore::open(&dir, 100);
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn snapshots_and_replays_the_rest() {
+        let dir = TempDir::new("snapshot");
+        let store = FileStore::open(&dir, 2).unwrap();
+        for name in ["a", "b", "c"] {
+            put_label(&store, name).await;
+        }
+        let journal = fs::read_to_string(dir.join(JOURNAL)).unwrap();
+        assert_eq!(1, journal.lines().count(), "{}", journal);
+        assert!(journal.starts_with(r#"{"seq":3,"#), "{}", journal);
+        let expected = store.read(State::clone);
+        drop(store);
+
+        let store = FileStore::open(&dir, 2).unwrap();
+        assert_eq!(expected, store.read(State::clone));
+        store.close().await;
+        assert!(fs::read(dir.join(JOURNAL)).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_write_leaves_nothing() {
+        let dir = TempDir::new("rollback");
+        let store = FileStore::open(&dir, 100).unwrap();
+        put_label(&store, "kept").await;
+        let expected = store.read(State::clone);
+
+        let res: anyhow::Result<()> = store
+            .write(|tx| {
+                tx.apply(label(1, "renamed"));
+                tx.apply(label(2, "added"));
+                tx.apply(Change::DeleteProject(DEFAULT_PROJECT_ID));
+                bail!("injected failure")
+            })
+            .await;
+        assert!(res.is_err());
+        assert_eq!(expected, store.read(State::clone));
+        assert_eq!(
+            1,
+            fs::read_to_string(dir.join(JOURNAL))
+                .unwrap()
+                .lines()
+                .count()
+        );
+    }
+
+    #[tokio::test]
+    async fn panicking_write_leaves_nothing() {
+        let dir = TempDir::new("panic");
+        let store = FileStore::open(&dir, 100).unwrap();
+        put_label(&store, "kept").await;
+        let expected = store.read(State::clone);
+
+        let res: anyhow::Result<()> = store
+            .write(|tx| {
+                tx.apply(label(1, "renamed"));
+                panic!("injected panic")
+            })
+            .await;
+        assert!(res.is_err());
+        assert_eq!(expected, store.read(State::clone));
+        // the store goes on taking writes
+        put_label(&store, "added").await;
+        assert_eq!(2, store.read(|state| state.labels.len()));
+    }
+
+    #[tokio::test]
+    async fn refuses_a_second_process() {
+        let dir = TempDir::new("lock");
+        let store = FileStore::open(&dir, 100).unwrap();
+        let res = FileStore::open(&dir, 100);
+        assert!(res.is_err());
+        drop(store);
+        assert!(FileStore::open(&dir, 100).is_ok());
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::FileStore;
+    use std::{
+        ops::Deref,
+        path::{Path, PathBuf},
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    /// Directory removed again when dropped.
+    #[derive(Debug)]
+    pub struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub fn new(name: &str) -> Self {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "my_todo-{}-{}-{}",
+                name,
+                std::process::id(),
+                COUNT.fetch_add(1, Ordering::SeqCst)
+            ));
+            let _ = std::fs::remove_dir_all(&path);
+            Self(path)
+        }
+    }
+
+    impl Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl AsRef<Path> for TempDir {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A store in a fresh directory, which lives as long as the returned guard.
+    pub fn file_store(name: &str) -> (FileStore, TempDir) {
+        let dir = TempDir::new(name);
+        let store = FileStore::open(&dir, 100).expect("fail open file store");
+        (store, dir)
+    }
+}
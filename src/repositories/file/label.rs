@@ -0,0 +1,164 @@
+//! The label repository on the file store.
+
+use super::{Change, FileStore, LabelRow, State, TodoRow};
+use crate::repositories::{
+    label::{merge_sources, merged, Label, LabelDeleteMode, LabelRepository},
+    RepositoryError,
+};
+use anyhow::Ok;
+use axum::async_trait;
+
+#[derive(Debug, Clone)]
+pub struct LabelRepositoryForFile {
+    store: FileStore,
+}
+
+impl LabelRepositoryForFile {
+    pub fn new(store: FileStore) -> Self {
+        Self { store }
+    }
+}
+
+fn owned_label(state: &State, owner_id: i32, id: i32) -> Option<&LabelRow> {
+    state
+        .labels
+        .get(&id)
+        .filter(|label| label.owner_id == owner_id)
+}
+
+/// The todos label `id` is attached to, ordered by id.
+fn todos_labelled(state: &State, id: i32) -> Vec<i32> {
+    state
+        .todos
+        .values()
+        .filter(|todo| todo.labels.contains(&id))
+        .map(|todo| todo.id)
+        .collect()
+}
+
+/// The label of `owner_id` called `name` ignoring case, other than `id`.
+fn label_named(state: &State, owner_id: i32, name: &str, id: Option<i32>) -> Option<i32> {
+    state
+        .labels
+        .values()
+        .find(|label| {
+            label.owner_id == owner_id
+                && label.name.to_lowercase() == name.to_lowercase()
+                && Some(label.id) != id
+        })
+        .map(|label| label.id)
+}
+
+impl From<&LabelRow> for Label {
+    fn from(row: &LabelRow) -> Self {
+        Label {
+            id: row.id,
+            name: row.name.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl LabelRepository for LabelRepositoryForFile {
+    async fn create(&self, owner_id: i32, name: String) -> anyhow::Result<Label> {
+        self.store
+            .write(move |tx| {
+                if let Some(id) = label_named(tx.state(), owner_id, &name, None) {
+                    return Err(RepositoryError::Duplicate(id).into());
+                }
+                let row = LabelRow {
+                    id: tx.state().ids.label + 1,
+                    name,
+                    owner_id,
+                };
+                let label = Label::from(&row);
+                tx.apply(Change::PutLabel(row));
+                Ok(label)
+            })
+            .await
+    }
+
+    async fn all(&self, owner_id: i32) -> anyhow::Result<Vec<Label>> {
+        Ok(self.store.read(|state| {
+            state
+                .labels
+                .values()
+                .filter(|label| label.owner_id == owner_id)
+                .map(Label::from)
+                .collect()
+        }))
+    }
+
+    async fn update(&self, owner_id: i32, id: i32, name: String) -> anyhow::Result<Label> {
+        self.store
+            .write(move |tx| {
+                if let Some(id) = label_named(tx.state(), owner_id, &name, Some(id)) {
+                    return Err(RepositoryError::Duplicate(id).into());
+                }
+                let row = LabelRow {
+                    name,
+                    ..owned_label(tx.state(), owner_id, id)
+                        .ok_or(RepositoryError::NotFound(id))?
+                        .clone()
+                };
+                let label = Label::from(&row);
+                tx.apply(Change::PutLabel(row));
+                Ok(label)
+            })
+            .await
+    }
+
+    async fn usage(&self, owner_id: i32, id: i32) -> anyhow::Result<Vec<i32>> {
+        self.store.read(|state| {
+            owned_label(state, owner_id, id).ok_or(RepositoryError::NotFound(id))?;
+            Ok(todos_labelled(state, id))
+        })
+    }
+
+    async fn delete(&self, owner_id: i32, id: i32, mode: LabelDeleteMode) -> anyhow::Result<()> {
+        self.store
+            .write(move |tx| {
+                owned_label(tx.state(), owner_id, id).ok_or(RepositoryError::NotFound(id))?;
+                let todos = todos_labelled(tx.state(), id);
+                if !todos.is_empty() && mode == LabelDeleteMode::Restrict {
+                    return Err(RepositoryError::InUse { id, todos }.into());
+                }
+                for todo_id in todos {
+                    let mut todo = tx.state().todos[&todo_id].clone();
+                    todo.labels.retain(|label_id| *label_id != id);
+                    tx.apply(Change::PutTodo(todo));
+                }
+                tx.apply(Change::DeleteLabel(id));
+                Ok(())
+            })
+            .await
+    }
+
+    async fn merge(&self, owner_id: i32, id: i32, sources: Vec<i32>) -> anyhow::Result<usize> {
+        let sources = merge_sources(id, sources);
+        self.store
+            .write(move |tx| {
+                for label_id in std::iter::once(id).chain(sources.iter().copied()) {
+                    owned_label(tx.state(), owner_id, label_id)
+                        .ok_or(RepositoryError::NotFound(label_id))?;
+                }
+                let todos: Vec<TodoRow> = tx
+                    .state()
+                    .todos
+                    .values()
+                    .filter(|todo| todo.labels.iter().any(|label| sources.contains(label)))
+                    .cloned()
+                    .collect();
+                let affected = todos.len();
+                for todo in todos {
+                    let labels = merged(&todo.labels, |label| *label, id, &sources, id);
+                    tx.apply(Change::PutTodo(TodoRow { labels, ..todo }));
+                }
+                for source in sources {
+                    tx.apply(Change::DeleteLabel(source));
+                }
+                Ok(affected)
+            })
+            .await
+    }
+}
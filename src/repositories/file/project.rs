@@ -0,0 +1,231 @@
+//! The project repository on the file store.
+
+use super::{Change, FileStore, ProjectRow, State};
+use crate::repositories::{
+    project::{Project, ProjectRepository, DEFAULT_PROJECT_ID},
+    RepositoryError,
+};
+use anyhow::Ok;
+use axum::async_trait;
+
+#[derive(Debug, Clone)]
+pub struct ProjectRepositoryForFile {
+    store: FileStore,
+}
+
+impl ProjectRepositoryForFile {
+    pub fn new(store: FileStore) -> Self {
+        Self { store }
+    }
+}
+
+fn is_visible(project: &ProjectRow, owner_id: i32) -> bool {
+    project.owner_id == Some(owner_id) || project.id == DEFAULT_PROJECT_ID
+}
+
+/// The project visible to `owner_id` called `name`, other than `id`.
+fn project_named(state: &State, owner_id: i32, name: &str, id: Option<i32>) -> Option<i32> {
+    state
+        .projects
+        .values()
+        .find(|project| {
+            is_visible(project, owner_id) && project.name == name && Some(project.id) != id
+        })
+        .map(|project| project.id)
+}
+
+impl From<&ProjectRow> for Project {
+    fn from(row: &ProjectRow) -> Self {
+        Project {
+            id: row.id,
+            name: row.name.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl ProjectRepository for ProjectRepositoryForFile {
+    async fn create(&self, owner_id: i32, name: String) -> anyhow::Result<Project> {
+        self.store
+            .write(move |tx| {
+                if let Some(id) = project_named(tx.state(), owner_id, &name, None) {
+                    return Err(RepositoryError::Duplicate(id).into());
+                }
+                let row = ProjectRow {
+                    id: tx.state().ids.project + 1,
+                    name,
+                    owner_id: Some(owner_id),
+                };
+                let project = Project::from(&row);
+                tx.apply(Change::PutProject(row));
+                Ok(project)
+            })
+            .await
+    }
+
+    async fn find(&self, owner_id: i32, id: i32) -> anyhow::Result<Project> {
+        let project = self.store.read(|state| {
+            state
+                .projects
+                .get(&id)
+                .filter(|project| is_visible(project, owner_id))
+                .map(Project::from)
+        });
+        Ok(project.ok_or(RepositoryError::NotFound(id))?)
+    }
+
+    async fn all(&self, owner_id: i32) -> anyhow::Result<Vec<Project>> {
+        Ok(self.store.read(|state| {
+            state
+                .projects
+                .values()
+                .filter(|project| is_visible(project, owner_id))
+                .map(Project::from)
+                .collect()
+        }))
+    }
+
+    async fn update(&self, owner_id: i32, id: i32, name: String) -> anyhow::Result<Project> {
+        if id == DEFAULT_PROJECT_ID {
+            return Err(RepositoryError::Protected(id).into());
+        }
+        self.store
+            .write(move |tx| {
+                if let Some(id) = project_named(tx.state(), owner_id, &name, Some(id)) {
+                    return Err(RepositoryError::Duplicate(id).into());
+                }
+                let row = ProjectRow {
+                    name,
+                    ..tx.state()
+                        .projects
+                        .get(&id)
+                        .filter(|project| project.owner_id == Some(owner_id))
+                        .ok_or(RepositoryError::NotFound(id))?
+                        .clone()
+                };
+                let project = Project::from(&row);
+                tx.apply(Change::PutProject(row));
+                Ok(project)
+            })
+            .await
+    }
+
+    async fn delete(&self, owner_id: i32, id: i32) -> anyhow::Result<()> {
+        if id == DEFAULT_PROJECT_ID {
+            return Err(RepositoryError::Protected(id).into());
+        }
+        self.store
+            .write(move |tx| {
+                let state = tx.state();
+                state
+                    .projects
+                    .get(&id)
+                    .filter(|project| project.owner_id == Some(owner_id))
+                    .ok_or(RepositoryError::NotFound(id))?;
+                let todos: Vec<i32> = state
+                    .todos
+                    .values()
+                    .filter(|todo| todo.project_id == id)
+                    .map(|todo| todo.id)
+                    .collect();
+                if !todos.is_empty() {
+                    return Err(RepositoryError::NotEmpty { id, todos }.into());
+                }
+                tx.apply(Change::DeleteProject(id));
+                Ok(())
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::file::{test_utils::file_store, TodoRepositoryForFile};
+    use crate::repositories::todo::{CreateTodo, TodoRepository};
+    use crate::repositories::user::test_utils::file_fixture_user;
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let (store, _dir) = file_store("project-crud");
+        let owner = file_fixture_user(&store, "owner").await;
+        let other_owner = file_fixture_user(&store, "other owner").await;
+        let repository = ProjectRepositoryForFile::new(store.clone());
+
+        // create
+        let project = repository
+            .create(owner.id, "project".to_string())
+            .await
+            .expect("[create] returned Err");
+        assert_eq!(Project::new(2, "project".to_string()), project);
+        let res = repository
+            .create(owner.id, "Inbox".to_string())
+            .await
+            .expect_err("[create] duplicate name returned Ok");
+        assert!(matches!(
+            res.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Duplicate(DEFAULT_PROJECT_ID))
+        ));
+
+        // find and all
+        let found = repository
+            .find(owner.id, project.id)
+            .await
+            .expect("[find] returned Err");
+        assert_eq!(project, found);
+        let res = repository.find(other_owner.id, project.id).await;
+        assert!(res.is_err());
+        let projects = repository.all(owner.id).await.expect("[all] returned Err");
+        assert_eq!(vec![Project::inbox(), project.clone()], projects);
+        let projects = repository
+            .all(other_owner.id)
+            .await
+            .expect("[all] returned Err");
+        assert_eq!(vec![Project::inbox()], projects);
+
+        // update
+        let project = repository
+            .update(owner.id, project.id, "renamed".to_string())
+            .await
+            .expect("[update] returned Err");
+        assert_eq!("renamed", project.name);
+        let res = repository
+            .update(owner.id, DEFAULT_PROJECT_ID, "renamed inbox".to_string())
+            .await
+            .expect_err("[update] default project returned Ok");
+        assert!(matches!(
+            res.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Protected(DEFAULT_PROJECT_ID))
+        ));
+
+        // delete refuses while todos belong to the project
+        let todo_repository = TodoRepositoryForFile::new(store);
+        let todo = todo_repository
+            .create(
+                owner.id,
+                CreateTodo::new("todo".to_string(), vec![]).in_project(project.id),
+            )
+            .await
+            .expect("[create] returned Err");
+        let res = repository
+            .delete(owner.id, project.id)
+            .await
+            .expect_err("[delete] non empty project returned Ok");
+        assert!(matches!(
+            res.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotEmpty { todos, .. }) if *todos == vec![todo.id]
+        ));
+        todo_repository
+            .delete(owner.id, todo.id, Default::default())
+            .await
+            .expect("[delete] returned Err");
+        let res = repository.delete(other_owner.id, project.id).await;
+        assert!(res.is_err());
+        repository
+            .delete(owner.id, project.id)
+            .await
+            .expect("[delete] returned Err");
+        let res = repository.find(owner.id, project.id).await;
+        assert!(res.is_err());
+    }
+}
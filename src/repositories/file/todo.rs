@@ -0,0 +1,481 @@
+//! The todo repository on the file store.
+
+use super::{Change, FileStore, State, TodoRow, Tx};
+use crate::repositories::{
+    label::Label,
+    project::DEFAULT_PROJECT_ID,
+    todo::{
+        place, rebalanced, repeated_label, CreateTodo, DeleteMode, Placement, TodoEntity,
+        TodoListQuery, TodoPage, TodoRepository, TodoStats, UpdateTodo, POSITION_GAP,
+    },
+    RepositoryError,
+};
+use anyhow::Ok;
+use axum::async_trait;
+
+#[derive(Debug, Clone)]
+pub struct TodoRepositoryForFile {
+    store: FileStore,
+}
+
+impl TodoRepositoryForFile {
+    pub fn new(store: FileStore) -> Self {
+        TodoRepositoryForFile { store }
+    }
+}
+
+fn to_entity(state: &State, row: &TodoRow) -> TodoEntity {
+    TodoEntity {
+        id: row.id,
+        text: row.text.clone(),
+        completed: row.completed,
+        priority: row.priority,
+        due_at: row.due_at,
+        parent_id: row.parent_id,
+        auto_complete: row.auto_complete,
+        project_id: row.project_id,
+        position: row.position,
+        labels: row
+            .labels
+            .iter()
+            .filter_map(|id| state.labels.get(id))
+            .map(Label::from)
+            .collect(),
+    }
+}
+
+fn owned_todo(state: &State, owner_id: i32, id: i32) -> anyhow::Result<&TodoRow> {
+    Ok(state
+        .todos
+        .get(&id)
+        .filter(|todo| todo.owner_id == owner_id)
+        .ok_or(RepositoryError::NotFound(id))?)
+}
+
+fn children_of(state: &State, id: i32) -> impl Iterator<Item = &TodoRow> {
+    state
+        .todos
+        .values()
+        .filter(move |todo| todo.parent_id == Some(id))
+}
+
+/// Fails with `InvalidParent` unless `parent_id` exists and is not `id` or one of its subtasks.
+/// Returns the project of the parent.
+fn check_parent(
+    state: &State,
+    owner_id: i32,
+    id: Option<i32>,
+    parent_id: i32,
+) -> anyhow::Result<i32> {
+    let parent = owned_todo(state, owner_id, parent_id)
+        .or(Err(RepositoryError::InvalidParent(parent_id)))?;
+    let mut ancestor = Some(parent);
+    while let Some(current) = ancestor {
+        if Some(current.id) == id {
+            return Err(RepositoryError::InvalidParent(parent_id).into());
+        }
+        ancestor = current.parent_id.and_then(|id| state.todos.get(&id));
+    }
+    Ok(parent.project_id)
+}
+
+/// Fails with `InvalidProject` unless `project_id` exists and is visible to `owner_id`.
+fn check_project(state: &State, owner_id: i32, project_id: i32) -> anyhow::Result<()> {
+    state
+        .projects
+        .get(&project_id)
+        .filter(|project| project.owner_id == Some(owner_id) || project.id == DEFAULT_PROJECT_ID)
+        .ok_or(RepositoryError::InvalidProject(project_id))?;
+    Ok(())
+}
+
+/// Fails with `Duplicate` for a repeated label, then with `UnknownLabels` naming every label
+/// `owner_id` does not have.
+fn check_labels(state: &State, owner_id: i32, labels: &[i32]) -> anyhow::Result<()> {
+    if let Some(label_id) = repeated_label(labels) {
+        return Err(RepositoryError::Duplicate(label_id).into());
+    }
+    // a label of someone else is as good as missing
+    let unknown: Vec<i32> = labels
+        .iter()
+        .copied()
+        .filter(|id| {
+            state
+                .labels
+                .get(id)
+                .is_none_or(|label| label.owner_id != owner_id)
+        })
+        .collect();
+    if !unknown.is_empty() {
+        return Err(RepositoryError::UnknownLabels(unknown).into());
+    }
+    Ok(())
+}
+
+/// `(position, id)` of every todo of `owner_id` in `project_id`.
+fn positions(state: &State, owner_id: i32, project_id: i32) -> Vec<(i64, i32)> {
+    state
+        .todos
+        .values()
+        .filter(|todo| todo.owner_id == owner_id && todo.project_id == project_id)
+        .map(|todo| (todo.position, todo.id))
+        .collect()
+}
+
+fn last_position(state: &State, owner_id: i32, project_id: i32) -> i64 {
+    positions(state, owner_id, project_id)
+        .into_iter()
+        .map(|(position, _)| position)
+        .max()
+        .unwrap_or(0)
+}
+
+/// `id` and every todo below it, ordered by id.
+fn subtree(state: &State, id: i32) -> Vec<i32> {
+    let mut found = vec![];
+    let mut ids = vec![id];
+    while let Some(current) = ids.pop() {
+        found.push(current);
+        ids.extend(children_of(state, current).map(|todo| todo.id));
+    }
+    found.sort();
+    found
+}
+
+/// Recomputes the completion of `parent_id` and its ancestors that auto-complete,
+/// stopping at the first one that does not change.
+fn roll_up(tx: &mut Tx<'_>, mut parent_id: Option<i32>) {
+    while let Some(id) = parent_id {
+        let state = tx.state();
+        let Some(todo) = state.todos.get(&id) else {
+            return;
+        };
+        let children: Vec<bool> = children_of(state, id).map(|todo| todo.completed).collect();
+        let completed = children.iter().all(|completed| *completed);
+        if !todo.auto_complete || children.is_empty() || todo.completed == completed {
+            return;
+        }
+        let todo = TodoRow {
+            completed,
+            ..todo.clone()
+        };
+        parent_id = todo.parent_id;
+        tx.apply(Change::PutTodo(todo));
+    }
+}
+
+#[async_trait]
+impl TodoRepository for TodoRepositoryForFile {
+    async fn create(&self, owner_id: i32, payload: CreateTodo) -> anyhow::Result<TodoEntity> {
+        self.store
+            .write(move |tx| {
+                let state = tx.state();
+                let project_id = match payload.parent_id {
+                    Some(parent_id) => {
+                        let parent_project_id = check_parent(state, owner_id, None, parent_id)?;
+                        if payload
+                            .project_id
+                            .is_some_and(|project_id| project_id != parent_project_id)
+                        {
+                            return Err(RepositoryError::InvalidParent(parent_id).into());
+                        }
+                        parent_project_id
+                    }
+                    None => {
+                        let project_id = payload.project_id.unwrap_or(DEFAULT_PROJECT_ID);
+                        check_project(state, owner_id, project_id)?;
+                        project_id
+                    }
+                };
+                check_labels(state, owner_id, &payload.labels)?;
+                let id = state.ids.todo + 1;
+                let position = last_position(state, owner_id, project_id) + POSITION_GAP;
+                tx.apply(Change::PutTodo(TodoRow {
+                    id,
+                    text: payload.text,
+                    completed: false,
+                    priority: payload.priority,
+                    due_at: payload.due_at,
+                    parent_id: payload.parent_id,
+                    auto_complete: payload.auto_complete,
+                    project_id,
+                    owner_id,
+                    position,
+                    labels: payload.labels,
+                }));
+                roll_up(tx, payload.parent_id);
+                Ok(to_entity(tx.state(), &tx.state().todos[&id]))
+            })
+            .await
+    }
+
+    async fn find(&self, owner_id: i32, id: i32) -> anyhow::Result<TodoEntity> {
+        self.store
+            .read(|state| Ok(to_entity(state, owned_todo(state, owner_id, id)?)))
+    }
+
+    async fn list(&self, owner_id: i32, query: TodoListQuery) -> anyhow::Result<TodoPage> {
+        let mut todos: Vec<TodoEntity> = self.store.read(|state| {
+            state
+                .todos
+                .values()
+                .filter(|todo| todo.owner_id == owner_id)
+                .map(|todo| to_entity(state, todo))
+                .filter(|todo| query.filter.matches(todo) && query.is_after_cursor(todo))
+                .collect()
+        });
+        todos.sort_by(|a, b| query.compare(a, b));
+        todos.truncate(query.limit as usize + 1);
+        Ok(TodoPage::new(todos, &query))
+    }
+
+    async fn update(
+        &self,
+        owner_id: i32,
+        id: i32,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<TodoEntity> {
+        self.store
+            .write(move |tx| {
+                let state = tx.state();
+                let old_todo = owned_todo(state, owner_id, id)?.clone();
+                let parent_id = payload.parent_id.unwrap_or(old_todo.parent_id);
+                if let Some(parent_id) = parent_id.filter(|_| parent_id != old_todo.parent_id) {
+                    // subtasks always share the project of their parent
+                    if check_parent(state, owner_id, Some(id), parent_id)? != old_todo.project_id {
+                        return Err(RepositoryError::InvalidParent(parent_id).into());
+                    }
+                }
+                if let Some(labels) = &payload.labels {
+                    check_labels(state, owner_id, labels)?;
+                }
+                let auto_complete = payload.auto_complete.unwrap_or(old_todo.auto_complete);
+                tx.apply(Change::PutTodo(TodoRow {
+                    text: payload.text.unwrap_or(old_todo.text),
+                    completed: payload.completed.unwrap_or(old_todo.completed),
+                    priority: payload.priority.unwrap_or(old_todo.priority),
+                    due_at: payload.due_at.unwrap_or(old_todo.due_at),
+                    parent_id,
+                    auto_complete,
+                    labels: payload.labels.unwrap_or(old_todo.labels),
+                    ..old_todo
+                }));
+
+                if auto_complete && !old_todo.auto_complete {
+                    roll_up(tx, Some(id));
+                }
+                roll_up(tx, parent_id);
+                if old_todo.parent_id != parent_id {
+                    roll_up(tx, old_todo.parent_id);
+                }
+
+                Ok(to_entity(tx.state(), &tx.state().todos[&id]))
+            })
+            .await
+    }
+
+    async fn delete(&self, owner_id: i32, id: i32, mode: DeleteMode) -> anyhow::Result<()> {
+        self.store
+            .write(move |tx| {
+                let state = tx.state();
+                let parent_id = owned_todo(state, owner_id, id)?.parent_id;
+                let children: Vec<i32> = children_of(state, id).map(|todo| todo.id).collect();
+                if !children.is_empty() && mode == DeleteMode::Restrict {
+                    return Err(RepositoryError::HasChildren { id, children }.into());
+                }
+                for id in subtree(state, id) {
+                    tx.apply(Change::DeleteTodo(id));
+                }
+                roll_up(tx, parent_id);
+                Ok(())
+            })
+            .await
+    }
+
+    async fn children(&self, owner_id: i32, id: i32) -> anyhow::Result<Vec<TodoEntity>> {
+        self.store.read(|state| {
+            owned_todo(state, owner_id, id)?;
+            Ok(children_of(state, id)
+                .map(|todo| to_entity(state, todo))
+                .collect())
+        })
+    }
+
+    async fn descendants(&self, owner_id: i32, ids: Vec<i32>) -> anyhow::Result<Vec<TodoEntity>> {
+        Ok(self.store.read(|state| {
+            let mut found: Vec<TodoEntity> = vec![];
+            let mut parents: Vec<i32> = ids
+                .into_iter()
+                .filter(|id| owned_todo(state, owner_id, *id).is_ok())
+                .collect();
+            while let Some(parent_id) = parents.pop() {
+                for todo in children_of(state, parent_id) {
+                    parents.push(todo.id);
+                    found.push(to_entity(state, todo));
+                }
+            }
+            found.sort_by_key(|todo| todo.id);
+            found
+        }))
+    }
+
+    async fn move_to_project(
+        &self,
+        owner_id: i32,
+        id: i32,
+        project_id: i32,
+    ) -> anyhow::Result<TodoEntity> {
+        self.store
+            .write(move |tx| {
+                let state = tx.state();
+                let old_todo = owned_todo(state, owner_id, id)?;
+                if old_todo.project_id == project_id {
+                    return Ok(to_entity(state, old_todo));
+                }
+                let old_parent_id = old_todo.parent_id;
+                check_project(state, owner_id, project_id)?;
+                // appended to the order of the new project, keeping their own
+                let tail = last_position(state, owner_id, project_id);
+                let mut ids = subtree(state, id);
+                ids.sort_by_key(|current| (state.todos[current].position, *current));
+                let moved: Vec<TodoRow> = rebalanced(ids)
+                    .into_iter()
+                    .map(|(current, position)| TodoRow {
+                        project_id,
+                        parent_id: match current == id {
+                            true => None,
+                            false => state.todos[&current].parent_id,
+                        },
+                        position: tail + position,
+                        ..state.todos[&current].clone()
+                    })
+                    .collect();
+                for todo in moved {
+                    tx.apply(Change::PutTodo(todo));
+                }
+                roll_up(tx, old_parent_id);
+
+                Ok(to_entity(tx.state(), &tx.state().todos[&id]))
+            })
+            .await
+    }
+
+    async fn reorder(
+        &self,
+        owner_id: i32,
+        id: i32,
+        placement: Placement,
+    ) -> anyhow::Result<TodoEntity> {
+        self.store
+            .write(move |tx| {
+                let state = tx.state();
+                let todo = owned_todo(state, owner_id, id)?;
+                if let Placement::Before(target) | Placement::After(target) = placement {
+                    if target == id {
+                        return Ok(to_entity(state, todo));
+                    }
+                    if owned_todo(state, owner_id, target)
+                        .ok()
+                        .is_none_or(|target| target.project_id != todo.project_id)
+                    {
+                        return Err(RepositoryError::InvalidPlacement(target).into());
+                    }
+                }
+                let placed: Vec<TodoRow> =
+                    place(positions(state, owner_id, todo.project_id), id, placement)
+                        .into_iter()
+                        .map(|(current, position)| TodoRow {
+                            position,
+                            ..state.todos[&current].clone()
+                        })
+                        .collect();
+                for todo in placed {
+                    tx.apply(Change::PutTodo(todo));
+                }
+
+                Ok(to_entity(tx.state(), &tx.state().todos[&id]))
+            })
+            .await
+    }
+
+    async fn stats(&self) -> anyhow::Result<TodoStats> {
+        Ok(self.store.read(|state| {
+            let mut stats = TodoStats::default();
+            for todo in state.todos.values() {
+                if todo.completed {
+                    stats.completed += 1;
+                } else {
+                    stats.open += 1;
+                }
+                for &label_id in &todo.labels {
+                    *stats.by_label.entry(label_id).or_default() += 1;
+                }
+            }
+            stats
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::file::{
+        test_utils::file_store, FileStore, LabelRepositoryForFile, ProjectRepositoryForFile,
+    };
+    use crate::repositories::label::LabelRepository;
+    use crate::repositories::project::ProjectRepository;
+    use crate::repositories::todo::contract;
+    use crate::repositories::user::test_utils::file_fixture_user;
+
+    #[tokio::test]
+    async fn contract_scenario() {
+        let (store, _dir) = file_store("todo-contract");
+        let owner = file_fixture_user(&store, "owner").await;
+        let other_owner = file_fixture_user(&store, "other owner").await;
+        let project = ProjectRepositoryForFile::new(store.clone())
+            .create(owner.id, "project".to_string())
+            .await
+            .unwrap();
+        contract::run(&contract::Fixture {
+            todos: TodoRepositoryForFile::new(store.clone()),
+            labels: LabelRepositoryForFile::new(store),
+            owner: owner.id,
+            other_owner: other_owner.id,
+            project: project.id,
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn acknowledged_writes_survive_a_restart() {
+        let (store, dir) = file_store("todo-restart");
+        let owner = file_fixture_user(&store, "owner").await;
+        let label = LabelRepositoryForFile::new(store.clone())
+            .create(owner.id, "label".to_string())
+            .await
+            .unwrap();
+        let repository = TodoRepositoryForFile::new(store.clone());
+        let todo = repository
+            .create(
+                owner.id,
+                CreateTodo::new("text".to_string(), vec![label.id]),
+            )
+            .await
+            .expect("[create] returned Err");
+        let res = repository
+            .create(
+                owner.id,
+                CreateTodo::new("text".to_string(), vec![i32::MAX]),
+            )
+            .await;
+        assert!(res.is_err());
+
+        // everything acknowledged survives a restart without a snapshot
+        drop((repository, store));
+        let store = FileStore::open(&dir, 100).expect("fail reopen file store");
+        let repository = TodoRepositoryForFile::new(store.clone());
+        assert_eq!(todo, repository.find(owner.id, todo.id).await.unwrap());
+        // the failed create left no todo behind
+        assert!(store.read(|state| state.todos.len() == 1));
+    }
+}
@@ -0,0 +1,288 @@
+//! The user repository on the file store.
+
+use super::{Change, FileStore, SessionRow, TokenRow, UserRow};
+use crate::repositories::{
+    user::{ApiToken, CreateApiToken, Scope, User, UserRepository},
+    RepositoryError,
+};
+use anyhow::Ok;
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone)]
+pub struct UserRepositoryForFile {
+    store: FileStore,
+}
+
+impl UserRepositoryForFile {
+    pub fn new(store: FileStore) -> Self {
+        Self { store }
+    }
+}
+
+impl From<&UserRow> for User {
+    fn from(row: &UserRow) -> Self {
+        User {
+            id: row.id,
+            username: row.username.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl UserRepository for UserRepositoryForFile {
+    async fn create(&self, username: String, password_hash: String) -> anyhow::Result<User> {
+        self.store
+            .write(move |tx| {
+                if let Some(user) = tx
+                    .state()
+                    .users
+                    .values()
+                    .find(|user| user.username == username)
+                {
+                    return Err(RepositoryError::Duplicate(user.id).into());
+                }
+                let row = UserRow {
+                    id: tx.state().ids.user + 1,
+                    username,
+                    password_hash,
+                };
+                let user = User::from(&row);
+                tx.apply(Change::PutUser(row));
+                Ok(user)
+            })
+            .await
+    }
+
+    async fn credentials(&self, username: String) -> anyhow::Result<Option<(User, String)>> {
+        Ok(self.store.read(|state| {
+            state
+                .users
+                .values()
+                .find(|user| user.username == username)
+                .map(|user| (User::from(user), user.password_hash.clone()))
+        }))
+    }
+
+    async fn create_session(
+        &self,
+        user_id: i32,
+        token_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        self.store
+            .write(move |tx| {
+                if !tx.state().users.contains_key(&user_id) {
+                    return Err(RepositoryError::NotFound(user_id).into());
+                }
+                tx.apply(Change::PutSession(SessionRow {
+                    token_hash,
+                    user_id,
+                    expires_at,
+                }));
+                Ok(())
+            })
+            .await
+    }
+
+    async fn find_session(
+        &self,
+        token_hash: String,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Option<User>> {
+        Ok(self.store.read(|state| {
+            state
+                .sessions
+                .get(&token_hash)
+                .filter(|session| session.expires_at > now)
+                .and_then(|session| state.users.get(&session.user_id))
+                .map(User::from)
+        }))
+    }
+
+    async fn delete_session(&self, token_hash: String) -> anyhow::Result<()> {
+        self.store
+            .write(move |tx| {
+                if tx.state().sessions.contains_key(&token_hash) {
+                    tx.apply(Change::DeleteSession(token_hash));
+                }
+                Ok(())
+            })
+            .await
+    }
+
+    async fn create_token(
+        &self,
+        user_id: i32,
+        token_hash: String,
+        payload: CreateApiToken,
+    ) -> anyhow::Result<ApiToken> {
+        self.store
+            .write(move |tx| {
+                if !tx.state().users.contains_key(&user_id) {
+                    return Err(RepositoryError::NotFound(user_id).into());
+                }
+                let token = ApiToken {
+                    id: tx.state().ids.token + 1,
+                    name: payload.name,
+                    scopes: payload.scopes,
+                    created_at: Utc::now(),
+                    last_used_at: None,
+                    expires_at: payload.expires_at,
+                };
+                tx.apply(Change::PutToken(TokenRow {
+                    user_id,
+                    token_hash,
+                    token: token.clone(),
+                }));
+                Ok(token)
+            })
+            .await
+    }
+
+    async fn tokens(&self, user_id: i32) -> anyhow::Result<Vec<ApiToken>> {
+        Ok(self.store.read(|state| {
+            state
+                .tokens
+                .values()
+                .filter(|row| row.user_id == user_id)
+                .map(|row| row.token.clone())
+                .collect()
+        }))
+    }
+
+    async fn delete_token(&self, user_id: i32, id: i32) -> anyhow::Result<()> {
+        self.store
+            .write(move |tx| {
+                tx.state()
+                    .tokens
+                    .get(&id)
+                    .filter(|row| row.user_id == user_id)
+                    .ok_or(RepositoryError::NotFound(id))?;
+                tx.apply(Change::DeleteToken(id));
+                Ok(())
+            })
+            .await
+    }
+
+    async fn use_token(
+        &self,
+        token_hash: String,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Option<(User, Vec<Scope>)>> {
+        self.store
+            .write(move |tx| {
+                let state = tx.state();
+                let Some(row) = state.tokens.values().find(|row| {
+                    row.token_hash == token_hash
+                        && row
+                            .token
+                            .expires_at
+                            .is_none_or(|expires_at| expires_at > now)
+                }) else {
+                    return Ok(None);
+                };
+                let user = state.users.get(&row.user_id).map(User::from);
+                let scopes = row.token.scopes.clone();
+                let mut row = row.clone();
+                row.token.last_used_at = Some(now);
+                tx.apply(Change::PutToken(row));
+                Ok(user.map(|user| (user, scopes)))
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::file::test_utils::file_store;
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn session_and_token_scenario() {
+        let (store, _dir) = file_store("user");
+        let repository = UserRepositoryForFile::new(store);
+        let user = repository
+            .create("alice".to_string(), "hash".to_string())
+            .await
+            .expect("[create] returned Err");
+        let res = repository
+            .create("alice".to_string(), "other".to_string())
+            .await
+            .expect_err("[create] duplicate username returned Ok");
+        assert!(matches!(
+            res.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Duplicate(id)) if *id == user.id
+        ));
+        let credentials = repository
+            .credentials("alice".to_string())
+            .await
+            .expect("[credentials] returned Err");
+        assert_eq!(Some((user.clone(), "hash".to_string())), credentials);
+
+        // sessions
+        let now = Utc::now();
+        repository
+            .create_session(user.id, "session".to_string(), now + Duration::hours(1))
+            .await
+            .expect("[create_session] returned Err");
+        let found = repository
+            .find_session("session".to_string(), now)
+            .await
+            .expect("[find_session] returned Err");
+        assert_eq!(Some(user.clone()), found);
+        let expired = repository
+            .find_session("session".to_string(), now + Duration::hours(2))
+            .await
+            .expect("[find_session] returned Err");
+        assert_eq!(None, expired);
+        repository
+            .delete_session("session".to_string())
+            .await
+            .expect("[delete_session] returned Err");
+        let found = repository
+            .find_session("session".to_string(), now)
+            .await
+            .expect("[find_session] returned Err");
+        assert_eq!(None, found);
+
+        // tokens
+        let payload = CreateApiToken {
+            name: "ci".to_string(),
+            scopes: vec![Scope::TodosRead, Scope::LabelsWrite],
+            expires_at: Some(now + Duration::hours(1)),
+        };
+        let token = repository
+            .create_token(user.id, "token".to_string(), payload)
+            .await
+            .expect("[create_token] returned Err");
+        assert_eq!(vec![Scope::TodosRead, Scope::LabelsWrite], token.scopes);
+        let used = repository
+            .use_token("token".to_string(), now)
+            .await
+            .expect("[use_token] returned Err");
+        assert_eq!(
+            Some((user.clone(), vec![Scope::TodosRead, Scope::LabelsWrite])),
+            used
+        );
+        let tokens = repository
+            .tokens(user.id)
+            .await
+            .expect("[tokens] returned Err");
+        assert_eq!(Some(now), tokens[0].last_used_at);
+        let expired = repository
+            .use_token("token".to_string(), now + Duration::hours(2))
+            .await
+            .expect("[use_token] returned Err");
+        assert_eq!(None, expired);
+
+        let res = repository.delete_token(user.id + 1, token.id).await;
+        assert!(res.is_err());
+        repository
+            .delete_token(user.id, token.id)
+            .await
+            .expect("[delete_token] returned Err");
+        assert!(repository.tokens(user.id).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,255 @@
+use crate::migrations::{self, SchemaStatus};
+use crate::repositories::file::FileStore;
+use anyhow::{bail, Ok};
+use axum::async_trait;
+use sqlx::{PgPool, SqlitePool};
+
+/// The store underneath the other repositories, as seen by `/readyz` and `/metrics`.
+#[async_trait]
+pub trait HealthRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    /// A cheap round trip to the store.
+    async fn ping(&self) -> anyhow::Result<()>;
+    /// Fails unless the schema is exactly the one this binary was built for.
+    async fn migrations(&self) -> anyhow::Result<()>;
+    /// The connection pool, for stores that have one.
+    fn pool(&self) -> Option<PoolStats>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Open connections, idle or in use.
+    pub size: u32,
+    pub idle: usize,
+}
+
+/// Fails unless `status` has every migration of this binary applied and none besides.
+fn check_schema(status: Option<SchemaStatus>) -> anyhow::Result<()> {
+    let Some(status) = status else {
+        bail!("no migrations have been applied");
+    };
+    if !status.unknown.is_empty() {
+        bail!(
+            "database schema is newer than this binary, unknown migrations {:?}",
+            status.unknown
+        );
+    }
+    if !status.pending.is_empty() {
+        bail!("migrations {:?} are pending", status.pending);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthRepositoryForDb {
+    pool: PgPool,
+}
+
+impl HealthRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl HealthRepository for HealthRepositoryForDb {
+    async fn ping(&self) -> anyhow::Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn migrations(&self) -> anyhow::Result<()> {
+        check_schema(migrations::peek_status(&self.pool).await?)
+    }
+
+    fn pool(&self) -> Option<PoolStats> {
+        Some(PoolStats {
+            size: self.pool.size(),
+            idle: self.pool.num_idle(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthRepositoryForSqlite {
+    pool: SqlitePool,
+}
+
+impl HealthRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl HealthRepository for HealthRepositoryForSqlite {
+    async fn ping(&self) -> anyhow::Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn migrations(&self) -> anyhow::Result<()> {
+        check_schema(migrations::peek_status(&self.pool).await?)
+    }
+
+    fn pool(&self) -> Option<PoolStats> {
+        Some(PoolStats {
+            size: self.pool.size(),
+            idle: self.pool.num_idle(),
+        })
+    }
+}
+
+/// The file store has no schema to migrate and no pool, only its journal can fail.
+#[derive(Debug, Clone)]
+pub struct HealthRepositoryForFile {
+    store: FileStore,
+}
+
+impl HealthRepositoryForFile {
+    pub fn new(store: FileStore) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl HealthRepository for HealthRepositoryForFile {
+    async fn ping(&self) -> anyhow::Result<()> {
+        self.store.check()
+    }
+
+    async fn migrations(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn pool(&self) -> Option<PoolStats> {
+        None
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "database-test")]
+mod test {
+    use super::*;
+    use dotenv::dotenv;
+    use std::env;
+
+    #[tokio::test]
+    async fn health_scenario() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        migrations::up(&pool).await.expect("[up] returned Err");
+        let repository = HealthRepositoryForDb::new(pool.clone());
+
+        repository.ping().await.expect("[ping] returned Err");
+        repository
+            .migrations()
+            .await
+            .expect("[migrations] returned Err");
+        let stats = repository.pool().expect("[pool] returned None");
+        assert!(stats.idle <= stats.size as usize);
+
+        pool.close().await;
+        assert!(repository.ping().await.is_err());
+    }
+}
+
+#[cfg(test)]
+mod sqlite_test {
+    use super::*;
+    use crate::migrations::test_utils::sqlite_pool;
+
+    #[tokio::test]
+    async fn health_scenario() {
+        let pool = sqlite_pool().await;
+        let repository = HealthRepositoryForSqlite::new(pool.clone());
+
+        repository.ping().await.expect("[ping] returned Err");
+        repository
+            .migrations()
+            .await
+            .expect("[migrations] returned Err");
+        let stats = repository.pool().expect("[pool] returned None");
+        assert_eq!(1, stats.size, "the test pool keeps its only connection");
+        assert!(stats.idle <= 1);
+
+        pool.close().await;
+        assert!(repository.ping().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn unmigrated_database_is_not_ready() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let repository = HealthRepositoryForSqlite::new(pool.clone());
+
+        assert!(repository.migrations().await.is_err());
+        // the probe only reads, the migrations table is still missing
+        assert_eq!(None, migrations::peek_status(&pool).await.unwrap());
+    }
+}
+
+#[cfg(test)]
+mod file_test {
+    use super::*;
+    use crate::repositories::file::test_utils::file_store;
+
+    #[tokio::test]
+    async fn health_scenario() {
+        let (store, _dir) = file_store("health");
+        let repository = HealthRepositoryForFile::new(store);
+
+        repository.ping().await.expect("[ping] returned Err");
+        repository
+            .migrations()
+            .await
+            .expect("[migrations] returned Err");
+        assert_eq!(None, repository.pool());
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+
+    #[derive(Debug, Clone, Default)]
+    pub struct HealthRepositoryForMemory {
+        /// Message every check fails with, if any.
+        failure: Option<String>,
+    }
+
+    impl HealthRepositoryForMemory {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn failing(message: &str) -> Self {
+            Self {
+                failure: Some(message.to_string()),
+            }
+        }
+
+        fn check(&self) -> anyhow::Result<()> {
+            match &self.failure {
+                Some(message) => bail!("{}", message),
+                None => Ok(()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl HealthRepository for HealthRepositoryForMemory {
+        async fn ping(&self) -> anyhow::Result<()> {
+            self.check()
+        }
+
+        async fn migrations(&self) -> anyhow::Result<()> {
+            self.check()
+        }
+
+        fn pool(&self) -> Option<PoolStats> {
+            None
+        }
+    }
+}
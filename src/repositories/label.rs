@@ -1,15 +1,32 @@
-use crate::repositories::RepositoryError;
+use crate::repositories::{is_unique_violation, unit_of_work, Dialect, RepositoryError};
 use anyhow::Ok;
 use axum::async_trait;
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
 use validator::Validate;
 
 #[async_trait]
 pub trait LabelRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
-    async fn create(&self, name: String) -> anyhow::Result<Label>;
-    async fn all(&self) -> anyhow::Result<Vec<Label>>;
-    async fn delete(&self, id: i32) -> anyhow::Result<()>;
+    async fn create(&self, owner_id: i32, name: String) -> anyhow::Result<Label>;
+    async fn all(&self, owner_id: i32) -> anyhow::Result<Vec<Label>>;
+    async fn update(&self, owner_id: i32, id: i32, name: String) -> anyhow::Result<Label>;
+    /// Ids of the todos the label is attached to, ordered by id.
+    async fn usage(&self, owner_id: i32, id: i32) -> anyhow::Result<Vec<i32>>;
+    async fn delete(&self, owner_id: i32, id: i32, mode: LabelDeleteMode) -> anyhow::Result<()>;
+    /// Moves the todos of every label in `sources` onto label `id`, then deletes `sources`.
+    /// A todo keeps a single one of them, in the place of the first.
+    /// Returns how many todos carried any of `sources`.
+    async fn merge(&self, owner_id: i32, id: i32, sources: Vec<i32>) -> anyhow::Result<usize>;
+}
+
+/// What deleting a label that is still attached to todos does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelDeleteMode {
+    /// Take the label off those todos, which are kept.
+    #[default]
+    Detach,
+    /// Refuse with `RepositoryError::InUse`.
+    Restrict,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, sqlx::FromRow)]
@@ -25,126 +42,67 @@ pub struct CreateLabel {
     pub name: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
 pub struct UpdateLabel {
-    pub id: i32,
+    #[validate(length(min = 1, message = "can not be empty"))]
+    #[validate(length(max = 100, message = "Over text length"))]
     pub name: String,
 }
 
-#[derive(Debug, Clone)]
-pub struct LabelRepositoryForDb {
-    pool: PgPool,
-}
-
-impl LabelRepositoryForDb {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
-    }
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+pub struct MergeLabels {
+    /// The labels merged into the one of the path.
+    #[validate(length(min = 1, message = "can not be empty"))]
+    pub sources: Vec<i32>,
 }
 
-#[async_trait]
-impl LabelRepository for LabelRepositoryForDb {
-    async fn create(&self, name: String) -> anyhow::Result<Label> {
-        let optional_label = sqlx::query_as::<_, Label>(
-            r#"
-                SELECT * FROM labels WHERE name = $1
-            "#,
-        )
-        .bind(name.clone())
-        .fetch_optional(&self.pool)
-        .await?;
-
-        if let Some(label) = optional_label {
-            return Err(RepositoryError::Duplicate(label.id).into());
+/// `sources` without repeats or the target `id`, which merging into itself leaves alone.
+pub(crate) fn merge_sources(id: i32, sources: Vec<i32>) -> Vec<i32> {
+    let mut unique = Vec::with_capacity(sources.len());
+    for source in sources {
+        if source != id && !unique.contains(&source) {
+            unique.push(source);
         }
-
-        let label = sqlx::query_as::<_, Label>(
-            r#"
-                INSERT INTO labels ( name )
-                VALUES ( $1 )
-                RETURNING *
-            "#,
-        )
-        .bind(name.clone())
-        .fetch_one(&self.pool)
-        .await?;
-
-        Ok(label)
-    }
-
-    async fn all(&self) -> anyhow::Result<Vec<Label>> {
-        let labels = sqlx::query_as::<_, Label>(
-            r#"
-                SELECT * FROM labels
-                ORDER BY labels.id ASC;
-            "#,
-        )
-        .fetch_all(&self.pool)
-        .await?;
-
-        Ok(labels)
-    }
-
-    async fn delete(&self, id: i32) -> anyhow::Result<()> {
-        sqlx::query(
-            r#"
-                DELETE FROM labels WHERE id = $1
-            "#,
-        )
-        .bind(id)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
-            _ => RepositoryError::Unexpected(e.to_string()),
-        })?;
-
-        Ok(())
     }
+    unique
 }
 
-#[cfg(test)]
-#[cfg(feature = "database-test")]
-mod test {
-    use super::*;
-    use dotenv::dotenv;
-    use sqlx::PgPool;
-    use std::env;
-
-    #[tokio::test]
-    async fn crud_scenario() {
-        dotenv().ok();
-        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
-        let pool = PgPool::connect(database_url)
-            .await
-            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
-
-        let repository = LabelRepositoryForDb::new(pool);
-        let label_text = "test_label";
-
-        // create
-        let label = repository
-            .create(label_text.to_string())
-            .await
-            .expect("[create] returned Err");
-        assert_eq!(label.name, label_text);
-
-        // all
-        let labels = repository.all().await.expect("[all] returned Err");
-        let label = labels.last().unwrap();
-        assert_eq!(label.name, label_text);
-
-        // delete
-        repository
-            .delete(label.id)
-            .await
-            .expect("[delete] returned Err")
-    }
+/// `labels` with the first of `id` and `sources` turned into `target` and the others dropped.
+pub(crate) fn merged<T: Clone>(
+    labels: &[T],
+    label_id: impl Fn(&T) -> i32,
+    id: i32,
+    sources: &[i32],
+    target: T,
+) -> Vec<T> {
+    let mut kept = false;
+    labels
+        .iter()
+        .filter_map(|label| {
+            let label_id = label_id(label);
+            if label_id != id && !sources.contains(&label_id) {
+                Some(label.clone())
+            } else if !kept {
+                kept = true;
+                Some(target.clone())
+            } else {
+                None
+            }
+        })
+        .collect()
 }
 
+mod pg;
+mod sql;
+mod sqlite;
+
+pub use pg::LabelRepositoryForDb;
+pub use sqlite::LabelRepositoryForSqlite;
+
 #[cfg(test)]
 pub mod test_utils {
     use super::*;
+    use crate::repositories::todo::test_utils::{TodoDatas, TodoStore};
     use axum::async_trait;
     use std::{
         collections::HashMap,
@@ -164,79 +122,164 @@ pub mod test_utils {
     }
 
     impl UpdateLabel {
-        pub fn _new(id: i32, name: String) -> Self {
-            Self { id, name }
+        pub fn _new(name: String) -> Self {
+            Self { name }
         }
     }
 
     type LabelDatas = HashMap<i32, Label>;
 
+    /// Labels of every owner, keyed by owner id.
     #[derive(Debug, Clone)]
     pub struct LabelRepositoryForMemory {
-        store: Arc<RwLock<LabelDatas>>,
+        store: Arc<RwLock<HashMap<i32, LabelDatas>>>,
+        /// The todos the labels are attached to, shared with
+        /// `TodoRepositoryForMemory::with_labels`.
+        todos: TodoStore,
     }
 
     impl LabelRepositoryForMemory {
         pub fn new() -> Self {
             LabelRepositoryForMemory {
                 store: Arc::default(),
+                todos: Arc::default(),
             }
         }
 
-        fn write_store_ref(&self) -> RwLockWriteGuard<LabelDatas> {
+        pub fn todos(&self) -> TodoStore {
+            self.todos.clone()
+        }
+
+        fn write_store_ref(&self) -> RwLockWriteGuard<'_, HashMap<i32, LabelDatas>> {
             self.store.write().unwrap()
         }
 
-        fn read_store_ref(&self) -> RwLockReadGuard<LabelDatas> {
+        fn read_store_ref(&self) -> RwLockReadGuard<'_, HashMap<i32, LabelDatas>> {
             self.store.read().unwrap()
         }
+
+        /// A snapshot of the labels of `owner_id`.
+        pub fn owned(&self, owner_id: i32) -> LabelDatas {
+            self.read_store_ref()
+                .get(&owner_id)
+                .cloned()
+                .unwrap_or_default()
+        }
     }
 
     #[async_trait]
     impl LabelRepository for LabelRepositoryForMemory {
-        async fn create(&self, name: String) -> anyhow::Result<Label> {
-            let mut store = self.write_store_ref();
-            let id = (store.len() + 1) as i32;
+        async fn create(&self, owner_id: i32, name: String) -> anyhow::Result<Label> {
+            let mut stores = self.write_store_ref();
+            let id = stores
+                .values()
+                .flat_map(|store| store.keys())
+                .max()
+                .map_or(1, |id| id + 1);
+            let store = stores.entry(owner_id).or_default();
+            if let Some(label) = store
+                .values()
+                .find(|label| label.name.to_lowercase() == name.to_lowercase())
+            {
+                return Err(RepositoryError::Duplicate(label.id).into());
+            }
             let label = Label::new(id, name.clone());
             store.insert(id, label.clone());
             Ok(label)
         }
 
-        async fn all(&self) -> anyhow::Result<Vec<Label>> {
-            let store = self.read_store_ref();
-            Ok(Vec::from_iter(store.values().cloned()))
+        async fn all(&self, owner_id: i32) -> anyhow::Result<Vec<Label>> {
+            let mut labels: Vec<Label> = self.owned(owner_id).into_values().collect();
+            labels.sort_by_key(|label| label.id);
+            Ok(labels)
+        }
+
+        async fn update(&self, owner_id: i32, id: i32, name: String) -> anyhow::Result<Label> {
+            // always the todos before the labels, the order `TodoRepositoryForMemory` locks them in
+            let mut todos = self.todos.write().unwrap();
+            let mut stores = self.write_store_ref();
+            let store = stores.entry(owner_id).or_default();
+            if let Some(label) = store
+                .values()
+                .find(|label| label.id != id && label.name.to_lowercase() == name.to_lowercase())
+            {
+                return Err(RepositoryError::Duplicate(label.id).into());
+            }
+            let label = store.get_mut(&id).ok_or(RepositoryError::NotFound(id))?;
+            label.name = name;
+            for todo in todos.entry(owner_id).or_default().values_mut() {
+                for attached in todo.labels.iter_mut().filter(|attached| attached.id == id) {
+                    attached.name = label.name.clone();
+                }
+            }
+            Ok(label.clone())
+        }
+
+        async fn usage(&self, owner_id: i32, id: i32) -> anyhow::Result<Vec<i32>> {
+            let todos = self.todos.read().unwrap();
+            self.owned(owner_id)
+                .get(&id)
+                .ok_or(RepositoryError::NotFound(id))?;
+            Ok(todos_labelled(todos.get(&owner_id), id))
         }
 
-        async fn delete(&self, id: i32) -> anyhow::Result<()> {
-            let mut store = self.write_store_ref();
-            store.remove(&id).ok_or(RepositoryError::NotFound(id))?;
+        async fn delete(
+            &self,
+            owner_id: i32,
+            id: i32,
+            mode: LabelDeleteMode,
+        ) -> anyhow::Result<()> {
+            let mut todos = self.todos.write().unwrap();
+            let mut stores = self.write_store_ref();
+            let store = stores
+                .get_mut(&owner_id)
+                .filter(|store| store.contains_key(&id))
+                .ok_or(RepositoryError::NotFound(id))?;
+            let usage = todos_labelled(todos.get(&owner_id), id);
+            if !usage.is_empty() && mode == LabelDeleteMode::Restrict {
+                return Err(RepositoryError::InUse { id, todos: usage }.into());
+            }
+            for todo in todos.entry(owner_id).or_default().values_mut() {
+                todo.labels.retain(|label| label.id != id);
+            }
+            store.remove(&id);
             Ok(())
         }
-    }
-    mod test {
-        use super::*;
-
-        #[tokio::test]
-        async fn label_crud_scenario() {
-            let name = "label name".to_string();
-            let id = 1;
-            let expected = Label::new(id, name);
-
-            // create
-            let repository = LabelRepositoryForMemory::new();
-            let label = repository
-                .create("label name".to_string())
-                .await
-                .expect("faild create label");
-            assert_eq!(expected, label);
-
-            // all
-            let labels = repository.all().await.expect("faild all label");
-            assert_eq!(vec![expected], labels);
-
-            // delete
-            let res = repository.delete(id).await;
-            assert!(res.is_ok());
+
+        async fn merge(&self, owner_id: i32, id: i32, sources: Vec<i32>) -> anyhow::Result<usize> {
+            let sources = merge_sources(id, sources);
+            let mut todos = self.todos.write().unwrap();
+            let mut stores = self.write_store_ref();
+            let store = stores.entry(owner_id).or_default();
+            for label_id in std::iter::once(id).chain(sources.iter().copied()) {
+                store
+                    .get(&label_id)
+                    .ok_or(RepositoryError::NotFound(label_id))?;
+            }
+            let target = store[&id].clone();
+            let mut affected = 0;
+            for todo in todos.entry(owner_id).or_default().values_mut() {
+                if todo.labels.iter().any(|label| sources.contains(&label.id)) {
+                    affected += 1;
+                    todo.labels =
+                        merged(&todo.labels, |label| label.id, id, &sources, target.clone());
+                }
+            }
+            for source in sources {
+                store.remove(&source);
+            }
+            Ok(affected)
         }
     }
+
+    fn todos_labelled(todos: Option<&TodoDatas>, id: i32) -> Vec<i32> {
+        let mut usage: Vec<i32> = todos
+            .into_iter()
+            .flat_map(|todos| todos.values())
+            .filter(|todo| todo.labels.iter().any(|label| label.id == id))
+            .map(|todo| todo.id)
+            .collect();
+        usage.sort();
+        usage
+    }
 }
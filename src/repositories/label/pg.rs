@@ -0,0 +1,54 @@
+//! The label repository on Postgres.
+
+use super::sql::LabelQueries;
+use super::*;
+use sqlx::{PgPool, Postgres};
+
+#[derive(Debug, Clone)]
+pub struct LabelRepositoryForDb {
+    pool: PgPool,
+}
+
+impl LabelRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl LabelRepository for LabelRepositoryForDb {
+    async fn create(&self, owner_id: i32, name: String) -> anyhow::Result<Label> {
+        let mut conn = self.pool.acquire().await?;
+        Postgres::create_label(&mut conn, owner_id, name).await
+    }
+
+    async fn all(&self, owner_id: i32) -> anyhow::Result<Vec<Label>> {
+        let mut conn = self.pool.acquire().await?;
+        Postgres::all_labels(&mut conn, owner_id).await
+    }
+
+    async fn update(&self, owner_id: i32, id: i32, name: String) -> anyhow::Result<Label> {
+        let mut conn = self.pool.acquire().await?;
+        Postgres::update_label(&mut conn, owner_id, id, name).await
+    }
+
+    async fn usage(&self, owner_id: i32, id: i32) -> anyhow::Result<Vec<i32>> {
+        let mut conn = self.pool.acquire().await?;
+        Postgres::label_usage(&mut conn, owner_id, id).await
+    }
+
+    async fn delete(&self, owner_id: i32, id: i32, mode: LabelDeleteMode) -> anyhow::Result<()> {
+        unit_of_work(&self.pool, |conn| {
+            Box::pin(Postgres::delete_label(conn, owner_id, id, mode))
+        })
+        .await
+    }
+
+    async fn merge(&self, owner_id: i32, id: i32, sources: Vec<i32>) -> anyhow::Result<usize> {
+        let sources = merge_sources(id, sources);
+        unit_of_work(&self.pool, |conn| {
+            Box::pin(Postgres::merge_labels(conn, owner_id, id, sources))
+        })
+        .await
+    }
+}
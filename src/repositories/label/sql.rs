@@ -0,0 +1,232 @@
+//! The label queries of the Postgres and SQLite repositories, written once over [`Dialect`].
+
+use super::*;
+use sqlx::{
+    database::HasArguments, ColumnIndex, Decode, Encode, Executor, FromRow, IntoArguments,
+    Postgres, Sqlite, Type,
+};
+
+#[async_trait]
+pub(super) trait LabelQueries: Dialect
+where
+    for<'c> &'c mut Self::Connection: Executor<'c, Database = Self>,
+    for<'q> <Self as HasArguments<'q>>::Arguments: IntoArguments<'q, Self>,
+    for<'q> i32: Encode<'q, Self> + Decode<'q, Self> + Type<Self>,
+    for<'q> i64: Decode<'q, Self> + Type<Self>,
+    for<'q> String: Encode<'q, Self> + Type<Self>,
+    for<'r> Label: FromRow<'r, Self::Row>,
+    usize: ColumnIndex<Self::Row>,
+{
+    /// The todos label `id` is attached to, ordered by id.
+    /// Locks the label, so no todo can pick it up until the transaction ends.
+    async fn label_usage(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        id: i32,
+    ) -> anyhow::Result<Vec<i32>> {
+        sqlx::query_scalar::<Self, i32>(&format!(
+            "SELECT id FROM labels WHERE id = $1 AND owner_id = $2{}",
+            Self::FOR_UPDATE
+        ))
+        .bind(id)
+        .bind(owner_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        let todos = sqlx::query_scalar::<Self, i32>(
+            r#"
+                SELECT DISTINCT todo_id FROM todo_labels WHERE label_id = $1 ORDER BY todo_id
+            "#,
+        )
+        .bind(id)
+        .fetch_all(conn)
+        .await?;
+
+        Ok(todos)
+    }
+
+    /// `Duplicate` naming the label of `owner_id` that `name` collides with.
+    async fn duplicate_of(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        name: String,
+    ) -> anyhow::Result<RepositoryError> {
+        let id = sqlx::query_scalar::<Self, i32>(
+            r#"
+                SELECT id FROM labels WHERE lower(name) = lower($1) AND owner_id = $2
+            "#,
+        )
+        .bind(name)
+        .bind(owner_id)
+        .fetch_one(conn)
+        .await?;
+
+        Ok(RepositoryError::Duplicate(id))
+    }
+
+    async fn create_label(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        name: String,
+    ) -> anyhow::Result<Label> {
+        // the unique index on the lowercased name settles concurrent creates
+        let label = sqlx::query_as::<Self, Label>(
+            r#"
+                INSERT INTO labels ( name, owner_id )
+                VALUES ( $1, $2 )
+                ON CONFLICT DO NOTHING
+                RETURNING id, name
+            "#,
+        )
+        .bind(name.clone())
+        .bind(owner_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        match label {
+            Some(label) => Ok(label),
+            None => Err(Self::duplicate_of(conn, owner_id, name).await?.into()),
+        }
+    }
+
+    async fn all_labels(conn: &mut Self::Connection, owner_id: i32) -> anyhow::Result<Vec<Label>> {
+        let labels = sqlx::query_as::<Self, Label>(
+            r#"
+                SELECT id, name FROM labels
+                WHERE owner_id = $1
+                ORDER BY labels.id ASC;
+            "#,
+        )
+        .bind(owner_id)
+        .fetch_all(conn)
+        .await?;
+
+        Ok(labels)
+    }
+
+    async fn update_label(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        id: i32,
+        name: String,
+    ) -> anyhow::Result<Label> {
+        let label = sqlx::query_as::<Self, Label>(
+            r#"
+                UPDATE labels SET name = $1
+                WHERE id = $2 AND owner_id = $3
+                RETURNING id, name
+            "#,
+        )
+        .bind(name.clone())
+        .bind(id)
+        .bind(owner_id)
+        .fetch_optional(&mut *conn)
+        .await;
+
+        match label {
+            Err(e) if is_unique_violation(&e) => {
+                Err(Self::duplicate_of(conn, owner_id, name).await?.into())
+            }
+            label => Ok(label?.ok_or(RepositoryError::NotFound(id))?),
+        }
+    }
+
+    async fn delete_label(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        id: i32,
+        mode: LabelDeleteMode,
+    ) -> anyhow::Result<()> {
+        let todos = Self::label_usage(&mut *conn, owner_id, id).await?;
+        if !todos.is_empty() && mode == LabelDeleteMode::Restrict {
+            return Err(RepositoryError::InUse { id, todos }.into());
+        }
+        sqlx::query::<Self>(
+            r#"
+                DELETE FROM todo_labels WHERE label_id = $1
+            "#,
+        )
+        .bind(id)
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query::<Self>(
+            r#"
+                DELETE FROM labels WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Merges `sources`, already passed through `merge_sources`, into label `id`.
+    async fn merge_labels(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        id: i32,
+        sources: Vec<i32>,
+    ) -> anyhow::Result<usize> {
+        let ids: Vec<i32> = std::iter::once(id).chain(sources.iter().copied()).collect();
+        let owned = sqlx::query_scalar::<Self, i32>(&format!(
+            "SELECT id FROM labels WHERE {} AND owner_id = $2{}",
+            Self::in_ids("id", "$1"),
+            Self::FOR_UPDATE
+        ))
+        .bind(Self::ids(&ids))
+        .bind(owner_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        if let Some(missing) = ids.iter().find(|id| !owned.contains(id)) {
+            return Err(RepositoryError::NotFound(*missing).into());
+        }
+
+        let affected = sqlx::query_scalar::<Self, i64>(&format!(
+            "SELECT COUNT ( DISTINCT todo_id ) FROM todo_labels WHERE {}",
+            Self::in_ids("label_id", "$1")
+        ))
+        .bind(Self::ids(&sources))
+        .fetch_one(&mut *conn)
+        .await?;
+        // one row per todo is left, the first of the merged labels
+        sqlx::query::<Self>(&format!(
+            r#"
+                DELETE FROM todo_labels
+                WHERE {}
+                AND EXISTS (
+                    SELECT 1 FROM todo_labels earlier
+                    WHERE earlier.todo_id = todo_labels.todo_id
+                    AND {}
+                    AND earlier.id < todo_labels.id
+                )
+            "#,
+            Self::in_ids("label_id", "$1"),
+            Self::in_ids("earlier.label_id", "$1")
+        ))
+        .bind(Self::ids(&ids))
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query::<Self>(&format!(
+            "UPDATE todo_labels SET label_id = $1 WHERE {}",
+            Self::in_ids("label_id", "$2")
+        ))
+        .bind(id)
+        .bind(Self::ids(&sources))
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query::<Self>(&format!(
+            "DELETE FROM labels WHERE {}",
+            Self::in_ids("id", "$1")
+        ))
+        .bind(Self::ids(&sources))
+        .execute(conn)
+        .await?;
+
+        Ok(affected as usize)
+    }
+}
+
+impl LabelQueries for Postgres {}
+
+impl LabelQueries for Sqlite {}
@@ -0,0 +1,168 @@
+use crate::metrics::{measure, DB_POOL_ACQUIRE_DURATION};
+use crate::repositories::label::{Label, LabelDeleteMode, LabelRepository};
+use crate::repositories::todo::{
+    CreateTodo, DeleteMode, Placement, TodoEntity, TodoListQuery, TodoPage, TodoRepository,
+    TodoStats, UpdateTodo,
+};
+use axum::async_trait;
+use sqlx::{Database, Pool};
+use std::{fmt, future::Future, pin::Pin, sync::Arc};
+
+/// Wraps a repository, recording the latency of every method in the metrics.
+#[derive(Debug, Clone)]
+pub struct Measured<R> {
+    inner: R,
+    name: &'static str,
+    acquire: Option<Acquire>,
+}
+
+type Acquiring = Pin<Box<dyn Future<Output = sqlx::Result<()>> + Send>>;
+
+/// Takes a connection from the pool of the wrapped repository and hands it straight back.
+#[derive(Clone)]
+struct Acquire(Arc<dyn Fn() -> Acquiring + Send + Sync>);
+
+impl fmt::Debug for Acquire {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Acquire")
+    }
+}
+
+impl<R> Measured<R> {
+    /// `name` becomes the `repository` label of the recorded latencies.
+    pub fn new(name: &'static str, inner: R) -> Self {
+        Self {
+            inner,
+            name,
+            acquire: None,
+        }
+    }
+
+    /// Also records how long every call waits for a connection of `pool`, the one `inner`
+    /// queries. sqlx does not report its waits, so a connection is taken and handed back
+    /// before the call, which costs the call an extra checkout.
+    pub fn with_pool<DB: Database>(self, pool: Pool<DB>) -> Self {
+        let acquire = Acquire(Arc::new(move || {
+            let pool = pool.clone();
+            Box::pin(async move { pool.acquire().await.map(drop) })
+        }));
+        Self {
+            acquire: Some(acquire),
+            ..self
+        }
+    }
+
+    async fn measure<T>(
+        &self,
+        method: &str,
+        call: impl Future<Output = anyhow::Result<T>>,
+    ) -> anyhow::Result<T> {
+        if let Some(Acquire(acquire)) = &self.acquire {
+            let timer = DB_POOL_ACQUIRE_DURATION
+                .with_label_values(&[self.name])
+                .start_timer();
+            acquire().await?;
+            timer.observe_duration();
+        }
+        measure(self.name, method, call).await
+    }
+}
+
+#[async_trait]
+impl<R: TodoRepository> TodoRepository for Measured<R> {
+    async fn create(&self, owner_id: i32, payload: CreateTodo) -> anyhow::Result<TodoEntity> {
+        self.measure("create", self.inner.create(owner_id, payload))
+            .await
+    }
+
+    async fn find(&self, owner_id: i32, id: i32) -> anyhow::Result<TodoEntity> {
+        self.measure("find", self.inner.find(owner_id, id)).await
+    }
+
+    async fn list(&self, owner_id: i32, query: TodoListQuery) -> anyhow::Result<TodoPage> {
+        self.measure("list", self.inner.list(owner_id, query)).await
+    }
+
+    async fn update(
+        &self,
+        owner_id: i32,
+        id: i32,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<TodoEntity> {
+        self.measure("update", self.inner.update(owner_id, id, payload))
+            .await
+    }
+
+    async fn delete(&self, owner_id: i32, id: i32, mode: DeleteMode) -> anyhow::Result<()> {
+        self.measure("delete", self.inner.delete(owner_id, id, mode))
+            .await
+    }
+
+    async fn children(&self, owner_id: i32, id: i32) -> anyhow::Result<Vec<TodoEntity>> {
+        self.measure("children", self.inner.children(owner_id, id))
+            .await
+    }
+
+    async fn descendants(&self, owner_id: i32, ids: Vec<i32>) -> anyhow::Result<Vec<TodoEntity>> {
+        self.measure("descendants", self.inner.descendants(owner_id, ids))
+            .await
+    }
+
+    async fn move_to_project(
+        &self,
+        owner_id: i32,
+        id: i32,
+        project_id: i32,
+    ) -> anyhow::Result<TodoEntity> {
+        self.measure(
+            "move_to_project",
+            self.inner.move_to_project(owner_id, id, project_id),
+        )
+        .await
+    }
+
+    async fn reorder(
+        &self,
+        owner_id: i32,
+        id: i32,
+        placement: Placement,
+    ) -> anyhow::Result<TodoEntity> {
+        self.measure("reorder", self.inner.reorder(owner_id, id, placement))
+            .await
+    }
+
+    async fn stats(&self) -> anyhow::Result<TodoStats> {
+        self.measure("stats", self.inner.stats()).await
+    }
+}
+
+#[async_trait]
+impl<R: LabelRepository> LabelRepository for Measured<R> {
+    async fn create(&self, owner_id: i32, name: String) -> anyhow::Result<Label> {
+        self.measure("create", self.inner.create(owner_id, name))
+            .await
+    }
+
+    async fn all(&self, owner_id: i32) -> anyhow::Result<Vec<Label>> {
+        self.measure("all", self.inner.all(owner_id)).await
+    }
+
+    async fn update(&self, owner_id: i32, id: i32, name: String) -> anyhow::Result<Label> {
+        self.measure("update", self.inner.update(owner_id, id, name))
+            .await
+    }
+
+    async fn usage(&self, owner_id: i32, id: i32) -> anyhow::Result<Vec<i32>> {
+        self.measure("usage", self.inner.usage(owner_id, id)).await
+    }
+
+    async fn delete(&self, owner_id: i32, id: i32, mode: LabelDeleteMode) -> anyhow::Result<()> {
+        self.measure("delete", self.inner.delete(owner_id, id, mode))
+            .await
+    }
+
+    async fn merge(&self, owner_id: i32, id: i32, sources: Vec<i32>) -> anyhow::Result<usize> {
+        self.measure("merge", self.inner.merge(owner_id, id, sources))
+            .await
+    }
+}
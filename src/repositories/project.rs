@@ -0,0 +1,278 @@
+use crate::repositories::{unit_of_work, Dialect, RepositoryError};
+use anyhow::Ok;
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+/// Project created by the migration; top level todos land here unless told otherwise.
+/// Every user shares it, but it only ever shows them their own todos.
+pub const DEFAULT_PROJECT_ID: i32 = 1;
+
+/// Every method sees the projects of `owner_id` plus the default project.
+#[async_trait]
+pub trait ProjectRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn create(&self, owner_id: i32, name: String) -> anyhow::Result<Project>;
+    async fn find(&self, owner_id: i32, id: i32) -> anyhow::Result<Project>;
+    async fn all(&self, owner_id: i32) -> anyhow::Result<Vec<Project>>;
+    /// The default project can not be renamed and fails with `Protected`.
+    async fn update(&self, owner_id: i32, id: i32, name: String) -> anyhow::Result<Project>;
+    /// Refuses with `NotEmpty` while todos still belong to the project.
+    async fn delete(&self, owner_id: i32, id: i32) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct Project {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Validate)]
+pub struct CreateProject {
+    #[validate(length(min = 1, message = "can not be empty"))]
+    #[validate(length(max = 100, message = "Over text length"))]
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+pub struct UpdateProject {
+    #[validate(length(min = 1, message = "can not be empty"))]
+    #[validate(length(max = 100, message = "Over text length"))]
+    pub name: String,
+}
+
+mod pg;
+mod sql;
+mod sqlite;
+
+pub use pg::ProjectRepositoryForDb;
+pub use sqlite::ProjectRepositoryForSqlite;
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use crate::repositories::todo::test_utils::TodoStore;
+    use axum::async_trait;
+    use std::{
+        collections::HashMap,
+        sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    };
+
+    impl Project {
+        pub fn new(id: i32, name: String) -> Self {
+            Self { id, name }
+        }
+
+        pub fn inbox() -> Self {
+            Self::new(DEFAULT_PROJECT_ID, "Inbox".to_string())
+        }
+    }
+
+    type ProjectDatas = HashMap<i32, Project>;
+
+    /// Projects of every owner, keyed by owner id, next to the default project.
+    #[derive(Debug, Clone)]
+    pub struct ProjectRepositoryForMemory {
+        store: Arc<RwLock<HashMap<i32, ProjectDatas>>>,
+        /// The todos filed under the projects, checked before deleting one.
+        todos: TodoStore,
+    }
+
+    impl ProjectRepositoryForMemory {
+        pub fn new() -> Self {
+            ProjectRepositoryForMemory {
+                store: Arc::default(),
+                todos: Arc::default(),
+            }
+        }
+
+        /// A repository seeing the todos of `TodoRepositoryForMemory::todos`,
+        /// so that deleting a project still holding some fails with `NotEmpty`.
+        pub fn with_todos(todos: TodoStore) -> Self {
+            ProjectRepositoryForMemory {
+                store: Arc::default(),
+                todos,
+            }
+        }
+
+        fn write_store_ref(&self) -> RwLockWriteGuard<'_, HashMap<i32, ProjectDatas>> {
+            self.store.write().unwrap()
+        }
+
+        fn read_store_ref(&self) -> RwLockReadGuard<'_, HashMap<i32, ProjectDatas>> {
+            self.store.read().unwrap()
+        }
+    }
+
+    #[async_trait]
+    impl ProjectRepository for ProjectRepositoryForMemory {
+        async fn create(&self, owner_id: i32, name: String) -> anyhow::Result<Project> {
+            let mut stores = self.write_store_ref();
+            let id = stores
+                .values()
+                .flat_map(|store| store.keys())
+                .copied()
+                .chain([DEFAULT_PROJECT_ID])
+                .max()
+                .unwrap()
+                + 1;
+            let store = stores.entry(owner_id).or_default();
+            if let Some(project) = store
+                .values()
+                .cloned()
+                .chain([Project::inbox()])
+                .find(|project| project.name == name)
+            {
+                return Err(RepositoryError::Duplicate(project.id).into());
+            }
+            let project = Project::new(id, name);
+            store.insert(id, project.clone());
+            Ok(project)
+        }
+
+        async fn find(&self, owner_id: i32, id: i32) -> anyhow::Result<Project> {
+            if id == DEFAULT_PROJECT_ID {
+                return Ok(Project::inbox());
+            }
+            let stores = self.read_store_ref();
+            let project = stores
+                .get(&owner_id)
+                .and_then(|store| store.get(&id))
+                .ok_or(RepositoryError::NotFound(id))?;
+            Ok(project.clone())
+        }
+
+        async fn all(&self, owner_id: i32) -> anyhow::Result<Vec<Project>> {
+            let stores = self.read_store_ref();
+            let mut projects = vec![Project::inbox()];
+            if let Some(store) = stores.get(&owner_id) {
+                projects.extend(store.values().cloned());
+            }
+            projects.sort_by_key(|project| project.id);
+            Ok(projects)
+        }
+
+        async fn update(&self, owner_id: i32, id: i32, name: String) -> anyhow::Result<Project> {
+            if id == DEFAULT_PROJECT_ID {
+                return Err(RepositoryError::Protected(id).into());
+            }
+            let mut stores = self.write_store_ref();
+            let store = stores.entry(owner_id).or_default();
+            if let Some(project) = store
+                .values()
+                .cloned()
+                .chain([Project::inbox()])
+                .find(|project| project.id != id && project.name == name)
+            {
+                return Err(RepositoryError::Duplicate(project.id).into());
+            }
+            let project = store.get_mut(&id).ok_or(RepositoryError::NotFound(id))?;
+            project.name = name;
+            Ok(project.clone())
+        }
+
+        async fn delete(&self, owner_id: i32, id: i32) -> anyhow::Result<()> {
+            if id == DEFAULT_PROJECT_ID {
+                return Err(RepositoryError::Protected(id).into());
+            }
+            let mut stores = self.write_store_ref();
+            let store = stores
+                .get_mut(&owner_id)
+                .filter(|store| store.contains_key(&id))
+                .ok_or(RepositoryError::NotFound(id))?;
+            let mut todos: Vec<i32> = self
+                .todos
+                .read()
+                .unwrap()
+                .get(&owner_id)
+                .into_iter()
+                .flat_map(|todos| todos.values())
+                .filter(|todo| todo.project_id == id)
+                .map(|todo| todo.id)
+                .collect();
+            if !todos.is_empty() {
+                todos.sort();
+                return Err(RepositoryError::NotEmpty { id, todos }.into());
+            }
+            store.remove(&id);
+            Ok(())
+        }
+    }
+
+    mod test {
+        use super::*;
+        use crate::repositories::todo::{
+            test_utils::TodoRepositoryForMemory, CreateTodo, TodoRepository,
+        };
+
+        #[tokio::test]
+        async fn project_crud_scenario() {
+            let owner_id = 1;
+            let todo_repository = TodoRepositoryForMemory::new(vec![]);
+            let repository = ProjectRepositoryForMemory::with_todos(todo_repository.todos());
+
+            // create
+            let project = repository
+                .create(owner_id, "project name".to_string())
+                .await
+                .expect("faild create project");
+            assert_eq!(Project::new(2, "project name".to_string()), project);
+            let res = repository.create(owner_id, "Inbox".to_string()).await;
+            assert!(res.is_err());
+
+            // find
+            let found = repository
+                .find(owner_id, project.id)
+                .await
+                .expect("faild find project");
+            assert_eq!(project, found);
+            let res = repository.find(2, project.id).await;
+            assert!(res.is_err());
+
+            // all
+            let projects = repository.all(owner_id).await.expect("faild all project");
+            assert_eq!(vec![Project::inbox(), project.clone()], projects);
+            let projects = repository.all(2).await.expect("faild all project");
+            assert_eq!(vec![Project::inbox()], projects);
+
+            // update
+            let project = repository
+                .update(owner_id, project.id, "renamed project".to_string())
+                .await
+                .expect("faild update project");
+            assert_eq!("renamed project", project.name);
+            let res = repository
+                .update(owner_id, DEFAULT_PROJECT_ID, "renamed inbox".to_string())
+                .await;
+            assert!(res.is_err());
+
+            // delete
+            let res = repository.delete(owner_id, DEFAULT_PROJECT_ID).await;
+            assert!(res.is_err());
+            let res = repository.delete(2, project.id).await;
+            assert!(res.is_err());
+            let todo = todo_repository
+                .create(
+                    owner_id,
+                    CreateTodo::new("todo".to_string(), vec![]).in_project(project.id),
+                )
+                .await
+                .expect("faild create todo");
+            let res = repository
+                .delete(owner_id, project.id)
+                .await
+                .expect_err("[delete] non empty project returned Ok");
+            assert!(matches!(
+                res.downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::NotEmpty { todos, .. }) if *todos == vec![todo.id]
+            ));
+            todo_repository
+                .delete(owner_id, todo.id, Default::default())
+                .await
+                .expect("faild delete todo");
+            let res = repository.delete(owner_id, project.id).await;
+            assert!(res.is_ok());
+            let res = repository.find(owner_id, project.id).await;
+            assert!(res.is_err());
+        }
+    }
+}
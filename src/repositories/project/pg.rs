@@ -0,0 +1,162 @@
+//! The project repository on Postgres.
+
+use super::sql::ProjectQueries;
+use super::*;
+use sqlx::{PgPool, Postgres};
+
+#[derive(Debug, Clone)]
+pub struct ProjectRepositoryForDb {
+    pool: PgPool,
+}
+
+impl ProjectRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ProjectRepository for ProjectRepositoryForDb {
+    async fn create(&self, owner_id: i32, name: String) -> anyhow::Result<Project> {
+        let mut conn = self.pool.acquire().await?;
+        Postgres::create_project(&mut conn, owner_id, name).await
+    }
+
+    async fn find(&self, owner_id: i32, id: i32) -> anyhow::Result<Project> {
+        let mut conn = self.pool.acquire().await?;
+        Postgres::find_project(&mut conn, owner_id, id).await
+    }
+
+    async fn all(&self, owner_id: i32) -> anyhow::Result<Vec<Project>> {
+        let mut conn = self.pool.acquire().await?;
+        Postgres::all_projects(&mut conn, owner_id).await
+    }
+
+    async fn update(&self, owner_id: i32, id: i32, name: String) -> anyhow::Result<Project> {
+        let mut conn = self.pool.acquire().await?;
+        Postgres::update_project(&mut conn, owner_id, id, name).await
+    }
+
+    async fn delete(&self, owner_id: i32, id: i32) -> anyhow::Result<()> {
+        unit_of_work(&self.pool, |conn| {
+            Box::pin(Postgres::delete_project(conn, owner_id, id))
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "database-test")]
+mod test {
+    use super::*;
+    use crate::repositories::todo::{CreateTodo, TodoRepository, TodoRepositoryForDb};
+    use crate::repositories::user::test_utils::fixture_user;
+    use dotenv::dotenv;
+    use sqlx::PgPool;
+    use std::env;
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+
+        let owner = fixture_user(&pool, "[project crud_scenario] owner").await;
+        let other_owner = fixture_user(&pool, "[project crud_scenario] other owner").await;
+        let repository = ProjectRepositoryForDb::new(pool.clone());
+        let project_name = "test_project";
+
+        // create
+        let project = repository
+            .create(owner.id, project_name.to_string())
+            .await
+            .expect("[create] returned Err");
+        assert_eq!(project.name, project_name);
+
+        // find
+        let found = repository
+            .find(owner.id, project.id)
+            .await
+            .expect("[find] returned Err");
+        assert_eq!(project, found);
+        let res = repository.find(other_owner.id, project.id).await;
+        assert!(res.is_err());
+
+        // all
+        let projects = repository.all(owner.id).await.expect("[all] returned Err");
+        assert_eq!(DEFAULT_PROJECT_ID, projects.first().unwrap().id);
+        assert!(projects.contains(&project));
+        let projects = repository
+            .all(other_owner.id)
+            .await
+            .expect("[all] returned Err");
+        assert!(!projects.contains(&project));
+
+        // update
+        let renamed_name = "test_project renamed";
+        let project = repository
+            .update(owner.id, project.id, renamed_name.to_string())
+            .await
+            .expect("[update] returned Err");
+        assert_eq!(project.name, renamed_name);
+        let res = repository
+            .create(owner.id, renamed_name.to_string())
+            .await
+            .expect_err("[create] duplicate name returned Ok");
+        assert!(matches!(
+            res.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Duplicate(id)) if *id == project.id
+        ));
+        let res = repository
+            .update(owner.id, DEFAULT_PROJECT_ID, "renamed inbox".to_string())
+            .await
+            .expect_err("[update] default project returned Ok");
+        assert!(matches!(
+            res.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Protected(DEFAULT_PROJECT_ID))
+        ));
+
+        // delete refuses while todos belong to the project
+        let todo_repository = TodoRepositoryForDb::new(pool);
+        let todo = todo_repository
+            .create(
+                owner.id,
+                CreateTodo::new("[project crud_scenario] todo".to_string(), vec![])
+                    .in_project(project.id),
+            )
+            .await
+            .expect("[create] returned Err");
+        let res = repository
+            .delete(owner.id, project.id)
+            .await
+            .expect_err("[delete] non empty project returned Ok");
+        assert!(matches!(
+            res.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotEmpty { todos, .. }) if *todos == vec![todo.id]
+        ));
+        let res = repository
+            .delete(owner.id, DEFAULT_PROJECT_ID)
+            .await
+            .expect_err("[delete] default project returned Ok");
+        assert!(matches!(
+            res.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Protected(DEFAULT_PROJECT_ID))
+        ));
+
+        // delete
+        todo_repository
+            .delete(owner.id, todo.id, Default::default())
+            .await
+            .expect("[delete] returned Err");
+        let res = repository.delete(other_owner.id, project.id).await;
+        assert!(res.is_err());
+        repository
+            .delete(owner.id, project.id)
+            .await
+            .expect("[delete] returned Err");
+        let res = repository.find(owner.id, project.id).await;
+        assert!(res.is_err());
+    }
+}
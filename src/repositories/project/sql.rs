@@ -0,0 +1,186 @@
+//! The project queries of the Postgres and SQLite repositories, written once over [`Dialect`].
+
+use super::*;
+use sqlx::{
+    database::HasArguments, ColumnIndex, Decode, Encode, Executor, FromRow, IntoArguments,
+    Postgres, Sqlite, Type,
+};
+
+#[async_trait]
+pub(super) trait ProjectQueries: Dialect
+where
+    for<'c> &'c mut Self::Connection: Executor<'c, Database = Self>,
+    for<'q> <Self as HasArguments<'q>>::Arguments: IntoArguments<'q, Self>,
+    for<'q> i32: Encode<'q, Self> + Decode<'q, Self> + Type<Self>,
+    for<'q> String: Encode<'q, Self> + Type<Self>,
+    for<'r> Project: FromRow<'r, Self::Row>,
+    usize: ColumnIndex<Self::Row>,
+{
+    async fn create_project(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        name: String,
+    ) -> anyhow::Result<Project> {
+        let optional_project = sqlx::query_as::<Self, Project>(
+            r#"
+                SELECT id, name FROM projects
+                WHERE name = $1 AND ( owner_id = $2 OR id = $3 )
+            "#,
+        )
+        .bind(name.clone())
+        .bind(owner_id)
+        .bind(DEFAULT_PROJECT_ID)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        if let Some(project) = optional_project {
+            return Err(RepositoryError::Duplicate(project.id).into());
+        }
+
+        let project = sqlx::query_as::<Self, Project>(
+            r#"
+                INSERT INTO projects ( name, owner_id )
+                VALUES ( $1, $2 )
+                RETURNING id, name
+            "#,
+        )
+        .bind(name)
+        .bind(owner_id)
+        .fetch_one(conn)
+        .await?;
+
+        Ok(project)
+    }
+
+    async fn find_project(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        id: i32,
+    ) -> anyhow::Result<Project> {
+        let project = sqlx::query_as::<Self, Project>(
+            r#"
+                SELECT id, name FROM projects
+                WHERE id = $1 AND ( owner_id = $2 OR id = $3 )
+            "#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .bind(DEFAULT_PROJECT_ID)
+        .fetch_optional(conn)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(project)
+    }
+
+    async fn all_projects(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+    ) -> anyhow::Result<Vec<Project>> {
+        let projects = sqlx::query_as::<Self, Project>(
+            r#"
+                SELECT id, name FROM projects
+                WHERE owner_id = $1 OR id = $2
+                ORDER BY projects.id ASC;
+            "#,
+        )
+        .bind(owner_id)
+        .bind(DEFAULT_PROJECT_ID)
+        .fetch_all(conn)
+        .await?;
+
+        Ok(projects)
+    }
+
+    async fn update_project(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        id: i32,
+        name: String,
+    ) -> anyhow::Result<Project> {
+        if id == DEFAULT_PROJECT_ID {
+            return Err(RepositoryError::Protected(id).into());
+        }
+        let optional_project = sqlx::query_as::<Self, Project>(
+            r#"
+                SELECT id, name FROM projects
+                WHERE name = $1 AND id <> $2 AND ( owner_id = $3 OR id = $4 )
+            "#,
+        )
+        .bind(name.clone())
+        .bind(id)
+        .bind(owner_id)
+        .bind(DEFAULT_PROJECT_ID)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        if let Some(project) = optional_project {
+            return Err(RepositoryError::Duplicate(project.id).into());
+        }
+
+        let project = sqlx::query_as::<Self, Project>(
+            r#"
+                UPDATE projects SET name = $1
+                WHERE id = $2 AND owner_id = $3
+                RETURNING id, name
+            "#,
+        )
+        .bind(name)
+        .bind(id)
+        .bind(owner_id)
+        .fetch_optional(conn)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(project)
+    }
+
+    async fn delete_project(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        id: i32,
+    ) -> anyhow::Result<()> {
+        if id == DEFAULT_PROJECT_ID {
+            return Err(RepositoryError::Protected(id).into());
+        }
+        // lock the project so that no todo moves in between the check and the delete;
+        // SQLite has no row locks, there a todo moving in after the check fails the
+        // foreign key of todos.project_id instead
+        sqlx::query_scalar::<Self, i32>(&format!(
+            "SELECT id FROM projects WHERE id = $1 AND owner_id = $2{}",
+            Self::FOR_UPDATE
+        ))
+        .bind(id)
+        .bind(owner_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        let todos = sqlx::query_scalar::<Self, i32>(
+            r#"
+                SELECT id FROM todos WHERE project_id = $1 ORDER BY id
+            "#,
+        )
+        .bind(id)
+        .fetch_all(&mut *conn)
+        .await?;
+        if !todos.is_empty() {
+            return Err(RepositoryError::NotEmpty { id, todos }.into());
+        }
+
+        sqlx::query::<Self>(
+            r#"
+                DELETE FROM projects WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl ProjectQueries for Postgres {}
+
+impl ProjectQueries for Sqlite {}
@@ -0,0 +1,138 @@
+//! The project repository on SQLite.
+
+use super::sql::ProjectQueries;
+use super::*;
+use sqlx::{Sqlite, SqlitePool};
+
+#[derive(Debug, Clone)]
+pub struct ProjectRepositoryForSqlite {
+    pool: SqlitePool,
+}
+
+impl ProjectRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ProjectRepository for ProjectRepositoryForSqlite {
+    async fn create(&self, owner_id: i32, name: String) -> anyhow::Result<Project> {
+        let mut conn = self.pool.acquire().await?;
+        Sqlite::create_project(&mut conn, owner_id, name).await
+    }
+
+    async fn find(&self, owner_id: i32, id: i32) -> anyhow::Result<Project> {
+        let mut conn = self.pool.acquire().await?;
+        Sqlite::find_project(&mut conn, owner_id, id).await
+    }
+
+    async fn all(&self, owner_id: i32) -> anyhow::Result<Vec<Project>> {
+        let mut conn = self.pool.acquire().await?;
+        Sqlite::all_projects(&mut conn, owner_id).await
+    }
+
+    async fn update(&self, owner_id: i32, id: i32, name: String) -> anyhow::Result<Project> {
+        let mut conn = self.pool.acquire().await?;
+        Sqlite::update_project(&mut conn, owner_id, id, name).await
+    }
+
+    async fn delete(&self, owner_id: i32, id: i32) -> anyhow::Result<()> {
+        unit_of_work(&self.pool, |conn| {
+            Box::pin(Sqlite::delete_project(conn, owner_id, id))
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::migrations::test_utils::sqlite_pool;
+    use crate::repositories::todo::{CreateTodo, TodoRepository, TodoRepositoryForSqlite};
+    use crate::repositories::user::test_utils::sqlite_fixture_user;
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let pool = sqlite_pool().await;
+        let owner = sqlite_fixture_user(&pool, "owner").await;
+        let other_owner = sqlite_fixture_user(&pool, "other owner").await;
+        let repository = ProjectRepositoryForSqlite::new(pool.clone());
+
+        // create
+        let project = repository
+            .create(owner.id, "project".to_string())
+            .await
+            .expect("[create] returned Err");
+        assert_eq!(Project::new(2, "project".to_string()), project);
+        let res = repository
+            .create(owner.id, "Inbox".to_string())
+            .await
+            .expect_err("[create] duplicate name returned Ok");
+        assert!(matches!(
+            res.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Duplicate(DEFAULT_PROJECT_ID))
+        ));
+
+        // find and all
+        let found = repository
+            .find(owner.id, project.id)
+            .await
+            .expect("[find] returned Err");
+        assert_eq!(project, found);
+        let res = repository.find(other_owner.id, project.id).await;
+        assert!(res.is_err());
+        let projects = repository.all(owner.id).await.expect("[all] returned Err");
+        assert_eq!(vec![Project::inbox(), project.clone()], projects);
+        let projects = repository
+            .all(other_owner.id)
+            .await
+            .expect("[all] returned Err");
+        assert_eq!(vec![Project::inbox()], projects);
+
+        // update
+        let project = repository
+            .update(owner.id, project.id, "renamed".to_string())
+            .await
+            .expect("[update] returned Err");
+        assert_eq!("renamed", project.name);
+        let res = repository
+            .update(owner.id, DEFAULT_PROJECT_ID, "renamed inbox".to_string())
+            .await
+            .expect_err("[update] default project returned Ok");
+        assert!(matches!(
+            res.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Protected(DEFAULT_PROJECT_ID))
+        ));
+
+        // delete refuses while todos belong to the project
+        let todo_repository = TodoRepositoryForSqlite::new(pool);
+        let todo = todo_repository
+            .create(
+                owner.id,
+                CreateTodo::new("todo".to_string(), vec![]).in_project(project.id),
+            )
+            .await
+            .expect("[create] returned Err");
+        let res = repository
+            .delete(owner.id, project.id)
+            .await
+            .expect_err("[delete] non empty project returned Ok");
+        assert!(matches!(
+            res.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotEmpty { todos, .. }) if *todos == vec![todo.id]
+        ));
+        todo_repository
+            .delete(owner.id, todo.id, Default::default())
+            .await
+            .expect("[delete] returned Err");
+        let res = repository.delete(other_owner.id, project.id).await;
+        assert!(res.is_err());
+        repository
+            .delete(owner.id, project.id)
+            .await
+            .expect("[delete] returned Err");
+        let res = repository.find(owner.id, project.id).await;
+        assert!(res.is_err());
+    }
+}
@@ -1,19 +1,392 @@
-use crate::repositories::RepositoryError;
-use anyhow::Ok;
+use crate::repositories::{is_unique_violation, unit_of_work, Dialect, RepositoryError};
+use anyhow::{Context, Ok};
 use axum::async_trait;
-use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, FixedOffset, NaiveTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::FromRow;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+};
 use validator::Validate;
 
 use super::label::Label;
+use super::project::DEFAULT_PROJECT_ID;
 
+/// Every method only sees the todos of `owner_id`; the todos of anyone else are `NotFound`.
 #[async_trait]
 pub trait TodoRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
-    async fn create(&self, payload: CreateTodo) -> anyhow::Result<TodoEntity>;
-    async fn find(&self, id: i32) -> anyhow::Result<TodoEntity>;
-    async fn all(&self) -> anyhow::Result<Vec<TodoEntity>>;
-    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<TodoEntity>;
-    async fn delete(&self, id: i32) -> anyhow::Result<()>;
+    async fn create(&self, owner_id: i32, payload: CreateTodo) -> anyhow::Result<TodoEntity>;
+    async fn find(&self, owner_id: i32, id: i32) -> anyhow::Result<TodoEntity>;
+    async fn list(&self, owner_id: i32, query: TodoListQuery) -> anyhow::Result<TodoPage>;
+    async fn update(
+        &self,
+        owner_id: i32,
+        id: i32,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<TodoEntity>;
+    async fn delete(&self, owner_id: i32, id: i32, mode: DeleteMode) -> anyhow::Result<()>;
+    /// Direct subtasks of `id`, ordered by id.
+    async fn children(&self, owner_id: i32, id: i32) -> anyhow::Result<Vec<TodoEntity>>;
+    /// Every todo below `ids` at any depth, ordered by id.
+    async fn descendants(&self, owner_id: i32, ids: Vec<i32>) -> anyhow::Result<Vec<TodoEntity>>;
+    /// Moves `id` and all of its subtasks into `project_id`.
+    /// A subtask is detached from its parent, which stays behind.
+    async fn move_to_project(
+        &self,
+        owner_id: i32,
+        id: i32,
+        project_id: i32,
+    ) -> anyhow::Result<TodoEntity>;
+    /// Moves `id` to `placement` within the manual order of its project.
+    /// A target that is not a todo of the same project, whoever owns it, is `InvalidPlacement`.
+    async fn reorder(
+        &self,
+        owner_id: i32,
+        id: i32,
+        placement: Placement,
+    ) -> anyhow::Result<TodoEntity>;
+    /// Counts over the todos of every owner.
+    async fn stats(&self) -> anyhow::Result<TodoStats>;
+}
+
+/// What deleting a todo that still has subtasks does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeleteMode {
+    /// Refuse with `RepositoryError::HasChildren`.
+    #[default]
+    Restrict,
+    /// Delete the whole subtree.
+    Cascade,
+}
+
+/// Half-open range `[from, to)` of due dates selected by the `due` filter.
+/// Only open todos are ever considered due.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DueWindow {
+    from: Option<DateTime<Utc>>,
+    to: DateTime<Utc>,
+}
+
+impl DueWindow {
+    pub fn overdue(now: DateTime<Utc>) -> Self {
+        Self {
+            from: None,
+            to: now,
+        }
+    }
+
+    /// The calendar day containing `now`, in the offset `now` carries.
+    pub fn today(now: DateTime<FixedOffset>) -> Self {
+        let start = now
+            .date_naive()
+            .and_time(NaiveTime::MIN)
+            .and_local_timezone(*now.offset())
+            .unwrap()
+            .with_timezone(&Utc);
+        Self {
+            from: Some(start),
+            to: start + Duration::days(1),
+        }
+    }
+
+    pub fn within(now: DateTime<Utc>, days: i64) -> Self {
+        Self {
+            from: Some(now),
+            to: now + Duration::days(days),
+        }
+    }
+
+    fn contains(&self, todo: &TodoEntity) -> bool {
+        !todo.completed
+            && todo.due_at.is_some_and(|due_at| {
+                self.from.is_none_or(|from| from <= due_at) && due_at < self.to
+            })
+    }
+}
+
+/// How pressing a todo is, stored as its rank from 0 for `None` up to 4 for `Urgent`.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[repr(i16)]
+pub enum Priority {
+    #[default]
+    None = 0,
+    Low = 1,
+    Medium = 2,
+    High = 3,
+    Urgent = 4,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    Id,
+    Text,
+    DueAt,
+    /// Open before completed, then the highest priority, then the earliest due date,
+    /// then the oldest todo.
+    Smart,
+    /// The manual order set through `TodoRepository::reorder`, which is kept per project.
+    Position,
+}
+
+impl SortField {
+    /// The order used when the query names none: smart sorting reads most pressing first
+    /// and the manual order top down, every other field newest first.
+    pub fn default_order(&self) -> SortOrder {
+        match self {
+            SortField::Smart | SortField::Position => SortOrder::Asc,
+            _ => SortOrder::Desc,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    fn sql(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+/// Value of the sort field for one todo, as carried by a cursor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SortKey {
+    Id,
+    Text(String),
+    DueAt(Option<DateTime<Utc>>),
+    Smart {
+        completed: bool,
+        priority: Priority,
+        due_at: Option<DateTime<Utc>>,
+    },
+    Position(i64),
+}
+
+impl SortKey {
+    fn of(field: SortField, todo: &TodoEntity) -> Self {
+        match field {
+            SortField::Id => SortKey::Id,
+            SortField::Text => SortKey::Text(todo.text.clone()),
+            SortField::DueAt => SortKey::DueAt(todo.due_at),
+            SortField::Smart => SortKey::Smart {
+                completed: todo.completed,
+                priority: todo.priority,
+                due_at: todo.due_at,
+            },
+            SortField::Position => SortKey::Position(todo.position),
+        }
+    }
+
+    fn field(&self) -> SortField {
+        match self {
+            SortKey::Id => SortField::Id,
+            SortKey::Text(_) => SortField::Text,
+            SortKey::DueAt(_) => SortField::DueAt,
+            SortKey::Smart { .. } => SortField::Smart,
+            SortKey::Position(_) => SortField::Position,
+        }
+    }
+}
+
+impl PartialOrd for SortKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Due dates in ascending order, undated after every dated one.
+fn cmp_due_at(a: &Option<DateTime<Utc>>, b: &Option<DateTime<Utc>>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.cmp(b),
+        (a, b) => a.is_none().cmp(&b.is_none()),
+    }
+}
+
+impl Ord for SortKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (SortKey::Text(a), SortKey::Text(b)) => a.as_bytes().cmp(b.as_bytes()),
+            (SortKey::DueAt(a), SortKey::DueAt(b)) => cmp_due_at(a, b),
+            (
+                SortKey::Smart {
+                    completed,
+                    priority,
+                    due_at,
+                },
+                SortKey::Smart {
+                    completed: other_completed,
+                    priority: other_priority,
+                    due_at: other_due_at,
+                },
+            ) => completed
+                .cmp(other_completed)
+                .then(other_priority.cmp(priority))
+                .then(cmp_due_at(due_at, other_due_at)),
+            (SortKey::Position(a), SortKey::Position(b)) => a.cmp(b),
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+/// Opaque keyset pagination position: the sort key and id of the last todo of a page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TodoCursor {
+    key: SortKey,
+    id: i32,
+}
+
+impl TodoCursor {
+    fn of(field: SortField, todo: &TodoEntity) -> Self {
+        Self {
+            key: SortKey::of(field, todo),
+            id: todo.id,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap())
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let bytes = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    pub fn field(&self) -> SortField {
+        self.key.field()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TodoFilter {
+    pub project_id: Option<i32>,
+    pub completed: Option<bool>,
+    /// Todos carrying any of these labels.
+    pub label_ids: Vec<i32>,
+    /// Case-insensitive substring of the todo text.
+    pub text: Option<String>,
+    pub due: Option<DueWindow>,
+    /// Only todos without a parent.
+    pub roots_only: bool,
+}
+
+impl TodoFilter {
+    pub(crate) fn matches(&self, todo: &TodoEntity) -> bool {
+        self.project_id
+            .is_none_or(|project_id| todo.project_id == project_id)
+            && self
+                .completed
+                .is_none_or(|completed| todo.completed == completed)
+            && (self.label_ids.is_empty()
+                || todo
+                    .labels
+                    .iter()
+                    .any(|label| self.label_ids.contains(&label.id)))
+            && self
+                .text
+                .as_ref()
+                .is_none_or(|text| todo.text.to_lowercase().contains(&text.to_lowercase()))
+            && self.due.is_none_or(|due| due.contains(todo))
+            && (!self.roots_only || todo.parent_id.is_none())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TodoListQuery {
+    pub filter: TodoFilter,
+    pub sort: SortField,
+    pub order: SortOrder,
+    pub cursor: Option<TodoCursor>,
+    pub limit: i64,
+}
+
+impl TodoListQuery {
+    pub const DEFAULT_LIMIT: i64 = 50;
+    pub const MAX_LIMIT: i64 = 200;
+
+    /// Orders `a` before `b` the way the page is sorted.
+    pub(crate) fn compare(&self, a: &TodoEntity, b: &TodoEntity) -> Ordering {
+        let ordering = SortKey::of(self.sort, a)
+            .cmp(&SortKey::of(self.sort, b))
+            .then(a.id.cmp(&b.id));
+        match self.order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    }
+
+    pub(crate) fn is_after_cursor(&self, todo: &TodoEntity) -> bool {
+        let Some(cursor) = &self.cursor else {
+            return true;
+        };
+        let ordering = SortKey::of(self.sort, todo)
+            .cmp(&cursor.key)
+            .then(todo.id.cmp(&cursor.id));
+        match self.order {
+            SortOrder::Asc => ordering == Ordering::Greater,
+            SortOrder::Desc => ordering == Ordering::Less,
+        }
+    }
+}
+
+impl Default for TodoListQuery {
+    fn default() -> Self {
+        Self {
+            filter: TodoFilter::default(),
+            sort: SortField::default(),
+            order: SortOrder::default(),
+            cursor: None,
+            limit: Self::DEFAULT_LIMIT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TodoStats {
+    pub open: i64,
+    pub completed: i64,
+    /// label id => todos carrying that label
+    pub by_label: BTreeMap<i32, i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TodoPage {
+    pub items: Vec<TodoEntity>,
+    pub next_cursor: Option<String>,
+}
+
+impl TodoPage {
+    /// Builds a page from up to `limit + 1` sorted todos; the extra one only signals that
+    /// another page follows.
+    pub(crate) fn new(mut items: Vec<TodoEntity>, query: &TodoListQuery) -> Self {
+        let limit = query.limit as usize;
+        let next_cursor = if items.len() > limit {
+            items.truncate(limit);
+            items
+                .last()
+                .map(|todo| TodoCursor::of(query.sort, todo).encode())
+        } else {
+            None
+        };
+        Self { items, next_cursor }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, FromRow)]
@@ -21,6 +394,12 @@ pub struct TodoWithLabelFromRow {
     id: i32,
     text: String,
     completed: bool,
+    priority: Priority,
+    due_at: Option<DateTime<Utc>>,
+    parent_id: Option<i32>,
+    auto_complete: bool,
+    project_id: i32,
+    position: i64,
     label_id: Option<i32>,
     label_name: Option<String>,
 }
@@ -30,362 +409,257 @@ pub struct TodoEntity {
     pub id: i32,
     pub text: String,
     pub completed: bool,
+    pub priority: Priority,
+    pub due_at: Option<DateTime<Utc>>,
+    pub parent_id: Option<i32>,
+    /// Completion follows the subtasks: done once all of them are, reopened with any of them.
+    pub auto_complete: bool,
+    pub project_id: i32,
+    /// Rank in the manual order of the project, only meaningful relative to its neighbours.
+    pub position: i64,
     pub labels: Vec<Label>,
 }
 
-fn fold_entities(rows: Vec<TodoWithLabelFromRow>) -> Vec<TodoEntity> {
-    let mut rows = rows.iter();
-    let mut accum: Vec<TodoEntity> = vec![];
-    'outer: while let Some(row) = rows.next() {
-        let mut todos = accum.iter_mut();
-        while let Some(todo) = todos.next() {
-            if todo.id == row.id {
-                todo.labels.push(Label {
-                    id: row.label_id.unwrap(),
-                    name: row.label_name.clone().unwrap(),
-                });
-                continue 'outer;
+/// A todo with its subtasks nested below it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TodoNode {
+    #[serde(flatten)]
+    pub todo: TodoEntity,
+    pub children: Vec<TodoNode>,
+}
+
+impl TodoNode {
+    /// Nests `descendants` below `roots`, keeping the order of both.
+    pub fn build(roots: Vec<TodoEntity>, descendants: Vec<TodoEntity>) -> Vec<TodoNode> {
+        let mut children: HashMap<i32, Vec<TodoEntity>> = HashMap::new();
+        for todo in descendants {
+            if let Some(parent_id) = todo.parent_id {
+                children.entry(parent_id).or_default().push(todo);
+            }
+        }
+        fn nest(todo: TodoEntity, children: &mut HashMap<i32, Vec<TodoEntity>>) -> TodoNode {
+            let nodes = children
+                .remove(&todo.id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|child| nest(child, children))
+                .collect();
+            TodoNode {
+                todo,
+                children: nodes,
             }
         }
+        roots
+            .into_iter()
+            .map(|todo| nest(todo, &mut children))
+            .collect()
+    }
+}
 
-        let labels = if row.label_id.is_some() {
-            vec![Label {
-                id: row.label_id.unwrap(),
-                name: row.label_name.clone().unwrap(),
-            }]
-        } else {
-            vec![]
-        };
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TodoTreePage {
+    pub items: Vec<TodoNode>,
+    pub next_cursor: Option<String>,
+}
 
-        accum.push(TodoEntity {
-            id: row.id,
-            text: row.text.clone(),
-            completed: row.completed,
-            labels,
+/// Groups joined label rows into their todos in one pass, keeping the order of the rows.
+fn fold_entities(rows: Vec<TodoWithLabelFromRow>) -> Vec<TodoEntity> {
+    let mut index: HashMap<i32, usize> = HashMap::new();
+    let mut accum: Vec<TodoEntity> = vec![];
+    for row in rows {
+        let i = *index.entry(row.id).or_insert_with(|| {
+            accum.push(TodoEntity {
+                id: row.id,
+                text: row.text,
+                completed: row.completed,
+                priority: row.priority,
+                due_at: row.due_at,
+                parent_id: row.parent_id,
+                auto_complete: row.auto_complete,
+                project_id: row.project_id,
+                position: row.position,
+                labels: vec![],
+            });
+            accum.len() - 1
         });
+        if let (Some(id), Some(name)) = (row.label_id, row.label_name) {
+            accum[i].labels.push(Label { id, name });
+        }
     }
     accum
-    // .fold(vec![], |mut accum, current| {
-    //     accum.push(TodoEntity {
-    //         id: current.id,
-    //         text: current.text.clone(),
-    //         completed: current.completed,
-    //         labels: vec![],
-    //     });
-    //     accum
-    // })
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq, Validate)]
 pub struct CreateTodo {
     #[validate(length(min = 1, message = "Can not be empty"))]
     #[validate(length(max = 100, message = "Over text length"))]
-    text: String,
-    labels: Vec<i32>,
+    pub(crate) text: String,
+    pub(crate) labels: Vec<i32>,
+    #[serde(default)]
+    pub(crate) priority: Priority,
+    #[serde(default)]
+    pub(crate) due_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub(crate) parent_id: Option<i32>,
+    #[serde(default)]
+    pub(crate) auto_complete: bool,
+    /// Defaults to the project of the parent, or `DEFAULT_PROJECT_ID` for a top level todo.
+    #[serde(default)]
+    pub(crate) project_id: Option<i32>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq, Validate)]
 pub struct UpdateTodo {
     #[validate(length(min = 1, message = "Can not be empty"))]
     #[validate(length(max = 100, message = "Over text length"))]
-    text: Option<String>,
-    completed: Option<bool>,
-    labels: Option<Vec<i32>>,
+    pub(crate) text: Option<String>,
+    pub(crate) completed: Option<bool>,
+    pub(crate) labels: Option<Vec<i32>>,
+    pub(crate) priority: Option<Priority>,
+    /// `None` keeps the current due date, `Some(None)` (an explicit `null`) clears it.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub(crate) due_at: Option<Option<DateTime<Utc>>>,
+    /// `Some(None)` turns the todo back into a top level one.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub(crate) parent_id: Option<Option<i32>>,
+    pub(crate) auto_complete: Option<bool>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
-struct TodoFromRow {
-    id: i32,
-    text: String,
-    completed: bool,
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+pub struct MoveTodo {
+    pub project_id: i32,
+}
+
+/// Where `TodoRepository::reorder` puts a todo within the manual order of its project.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Placement {
+    /// Right before another todo of the same project.
+    Before(i32),
+    /// Right after another todo of the same project.
+    After(i32),
+    /// At this zero based index of the order, the end when past it.
+    Index(usize),
 }
 
-#[derive(Debug, Clone)]
-pub struct TodoRepositoryForDb {
-    pub pool: PgPool,
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+pub struct ReorderTodo {
+    #[serde(flatten)]
+    pub placement: Placement,
 }
 
-impl TodoRepositoryForDb {
-    pub fn new(pool: PgPool) -> Self {
-        TodoRepositoryForDb { pool }
+/// Room left between neighbouring positions, so that a move usually finds a free position
+/// between its new neighbours and only rewrites the moved todo.
+pub const POSITION_GAP: i64 = 1 << 16;
+
+/// A position strictly between `prev` and `next`, either of which is open ended when `None`.
+/// `None` once they leave no room, which calls for rebalancing the list.
+fn position_between(prev: Option<i64>, next: Option<i64>) -> Option<i64> {
+    match (prev, next) {
+        (None, None) => Some(POSITION_GAP),
+        (Some(prev), None) => prev.checked_add(POSITION_GAP),
+        (None, Some(next)) => next.checked_sub(POSITION_GAP),
+        (Some(prev), Some(next)) => (next - prev >= 2).then(|| prev + (next - prev) / 2),
     }
 }
 
-#[async_trait]
-impl TodoRepository for TodoRepositoryForDb {
-    async fn create(&self, payload: CreateTodo) -> anyhow::Result<TodoEntity> {
-        let tx = self.pool.begin().await?;
-        let row = sqlx::query_as::<_, TodoFromRow>(
-            r#"
-                INSERT INTO todos ( text, completed )
-                VALUES ( $1, false )
-                RETURNING *;
-            "#,
-        )
-        .bind(payload.text.clone())
-        .fetch_one(&self.pool)
-        .await?;
-
-        sqlx::query(
-            r#"
-                INSERT INTO todo_labels ( todo_id, label_id )
-                SELECT $1, id
-                FROM UNNEST ( $2 ) as t (id);
-            "#,
-        )
-        .bind(row.id)
-        .bind(payload.labels)
-        .execute(&self.pool)
-        .await?;
-
-        tx.commit().await?;
-
-        let todo = self.find(row.id).await?;
-        Ok(todo)
-    }
-
-    async fn find(&self, id: i32) -> anyhow::Result<TodoEntity> {
-        let items = sqlx::query_as::<_, TodoWithLabelFromRow>(
-            r#"
-                SELECT todos.*, labels.id as label_id, labels.name as label_name
-                FROM todos
-                LEFT OUTER JOIN todo_labels tl ON todos.id = tl.todo_id
-                LEFT OUTER JOIN labels ON labels.id = tl.label_id
-                WHERE todos.id = $1;
-            "#,
-        )
-        .bind(id)
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
-            _ => RepositoryError::Unexpected(e.to_string()),
-        })?;
-
-        let todos = fold_entities(items);
-        let todo = todos.first().ok_or(RepositoryError::NotFound(id))?;
-        Ok(todo.clone())
-    }
-
-    async fn all(&self) -> anyhow::Result<Vec<TodoEntity>> {
-        let items = sqlx::query_as::<_, TodoWithLabelFromRow>(
-            r#"
-                SELECT todos.*, labels.id as label_id, labels.name as label_name
-                FROM todos
-                LEFT OUTER JOIN todo_labels tl ON todos.id = tl.todo_id
-                LEFT OUTER JOIN labels ON labels.id = tl.label_id
-                ORDER BY todos.id desc;
-            "#,
-        )
-        .fetch_all(&self.pool)
-        .await?;
-
-        Ok(fold_entities(items))
-    }
-
-    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<TodoEntity> {
-        let tx = self.pool.begin().await?;
-
-        let old_todo = self.find(id).await?;
-        sqlx::query(
-            r#"
-                UPDATE todos SET text=$1, completed=$2
-                WHERE id=$3
-                RETURNING *
-            "#,
-        )
-        .bind(payload.text.unwrap_or(old_todo.text))
-        .bind(payload.completed.unwrap_or(old_todo.completed))
-        .bind(id)
-        .fetch_one(&self.pool)
-        .await?;
-
-        if let Some(labels) = payload.labels {
-            // todos label update
-            sqlx::query(
-                r#"
-                    DELETE FROM todo_labels WHERE todo_id = $1
-                "#,
-            )
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
-
-            sqlx::query(
-                r#"
-                    INSERT INTO todo_labels ( todo_id, label_id )
-                    SELECT $1, id
-                    FROM UNNEST ( $2 ) AS t ( id );
-                "#,
-            )
-            .bind(id)
-            .bind(labels)
-            .execute(&self.pool)
-            .await?;
-        }
-
-        tx.commit().await?;
-        let todo = self.find(id).await?;
-
-        Ok(todo)
-    }
-
-    async fn delete(&self, id: i32) -> anyhow::Result<()> {
-        let tx = self.pool.begin().await?;
-        // todos label delete]
-        sqlx::query(
-            r#"
-                DELETE FROM todo_labels WHERE todo_id = $1
-            "#,
-        )
-        .bind(id)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
-            _ => RepositoryError::Unexpected(e.to_string()),
-        })?;
-
-        // todos delete
-        sqlx::query(
-            r#"
-                DELETE FROM todos WHERE id = $1
-            "#,
-        )
-        .bind(id)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
-            _ => RepositoryError::Unexpected(e.to_string()),
-        })?;
-
-        tx.commit().await?;
-
-        Ok(())
+/// Index of the slot `placement` names in `others`, the `(position, id)` of the rest of the
+/// list in order. A missing target is the caller's to report.
+fn placement_slot(others: &[(i64, i32)], placement: Placement) -> usize {
+    let index_of = |target: i32| others.iter().position(|(_, id)| *id == target);
+    match placement {
+        Placement::Before(target) => index_of(target).unwrap_or(others.len()),
+        Placement::After(target) => index_of(target).map_or(others.len(), |i| i + 1),
+        Placement::Index(index) => index.min(others.len()),
     }
 }
 
-#[cfg(test)]
-#[cfg(feature = "database-test")]
-mod test {
-    use super::*;
-    use dotenv::dotenv;
-    use sqlx::PgPool;
-    use std::env;
-
-    #[tokio::test]
-    async fn crud_scenario() {
-        dotenv().ok();
-        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
-        let pool = PgPool::connect(database_url)
-            .await
-            .expect(&format!("fail connect database, url is [{}]", database_url));
-
-        // label data prepare
-        let label_name = String::from("test label");
-        let optional_label = sqlx::query_as::<_, Label>(
-            r#"
-                SELECT * FROM labels WHERE name = $1
-            "#,
-        )
-        .bind(label_name.clone())
-        .fetch_optional(&pool)
-        .await
-        .expect("Failed to prepare label data.");
-        let label_1 = if let Some(label) = optional_label {
-            label
-        } else {
-            let label = sqlx::query_as::<_, Label>(
-                r#"
-                    INSERT INTO labels ( name )
-                    VALUES ( $1 )
-                    RETURNING *
-                "#,
-            )
-            .bind(label_name)
-            .fetch_one(&pool)
-            .await
-            .expect("Failed to insert label data.");
-            label
-        };
+/// New positions for putting `id` at `placement` in `list`, the `(position, id)` of every
+/// todo of its project in any order: `id` alone between its new neighbours or, once they
+/// leave no room, the whole list spread out evenly again.
+pub(crate) fn place(mut list: Vec<(i64, i32)>, id: i32, placement: Placement) -> Vec<(i32, i64)> {
+    list.retain(|(_, other)| *other != id);
+    list.sort();
+    let slot = placement_slot(&list, placement);
+    let prev = slot.checked_sub(1).map(|i| list[i].0);
+    let next = list.get(slot).map(|(position, _)| *position);
+    if let Some(position) = position_between(prev, next) {
+        return vec![(id, position)];
+    }
+    let mut ids: Vec<i32> = list.into_iter().map(|(_, id)| id).collect();
+    ids.insert(slot, id);
+    rebalanced(ids)
+}
 
-        let repository = TodoRepositoryForDb::new(pool.clone());
-        let todo_text = "[crud_scenario] text";
-
-        // create
-        let created = repository
-            .create(CreateTodo::new(todo_text.to_string(), vec![label_1.id]))
-            .await
-            .expect("[create] returned Err");
-        assert_eq!(created.text, todo_text);
-        assert!(!created.completed);
-        assert_eq!(*created.labels.first().unwrap(), label_1);
-
-        // find
-        let todo = repository
-            .find(created.id)
-            .await
-            .expect("[find] returned Err");
-        assert_eq!(created, todo);
-
-        // all
-        let todos = repository.all().await.expect("[all] returned Err");
-        let todo = todos.first().unwrap();
-        assert_eq!(created, *todo);
-
-        // update
-        let update_text = "[crud_scenario] updated text";
-        let todo = repository
-            .update(
-                todo.id,
-                UpdateTodo {
-                    text: Some(update_text.to_string()),
-                    completed: Some(true),
-                    labels: Some(vec![]),
-                },
-            )
-            .await
-            .expect("[update] returned Err");
-        assert_eq!(created.id, todo.id);
-        assert_eq!(update_text, todo.text);
-        assert!(todo.labels.len() == 0);
-
-        // delete
-        let _ = repository
-            .delete(todo.id)
-            .await
-            .expect("[delete] returned Err");
-        let res = repository.find(todo.id).await; // expect not found err
-        assert!(res.is_err());
-
-        let todo_rows = sqlx::query(
-            r#"
-                SELECT * FROM todos WHERE id = $1
-            "#,
-        )
-        .bind(todo.id)
-        .fetch_all(&pool)
-        .await
-        .expect("[delete] todo_labels fetch error");
-        assert!(todo_rows.len() == 0);
-
-        let rows = sqlx::query(
-            r#"
-                SELECT * FROM todo_labels WHERE todo_id = $1
-            "#,
-        )
-        .bind(todo.id)
-        .fetch_all(&pool)
-        .await
-        .expect("[delete] todo_labels fetch error");
-        assert!(rows.len() == 0)
+/// Positions spread `POSITION_GAP` apart for `ids` in order.
+pub(crate) fn rebalanced(ids: Vec<i32>) -> Vec<(i32, i64)> {
+    ids.into_iter()
+        .zip(1..)
+        .map(|(id, n)| (id, n * POSITION_GAP))
+        .collect()
+}
+
+fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Deserialize::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+struct TodoFromRow {
+    id: i32,
+    text: String,
+    completed: bool,
+    priority: Priority,
+    due_at: Option<DateTime<Utc>>,
+    parent_id: Option<i32>,
+    auto_complete: bool,
+    project_id: i32,
+    position: i64,
+}
+
+impl TodoFromRow {
+    fn with_labels(self, labels: Vec<Label>) -> TodoEntity {
+        TodoEntity {
+            id: self.id,
+            text: self.text,
+            completed: self.completed,
+            priority: self.priority,
+            due_at: self.due_at,
+            parent_id: self.parent_id,
+            auto_complete: self.auto_complete,
+            project_id: self.project_id,
+            position: self.position,
+            labels,
+        }
     }
 }
 
+/// The first label `labels` names more than once, as a todo carries each label once.
+pub(crate) fn repeated_label(labels: &[i32]) -> Option<i32> {
+    labels
+        .iter()
+        .enumerate()
+        .find(|(i, id)| labels[..*i].contains(id))
+        .map(|(_, id)| *id)
+}
+
+mod pg;
+mod sql;
+mod sqlite;
+
+pub use pg::TodoRepositoryForDb;
+pub use sqlite::TodoRepositoryForSqlite;
+
+#[cfg(test)]
+pub(crate) mod contract;
+
 #[cfg(test)]
 pub mod test_utils {
     use super::*;
+    use crate::repositories::label::test_utils::LabelRepositoryForMemory;
     use anyhow::Context;
     use axum::async_trait;
     use std::{
@@ -399,6 +673,13 @@ pub mod test_utils {
                 id,
                 text,
                 completed: false,
+                priority: Priority::None,
+                due_at: None,
+                parent_id: None,
+                auto_complete: false,
+                project_id: DEFAULT_PROJECT_ID,
+                // where the first todo of a project goes
+                position: POSITION_GAP,
                 labels,
             }
         }
@@ -406,56 +687,191 @@ pub mod test_utils {
 
     impl CreateTodo {
         pub fn new(text: String, labels: Vec<i32>) -> Self {
-            Self { text, labels }
+            Self {
+                text,
+                labels,
+                ..Default::default()
+            }
+        }
+
+        pub fn in_project(self, project_id: i32) -> Self {
+            Self {
+                project_id: Some(project_id),
+                ..self
+            }
         }
     }
 
-    type TodoDatas = HashMap<i32, TodoEntity>;
+    pub type TodoDatas = HashMap<i32, TodoEntity>;
+
+    /// Todos keyed by the id of their owner.
+    pub type TodoStore = Arc<RwLock<HashMap<i32, TodoDatas>>>;
 
     #[derive(Debug, Clone)]
     pub struct TodoRepositoryForMemory {
-        store: Arc<RwLock<TodoDatas>>,
-        labels: Vec<Label>,
+        store: TodoStore,
+        labels: MemoryLabels,
+    }
+
+    /// Where the memory repository looks up the labels attached to a todo.
+    #[derive(Debug, Clone)]
+    enum MemoryLabels {
+        /// A fixed list any owner may attach.
+        Fixed(Vec<Label>),
+        /// The labels each owner created through a label repository.
+        Owned(LabelRepositoryForMemory),
     }
 
     impl TodoRepositoryForMemory {
         pub fn new(labels: Vec<Label>) -> Self {
             TodoRepositoryForMemory {
                 store: Arc::default(),
-                labels,
+                labels: MemoryLabels::Fixed(labels),
             }
         }
-        fn write_store_ref(&self) -> RwLockWriteGuard<TodoDatas> {
+
+        /// A repository attaching only the labels `labels` holds for the owner of the todo,
+        /// sharing its todos with `labels` so that renaming or deleting a label shows here.
+        pub fn with_labels(labels: LabelRepositoryForMemory) -> Self {
+            TodoRepositoryForMemory {
+                store: labels.todos(),
+                labels: MemoryLabels::Owned(labels),
+            }
+        }
+
+        /// The todos of every owner, to share with `ProjectRepositoryForMemory::with_todos`.
+        pub fn todos(&self) -> TodoStore {
+            self.store.clone()
+        }
+
+        fn write_store_ref(&self) -> RwLockWriteGuard<'_, HashMap<i32, TodoDatas>> {
             self.store.write().unwrap()
         }
 
-        fn read_store_ref(&self) -> RwLockReadGuard<TodoDatas> {
+        fn read_store_ref(&self) -> RwLockReadGuard<'_, HashMap<i32, TodoDatas>> {
             self.store.read().unwrap()
         }
 
-        fn conversion_label(&self, labels: Vec<i32>) -> Vec<Label> {
-            let mut label_list = self.labels.iter().cloned();
-            let labels = labels
+        /// A snapshot of the todos of `owner_id`.
+        fn owned(&self, owner_id: i32) -> TodoDatas {
+            self.read_store_ref()
+                .get(&owner_id)
+                .cloned()
+                .unwrap_or_default()
+        }
+
+        fn conversion_label(&self, owner_id: i32, labels: Vec<i32>) -> anyhow::Result<Vec<Label>> {
+            if let Some(label_id) = repeated_label(&labels) {
+                return Err(RepositoryError::Duplicate(label_id).into());
+            }
+            let known: Vec<Label> = match &self.labels {
+                MemoryLabels::Fixed(labels) => labels.clone(),
+                MemoryLabels::Owned(repository) => {
+                    repository.owned(owner_id).into_values().collect()
+                }
+            };
+            let unknown: Vec<i32> = labels
                 .iter()
-                .map(|id| label_list.find(|label| label.id == *id).unwrap())
+                .copied()
+                .filter(|id| !known.iter().any(|label| label.id == *id))
+                .collect();
+            if !unknown.is_empty() {
+                return Err(RepositoryError::UnknownLabels(unknown).into());
+            }
+            Ok(labels
+                .into_iter()
+                .filter_map(|id| known.iter().find(|label| label.id == id).cloned())
+                .collect())
+        }
+    }
+
+    fn last_position(store: &TodoDatas, project_id: i32) -> i64 {
+        store
+            .values()
+            .filter(|todo| todo.project_id == project_id)
+            .map(|todo| todo.position)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn check_parent(store: &TodoDatas, id: Option<i32>, parent_id: i32) -> anyhow::Result<i32> {
+        let mut ancestor_id = Some(parent_id);
+        while let Some(current) = ancestor_id {
+            let ancestor = store
+                .get(&current)
+                .filter(|_| Some(current) != id)
+                .ok_or(RepositoryError::InvalidParent(parent_id))?;
+            ancestor_id = ancestor.parent_id;
+        }
+        Ok(store[&parent_id].project_id)
+    }
+
+    fn roll_up(store: &mut TodoDatas, mut parent_id: Option<i32>) {
+        while let Some(id) = parent_id {
+            let children: Vec<bool> = store
+                .values()
+                .filter(|todo| todo.parent_id == Some(id))
+                .map(|todo| todo.completed)
                 .collect();
-            labels
+            let completed = children.iter().all(|completed| *completed);
+            let Some(todo) = store.get_mut(&id) else {
+                return;
+            };
+            if !todo.auto_complete || children.is_empty() || todo.completed == completed {
+                return;
+            }
+            todo.completed = completed;
+            parent_id = todo.parent_id;
         }
     }
 
+    fn sorted_by_id(mut todos: Vec<TodoEntity>) -> Vec<TodoEntity> {
+        todos.sort_by_key(|todo| todo.id);
+        todos
+    }
+
     #[async_trait]
     impl TodoRepository for TodoRepositoryForMemory {
-        async fn create(&self, payload: CreateTodo) -> anyhow::Result<TodoEntity> {
-            let mut store = self.write_store_ref();
-            let id = (store.len() + 1) as i32;
-            let labels = self.conversion_label(payload.labels);
-            let todo = TodoEntity::new(id, payload.text.clone(), labels);
+        async fn create(&self, owner_id: i32, payload: CreateTodo) -> anyhow::Result<TodoEntity> {
+            let mut stores = self.write_store_ref();
+            let id = stores
+                .values()
+                .flat_map(|store| store.keys())
+                .max()
+                .copied()
+                .unwrap_or(0)
+                + 1;
+            let store = stores.entry(owner_id).or_default();
+            let project_id = match payload.parent_id {
+                Some(parent_id) => {
+                    let parent_project_id = check_parent(store, None, parent_id)?;
+                    if payload
+                        .project_id
+                        .is_some_and(|project_id| project_id != parent_project_id)
+                    {
+                        return Err(RepositoryError::InvalidParent(parent_id).into());
+                    }
+                    parent_project_id
+                }
+                None => payload.project_id.unwrap_or(DEFAULT_PROJECT_ID),
+            };
+            let labels = self.conversion_label(owner_id, payload.labels)?;
+            let todo = TodoEntity {
+                priority: payload.priority,
+                position: last_position(store, project_id) + POSITION_GAP,
+                due_at: payload.due_at,
+                parent_id: payload.parent_id,
+                auto_complete: payload.auto_complete,
+                project_id,
+                ..TodoEntity::new(id, payload.text.clone(), labels)
+            };
             store.insert(id, todo.clone());
+            roll_up(store, todo.parent_id);
             Ok(todo)
         }
 
-        async fn find(&self, id: i32) -> anyhow::Result<TodoEntity> {
-            let store = self.read_store_ref();
+        async fn find(&self, owner_id: i32, id: i32) -> anyhow::Result<TodoEntity> {
+            let store = self.owned(owner_id);
             let todo = store
                 .get(&id)
                 .cloned()
@@ -463,93 +879,267 @@ pub mod test_utils {
             Ok(todo)
         }
 
-        async fn all(&self) -> anyhow::Result<Vec<TodoEntity>> {
-            let store = self.read_store_ref();
-            Ok(Vec::from_iter(store.values().cloned()))
+        async fn list(&self, owner_id: i32, query: TodoListQuery) -> anyhow::Result<TodoPage> {
+            let store = self.owned(owner_id);
+            let mut todos: Vec<TodoEntity> = store
+                .values()
+                .filter(|todo| query.filter.matches(todo) && query.is_after_cursor(todo))
+                .cloned()
+                .collect();
+            todos.sort_by(|a, b| query.compare(a, b));
+            todos.truncate(query.limit as usize + 1);
+            Ok(TodoPage::new(todos, &query))
         }
 
-        async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<TodoEntity> {
-            let mut store = self.write_store_ref();
+        async fn update(
+            &self,
+            owner_id: i32,
+            id: i32,
+            payload: UpdateTodo,
+        ) -> anyhow::Result<TodoEntity> {
+            let mut stores = self.write_store_ref();
+            let store = stores.entry(owner_id).or_default();
             let todo = store.get(&id).context(RepositoryError::NotFound(id))?;
             let text = payload.text.unwrap_or(todo.text.clone());
             let completed = payload.completed.unwrap_or(todo.completed);
+            let priority = payload.priority.unwrap_or(todo.priority);
+            let due_at = payload.due_at.unwrap_or(todo.due_at);
+            let old_parent_id = todo.parent_id;
+            let parent_id = payload.parent_id.unwrap_or(old_parent_id);
+            let old_auto_complete = todo.auto_complete;
+            let project_id = todo.project_id;
+            let auto_complete = payload.auto_complete.unwrap_or(old_auto_complete);
             let labels = match payload.labels {
-                Some(labels_id) => self.conversion_label(labels_id),
+                Some(labels_id) => self.conversion_label(owner_id, labels_id)?,
                 None => todo.labels.clone(),
             };
+            if let Some(parent_id) = parent_id.filter(|_| parent_id != old_parent_id) {
+                if check_parent(store, Some(id), parent_id)? != project_id {
+                    return Err(RepositoryError::InvalidParent(parent_id).into());
+                }
+            }
             let todo = TodoEntity {
                 id,
                 text,
                 completed,
+                priority,
+                due_at,
+                parent_id,
+                auto_complete,
+                project_id,
+                position: todo.position,
                 labels,
             };
-            store.insert(id, todo.clone());
-            Ok(todo)
+            store.insert(id, todo);
+            if auto_complete && !old_auto_complete {
+                roll_up(store, Some(id));
+            }
+            roll_up(store, parent_id);
+            if old_parent_id != parent_id {
+                roll_up(store, old_parent_id);
+            }
+            Ok(store[&id].clone())
         }
 
-        async fn delete(&self, id: i32) -> anyhow::Result<()> {
-            let mut store = self.write_store_ref();
-            store.remove(&id).ok_or(RepositoryError::NotFound(id))?;
+        async fn delete(&self, owner_id: i32, id: i32, mode: DeleteMode) -> anyhow::Result<()> {
+            let mut stores = self.write_store_ref();
+            let store = stores.entry(owner_id).or_default();
+            let parent_id = store
+                .get(&id)
+                .ok_or(RepositoryError::NotFound(id))?
+                .parent_id;
+            let children: Vec<i32> = store
+                .values()
+                .filter(|todo| todo.parent_id == Some(id))
+                .map(|todo| todo.id)
+                .collect();
+            if !children.is_empty() && mode == DeleteMode::Restrict {
+                let children = sorted_by_id(children.iter().map(|id| store[id].clone()).collect())
+                    .into_iter()
+                    .map(|todo| todo.id)
+                    .collect();
+                return Err(RepositoryError::HasChildren { id, children }.into());
+            }
+            let mut ids = vec![id];
+            while let Some(current) = ids.pop() {
+                store.remove(&current);
+                ids.extend(
+                    store
+                        .values()
+                        .filter(|todo| todo.parent_id == Some(current))
+                        .map(|todo| todo.id),
+                );
+            }
+            roll_up(store, parent_id);
             Ok(())
         }
+
+        async fn children(&self, owner_id: i32, id: i32) -> anyhow::Result<Vec<TodoEntity>> {
+            let store = self.owned(owner_id);
+            store.get(&id).ok_or(RepositoryError::NotFound(id))?;
+            Ok(sorted_by_id(
+                store
+                    .values()
+                    .filter(|todo| todo.parent_id == Some(id))
+                    .cloned()
+                    .collect(),
+            ))
+        }
+
+        async fn descendants(
+            &self,
+            owner_id: i32,
+            ids: Vec<i32>,
+        ) -> anyhow::Result<Vec<TodoEntity>> {
+            let store = self.owned(owner_id);
+            let mut found = vec![];
+            let mut parents = ids;
+            while let Some(parent_id) = parents.pop() {
+                for todo in store
+                    .values()
+                    .filter(|todo| todo.parent_id == Some(parent_id))
+                {
+                    parents.push(todo.id);
+                    found.push(todo.clone());
+                }
+            }
+            Ok(sorted_by_id(found))
+        }
+
+        async fn move_to_project(
+            &self,
+            owner_id: i32,
+            id: i32,
+            project_id: i32,
+        ) -> anyhow::Result<TodoEntity> {
+            let mut stores = self.write_store_ref();
+            let store = stores.entry(owner_id).or_default();
+            let todo = store.get(&id).ok_or(RepositoryError::NotFound(id))?;
+            if todo.project_id == project_id {
+                return Ok(todo.clone());
+            }
+            let parent_id = todo.parent_id;
+            let mut moved = vec![];
+            let mut ids = vec![id];
+            while let Some(current) = ids.pop() {
+                ids.extend(
+                    store
+                        .values()
+                        .filter(|todo| todo.parent_id == Some(current))
+                        .map(|todo| todo.id),
+                );
+                moved.push((store[&current].position, current));
+            }
+            // appended to the order of the new project, keeping their own
+            moved.sort();
+            let tail = last_position(store, project_id);
+            for (current, position) in rebalanced(moved.into_iter().map(|(_, id)| id).collect()) {
+                let todo = store.get_mut(&current).unwrap();
+                todo.project_id = project_id;
+                todo.position = tail + position;
+            }
+            if parent_id.is_some() {
+                store.get_mut(&id).unwrap().parent_id = None;
+                roll_up(store, parent_id);
+            }
+            Ok(store[&id].clone())
+        }
+
+        async fn reorder(
+            &self,
+            owner_id: i32,
+            id: i32,
+            placement: Placement,
+        ) -> anyhow::Result<TodoEntity> {
+            let mut stores = self.write_store_ref();
+            let store = stores.entry(owner_id).or_default();
+            let todo = store.get(&id).ok_or(RepositoryError::NotFound(id))?;
+            let project_id = todo.project_id;
+            if let Placement::Before(target) | Placement::After(target) = placement {
+                if target == id {
+                    return Ok(todo.clone());
+                }
+                if store
+                    .get(&target)
+                    .is_none_or(|target| target.project_id != project_id)
+                {
+                    return Err(RepositoryError::InvalidPlacement(target).into());
+                }
+            }
+            let positions = store
+                .values()
+                .filter(|todo| todo.project_id == project_id)
+                .map(|todo| (todo.position, todo.id))
+                .collect();
+            for (current, position) in place(positions, id, placement) {
+                store.get_mut(&current).unwrap().position = position;
+            }
+            Ok(store[&id].clone())
+        }
+
+        async fn stats(&self) -> anyhow::Result<TodoStats> {
+            let mut stats = TodoStats::default();
+            for todo in self
+                .read_store_ref()
+                .values()
+                .flat_map(|store| store.values())
+            {
+                if todo.completed {
+                    stats.completed += 1;
+                } else {
+                    stats.open += 1;
+                }
+                for label in &todo.labels {
+                    *stats.by_label.entry(label.id).or_default() += 1;
+                }
+            }
+            Ok(stats)
+        }
     }
+
     mod test {
         use super::*;
 
+        const OWNER_ID: i32 = 1;
+
         #[tokio::test]
-        async fn todo_crud_scenario() {
-            let text = "todo text".to_string();
-            let id = 1;
-            let label = Label {
-                id: 1,
-                name: String::from("test label1"),
-            };
-            let labels = vec![label.clone()];
-            let expected = TodoEntity::new(id, text.clone(), labels.clone());
+        async fn contract_scenario() {
+            let labels = LabelRepositoryForMemory::new();
+            contract::run(&contract::Fixture {
+                todos: TodoRepositoryForMemory::with_labels(labels.clone()),
+                labels,
+                owner: OWNER_ID,
+                other_owner: OWNER_ID + 1,
+                // the memory repository takes any project as given
+                project: DEFAULT_PROJECT_ID + 1,
+            })
+            .await;
+        }
 
-            // create
+        #[tokio::test]
+        async fn todo_stats_scenario() {
+            let labels = vec![Label::new(1, "label".to_string())];
             let repository = TodoRepositoryForMemory::new(labels);
-            let todo = repository
-                .create(CreateTodo {
-                    text,
-                    labels: vec![label.id],
-                })
-                .await
-                .expect("failed create todo");
-            assert_eq!(expected, todo);
-
-            // find
-            let todo = repository.find(todo.id).await.unwrap();
-            assert_eq!(expected, todo);
-
-            let todo = repository.all().await.expect("failed get all todo");
-            assert_eq!(vec![expected], todo);
-
-            let text = "update todo text".to_string();
-            let todo = repository
-                .update(
-                    1,
-                    UpdateTodo {
-                        text: Some(text.clone()),
-                        completed: Some(true),
-                        labels: Some(vec![]),
-                    },
-                )
-                .await
-                .expect("failed update todo.");
-            assert_eq!(
-                TodoEntity {
-                    id,
-                    text,
-                    completed: true,
-                    labels: vec![],
-                },
-                todo
-            );
+            for (owner_id, text, labels) in [
+                (OWNER_ID, "a", vec![1]),
+                (OWNER_ID, "b", vec![]),
+                (OWNER_ID + 1, "c", vec![1]),
+            ] {
+                repository
+                    .create(owner_id, CreateTodo::new(text.to_string(), labels))
+                    .await
+                    .unwrap();
+            }
+            let payload = UpdateTodo {
+                completed: Some(true),
+                ..UpdateTodo::default()
+            };
+            repository.update(OWNER_ID, 2, payload).await.unwrap();
 
-            // delete
-            let res = repository.delete(id).await;
-            assert!(res.is_ok());
+            let stats = repository.stats().await.unwrap();
+            assert_eq!(2, stats.open);
+            assert_eq!(1, stats.completed);
+            assert_eq!(BTreeMap::from([(1, 2)]), stats.by_label);
         }
 
         #[test]
@@ -568,6 +1158,12 @@ pub mod test_utils {
                     id: 1,
                     text: String::from("todo 1"),
                     completed: false,
+                    priority: Priority::None,
+                    due_at: None,
+                    parent_id: None,
+                    auto_complete: false,
+                    project_id: DEFAULT_PROJECT_ID,
+                    position: POSITION_GAP,
                     label_id: Some(label_1.id),
                     label_name: Some(label_1.name.clone()),
                 },
@@ -575,6 +1171,12 @@ pub mod test_utils {
                     id: 1,
                     text: String::from("todo 1"),
                     completed: false,
+                    priority: Priority::None,
+                    due_at: None,
+                    parent_id: None,
+                    auto_complete: false,
+                    project_id: DEFAULT_PROJECT_ID,
+                    position: POSITION_GAP,
                     label_id: Some(label_2.id),
                     label_name: Some(label_2.name.clone()),
                 },
@@ -582,6 +1184,12 @@ pub mod test_utils {
                     id: 2,
                     text: String::from("todo 2"),
                     completed: false,
+                    priority: Priority::None,
+                    due_at: None,
+                    parent_id: None,
+                    auto_complete: false,
+                    project_id: DEFAULT_PROJECT_ID,
+                    position: 2 * POSITION_GAP,
                     label_id: Some(label_1.id),
                     label_name: Some(label_1.name.clone()),
                 },
@@ -594,12 +1202,24 @@ pub mod test_utils {
                         id: 1,
                         text: String::from("todo 1"),
                         completed: false,
+                        priority: Priority::None,
+                        due_at: None,
+                        parent_id: None,
+                        auto_complete: false,
+                        project_id: DEFAULT_PROJECT_ID,
+                        position: POSITION_GAP,
                         labels: vec![label_1.clone(), label_2.clone(),],
                     },
                     TodoEntity {
                         id: 2,
                         text: String::from("todo 2"),
                         completed: false,
+                        priority: Priority::None,
+                        due_at: None,
+                        parent_id: None,
+                        auto_complete: false,
+                        project_id: DEFAULT_PROJECT_ID,
+                        position: 2 * POSITION_GAP,
                         labels: vec![label_1.clone(),]
                     },
                 ]
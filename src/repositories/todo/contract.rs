@@ -0,0 +1,1251 @@
+//! Behaviour every backend has to share, run by each of them through [`run`].
+//!
+//! The scenarios only go through `TodoRepository` and `LabelRepository`, so a new backend
+//! is held to the same contract by calling [`run`] from its test module.
+
+use super::*;
+use crate::repositories::label::{LabelDeleteMode, LabelRepository};
+
+/// The repositories under test, sharing one store, and two distinct users of it.
+pub struct Fixture<T, L> {
+    pub todos: T,
+    pub labels: L,
+    pub owner: i32,
+    pub other_owner: i32,
+    /// A project of `owner` besides the default one.
+    pub project: i32,
+}
+
+/// Runs every scenario in turn, each one starting from owners without todos or labels.
+pub async fn run<T: TodoRepository, L: LabelRepository>(fixture: &Fixture<T, L>) {
+    clear(fixture).await;
+    label_scenario(fixture).await;
+    clear(fixture).await;
+    crud_scenario(fixture).await;
+    clear(fixture).await;
+    list_scenario(fixture).await;
+    clear(fixture).await;
+    due_scenario(fixture).await;
+    clear(fixture).await;
+    subtask_scenario(fixture).await;
+    clear(fixture).await;
+    project_scenario(fixture).await;
+    clear(fixture).await;
+    not_found_scenario(fixture).await;
+    clear(fixture).await;
+    ordering_scenario(fixture).await;
+    clear(fixture).await;
+    smart_ordering_scenario(fixture).await;
+    clear(fixture).await;
+    manual_ordering_scenario(fixture).await;
+    clear(fixture).await;
+    label_replacement_scenario(fixture).await;
+    clear(fixture).await;
+    label_delete_scenario(fixture).await;
+    clear(fixture).await;
+    label_merge_scenario(fixture).await;
+    clear(fixture).await;
+    cascade_scenario(fixture).await;
+}
+
+/// Deletes whatever both owners have left, e.g. from an earlier run on a persistent database.
+async fn clear<T: TodoRepository, L: LabelRepository>(fixture: &Fixture<T, L>) {
+    for owner_id in [fixture.owner, fixture.other_owner] {
+        let query = TodoListQuery {
+            filter: TodoFilter {
+                roots_only: true,
+                ..TodoFilter::default()
+            },
+            limit: TodoListQuery::MAX_LIMIT,
+            ..TodoListQuery::default()
+        };
+        loop {
+            let page = fixture.todos.list(owner_id, query.clone()).await.unwrap();
+            if page.items.is_empty() {
+                break;
+            }
+            for todo in page.items {
+                fixture
+                    .todos
+                    .delete(owner_id, todo.id, DeleteMode::Cascade)
+                    .await
+                    .unwrap();
+            }
+        }
+        for label in fixture.labels.all(owner_id).await.unwrap() {
+            fixture
+                .labels
+                .delete(owner_id, label.id, LabelDeleteMode::Detach)
+                .await
+                .unwrap();
+        }
+    }
+}
+
+fn is_not_found(err: &anyhow::Error, expected: i32) -> bool {
+    matches!(
+        err.downcast_ref::<RepositoryError>(),
+        Some(RepositoryError::NotFound(id)) if *id == expected
+    )
+}
+
+fn is_invalid_placement(err: &anyhow::Error, expected: i32) -> bool {
+    matches!(
+        err.downcast_ref::<RepositoryError>(),
+        Some(RepositoryError::InvalidPlacement(id)) if *id == expected
+    )
+}
+
+fn is_unknown_labels(err: &anyhow::Error, expected: &[i32]) -> bool {
+    matches!(
+        err.downcast_ref::<RepositoryError>(),
+        Some(RepositoryError::UnknownLabels(ids)) if ids == expected
+    )
+}
+
+fn is_duplicate(err: &anyhow::Error, expected: i32) -> bool {
+    matches!(
+        err.downcast_ref::<RepositoryError>(),
+        Some(RepositoryError::Duplicate(id)) if *id == expected
+    )
+}
+
+async fn create<T: TodoRepository>(
+    todos: &T,
+    owner_id: i32,
+    text: &str,
+    labels: Vec<i32>,
+    parent_id: Option<i32>,
+) -> TodoEntity {
+    let payload = CreateTodo {
+        parent_id,
+        ..CreateTodo::new(text.to_string(), labels)
+    };
+    todos
+        .create(owner_id, payload)
+        .await
+        .unwrap_or_else(|e| panic!("[create] {text} returned Err: {e}"))
+}
+
+async fn label_scenario<T: TodoRepository, L: LabelRepository>(fixture: &Fixture<T, L>) {
+    let Fixture {
+        labels,
+        owner,
+        other_owner,
+        ..
+    } = fixture;
+
+    let b = labels.create(*owner, "b".to_string()).await.unwrap();
+    let a = labels.create(*owner, "a".to_string()).await.unwrap();
+    assert_eq!("b", b.name);
+    assert!(b.id < a.id);
+
+    // names are unique per owner only
+    let err = labels
+        .create(*owner, "b".to_string())
+        .await
+        .expect_err("[create] duplicate returned Ok");
+    assert!(is_duplicate(&err, b.id), "{err:?}");
+    // whatever the case
+    let err = labels
+        .create(*owner, "B".to_string())
+        .await
+        .expect_err("[create] duplicate in another case returned Ok");
+    assert!(is_duplicate(&err, b.id), "{err:?}");
+    let foreign = labels.create(*other_owner, "b".to_string()).await.unwrap();
+
+    // ordered by id, not by name, and never showing other owners' labels
+    assert_eq!(
+        vec![b.clone(), a.clone()],
+        labels.all(*owner).await.unwrap()
+    );
+    assert_eq!(
+        vec![foreign.clone()],
+        labels.all(*other_owner).await.unwrap()
+    );
+
+    let err = labels
+        .update(*owner, a.id, "b".to_string())
+        .await
+        .expect_err("[update] duplicate returned Ok");
+    assert!(is_duplicate(&err, b.id), "{err:?}");
+    let err = labels
+        .update(*owner, a.id, "B".to_string())
+        .await
+        .expect_err("[update] duplicate in another case returned Ok");
+    assert!(is_duplicate(&err, b.id), "{err:?}");
+    // keeping its own name, in any case, is not a duplicate
+    assert_eq!(
+        Label::new(a.id, "A".to_string()),
+        labels.update(*owner, a.id, "A".to_string()).await.unwrap()
+    );
+    assert_eq!(
+        a,
+        labels.update(*owner, a.id, "a".to_string()).await.unwrap()
+    );
+    let renamed = labels.update(*owner, a.id, "c".to_string()).await.unwrap();
+    assert_eq!(Label::new(a.id, "c".to_string()), renamed);
+
+    let err = labels
+        .update(*owner, foreign.id, "d".to_string())
+        .await
+        .expect_err("[update] foreign returned Ok");
+    assert!(is_not_found(&err, foreign.id), "{err:?}");
+    let err = labels
+        .delete(*owner, foreign.id, LabelDeleteMode::Detach)
+        .await
+        .expect_err("[delete] foreign returned Ok");
+    assert!(is_not_found(&err, foreign.id), "{err:?}");
+
+    labels
+        .delete(*owner, b.id, LabelDeleteMode::Restrict)
+        .await
+        .unwrap();
+    assert_eq!(vec![renamed], labels.all(*owner).await.unwrap());
+    let err = labels
+        .delete(*owner, b.id, LabelDeleteMode::Detach)
+        .await
+        .expect_err("[delete] deleted returned Ok");
+    assert!(is_not_found(&err, b.id), "{err:?}");
+}
+
+async fn not_found_scenario<T: TodoRepository, L: LabelRepository>(fixture: &Fixture<T, L>) {
+    let Fixture {
+        todos,
+        labels,
+        owner,
+        other_owner,
+        ..
+    } = fixture;
+    let label = labels.create(*owner, "label".to_string()).await.unwrap();
+    let foreign_label = labels
+        .create(*other_owner, "label".to_string())
+        .await
+        .unwrap();
+    let foreign = create(todos, *other_owner, "foreign", vec![], None).await;
+    let todo = create(todos, *owner, "todo", vec![label.id], None).await;
+    let missing = foreign.id.max(todo.id) + 1000;
+
+    // a todo of someone else is as good as missing
+    for id in [foreign.id, missing] {
+        let err = todos
+            .find(*owner, id)
+            .await
+            .expect_err("[find] returned Ok");
+        assert!(is_not_found(&err, id), "{err:?}");
+        let err = todos
+            .update(*owner, id, UpdateTodo::default())
+            .await
+            .expect_err("[update] returned Ok");
+        assert!(is_not_found(&err, id), "{err:?}");
+        let err = todos
+            .delete(*owner, id, DeleteMode::Cascade)
+            .await
+            .expect_err("[delete] returned Ok");
+        assert!(is_not_found(&err, id), "{err:?}");
+        let err = todos
+            .children(*owner, id)
+            .await
+            .expect_err("[children] returned Ok");
+        assert!(is_not_found(&err, id), "{err:?}");
+    }
+    assert_eq!(foreign, todos.find(*other_owner, foreign.id).await.unwrap());
+
+    // labels of someone else are as good as missing, every one of them is named
+    // in the order given, and nothing is left behind
+    let missing_label = foreign_label.id.max(label.id) + 1000;
+    let expected = vec![missing_label, foreign_label.id];
+    let err = todos
+        .create(
+            *owner,
+            CreateTodo::new(
+                "labelled".to_string(),
+                vec![missing_label, label.id, foreign_label.id],
+            ),
+        )
+        .await
+        .expect_err("[create] returned Ok");
+    assert!(is_unknown_labels(&err, &expected), "{err:?}");
+    let payload = UpdateTodo {
+        text: Some("relabelled".to_string()),
+        labels: Some(vec![missing_label, foreign_label.id, label.id]),
+        ..UpdateTodo::default()
+    };
+    let err = todos
+        .update(*owner, todo.id, payload)
+        .await
+        .expect_err("[update] returned Ok");
+    assert!(is_unknown_labels(&err, &expected), "{err:?}");
+    let page = todos.list(*owner, TodoListQuery::default()).await.unwrap();
+    assert_eq!(vec![todo], page.items);
+}
+
+async fn crud_scenario<T: TodoRepository, L: LabelRepository>(fixture: &Fixture<T, L>) {
+    let Fixture {
+        todos,
+        labels,
+        owner,
+        other_owner,
+        ..
+    } = fixture;
+    let label = labels.create(*owner, "label".to_string()).await.unwrap();
+
+    // a new todo is open, has no due date and goes first into the default project
+    let created = create(todos, *owner, "text", vec![label.id], None).await;
+    assert_eq!(
+        TodoEntity::new(created.id, "text".to_string(), vec![label.clone()]),
+        created
+    );
+    assert_eq!(created, todos.find(*owner, created.id).await.unwrap());
+    let page = todos.list(*owner, TodoListQuery::default()).await.unwrap();
+    assert_eq!(vec![created.clone()], page.items);
+    assert!(todos
+        .list(*other_owner, TodoListQuery::default())
+        .await
+        .unwrap()
+        .items
+        .is_empty());
+
+    // the due date keeps its fraction of a second
+    let due_at = "2000-01-10T20:00:00.5Z".parse::<DateTime<Utc>>().unwrap();
+    let updated = todos
+        .update(
+            *owner,
+            created.id,
+            UpdateTodo {
+                text: Some("updated".to_string()),
+                completed: Some(true),
+                labels: Some(vec![]),
+                due_at: Some(Some(due_at)),
+                ..UpdateTodo::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!("updated", updated.text);
+    assert!(updated.completed);
+    assert!(updated.labels.is_empty());
+    assert_eq!(Some(due_at), updated.due_at);
+    assert_eq!(updated, todos.find(*owner, created.id).await.unwrap());
+
+    // leaving the due date out keeps it, an explicit null clears it
+    let payload = UpdateTodo {
+        completed: Some(false),
+        ..UpdateTodo::default()
+    };
+    let updated = todos.update(*owner, created.id, payload).await.unwrap();
+    assert_eq!(Some(due_at), updated.due_at);
+    let payload: UpdateTodo = serde_json::from_str(r#"{ "due_at": null }"#).unwrap();
+    let updated = todos.update(*owner, created.id, payload).await.unwrap();
+    assert_eq!(None, updated.due_at);
+
+    todos
+        .delete(*owner, created.id, DeleteMode::Restrict)
+        .await
+        .unwrap();
+    let err = todos
+        .find(*owner, created.id)
+        .await
+        .expect_err("[find] deleted returned Ok");
+    assert!(is_not_found(&err, created.id), "{err:?}");
+    assert_eq!(vec![label], labels.all(*owner).await.unwrap());
+}
+
+async fn list_scenario<T: TodoRepository, L: LabelRepository>(fixture: &Fixture<T, L>) {
+    let Fixture {
+        todos,
+        labels,
+        owner,
+        ..
+    } = fixture;
+    let label = labels.create(*owner, "label".to_string()).await.unwrap();
+    let mut created = vec![];
+    for (text, labels, due_at) in [
+        ("b 100%", vec![label.id], Some("2000-01-11T12:00:00+09:00")),
+        ("a", vec![], None),
+        ("C", vec![label.id], Some("2000-01-09T12:00:00Z")),
+    ] {
+        let payload = CreateTodo {
+            due_at: due_at.map(|due_at| due_at.parse().unwrap()),
+            ..CreateTodo::new(text.to_string(), labels)
+        };
+        created.push(todos.create(*owner, payload).await.unwrap());
+    }
+    let (b, a, c) = (created[0].clone(), created[1].clone(), created[2].clone());
+
+    // the counts cover every owner, so others may add to them
+    let stats = todos.stats().await.unwrap();
+    assert!(stats.open >= 3, "{stats:?}");
+    assert!(
+        stats
+            .by_label
+            .get(&label.id)
+            .is_some_and(|todos| *todos >= 2),
+        "{stats:?}"
+    );
+
+    // text compares bytewise, so upper case sorts first, also across pages
+    let mut query = TodoListQuery {
+        sort: SortField::Text,
+        order: SortOrder::Asc,
+        limit: 2,
+        ..TodoListQuery::default()
+    };
+    let page = todos.list(*owner, query.clone()).await.unwrap();
+    assert_eq!(vec![c.clone(), a.clone()], page.items);
+    query.cursor = Some(TodoCursor::decode(&page.next_cursor.unwrap()).unwrap());
+    let page = todos.list(*owner, query).await.unwrap();
+    assert_eq!(vec![b.clone()], page.items);
+    assert_eq!(None, page.next_cursor);
+
+    // todos without a due date come last, also across pages
+    let mut query = TodoListQuery {
+        sort: SortField::DueAt,
+        order: SortOrder::Asc,
+        limit: 2,
+        ..TodoListQuery::default()
+    };
+    let page = todos.list(*owner, query.clone()).await.unwrap();
+    assert_eq!(vec![c.clone(), b.clone()], page.items);
+    query.cursor = Some(TodoCursor::decode(&page.next_cursor.unwrap()).unwrap());
+    let page = todos.list(*owner, query).await.unwrap();
+    assert_eq!(vec![a.clone()], page.items);
+    assert_eq!(None, page.next_cursor);
+
+    let filtered = |filter: TodoFilter| async move {
+        let query = TodoListQuery {
+            filter,
+            ..TodoListQuery::default()
+        };
+        todos.list(*owner, query).await.unwrap().items
+    };
+    assert_eq!(
+        vec![c.clone(), b.clone()],
+        filtered(TodoFilter {
+            label_ids: vec![label.id],
+            ..TodoFilter::default()
+        })
+        .await
+    );
+    // the search ignores case and matches LIKE wildcards literally
+    for (text, expected) in [
+        ("c", vec![c.clone()]),
+        ("B 100", vec![b.clone()]),
+        ("_ 100%", vec![]),
+    ] {
+        let filter = TodoFilter {
+            text: Some(text.to_string()),
+            ..TodoFilter::default()
+        };
+        assert_eq!(expected, filtered(filter).await, "{text}");
+    }
+    let filter = TodoFilter {
+        label_ids: vec![label.id],
+        text: Some("B".to_string()),
+        ..TodoFilter::default()
+    };
+    assert_eq!(vec![b.clone()], filtered(filter).await);
+
+    let payload = UpdateTodo {
+        completed: Some(true),
+        ..UpdateTodo::default()
+    };
+    let a = todos.update(*owner, a.id, payload).await.unwrap();
+    let filter = TodoFilter {
+        completed: Some(true),
+        ..TodoFilter::default()
+    };
+    assert_eq!(vec![a], filtered(filter).await);
+}
+
+async fn due_scenario<T: TodoRepository, L: LabelRepository>(fixture: &Fixture<T, L>) {
+    let Fixture { todos, owner, .. } = fixture;
+    let now = "2000-01-10T20:00:00Z".parse::<DateTime<Utc>>().unwrap();
+    let mut ids = vec![];
+    for (due_at, completed) in [
+        ("2000-01-09T12:00:00Z", false),
+        ("2000-01-11T12:00:00+09:00", false),
+        ("2000-01-16T12:00:00Z", false),
+        ("2000-01-09T00:00:00Z", true),
+    ] {
+        let payload = CreateTodo {
+            due_at: Some(due_at.parse().unwrap()),
+            ..CreateTodo::new(due_at.to_string(), vec![])
+        };
+        let todo = todos.create(*owner, payload).await.unwrap();
+        let payload = UpdateTodo {
+            completed: Some(completed),
+            ..UpdateTodo::default()
+        };
+        // completing a todo keeps its due date
+        let updated = todos.update(*owner, todo.id, payload).await.unwrap();
+        assert_eq!(todo.due_at, updated.due_at);
+        ids.push(todo.id);
+    }
+    let due = |window: DueWindow| async move {
+        let query = TodoListQuery {
+            filter: TodoFilter {
+                due: Some(window),
+                ..TodoFilter::default()
+            },
+            sort: SortField::DueAt,
+            order: SortOrder::Asc,
+            ..TodoListQuery::default()
+        };
+        let page = todos.list(*owner, query).await.unwrap();
+        page.items
+            .into_iter()
+            .map(|todo| todo.id)
+            .collect::<Vec<_>>()
+    };
+
+    // completed todos are never due
+    assert_eq!(vec![ids[0]], due(DueWindow::overdue(now)).await);
+
+    let tokyo = FixedOffset::east_opt(9 * 3600).unwrap();
+    assert_eq!(
+        vec![ids[1]],
+        due(DueWindow::today(now.with_timezone(&tokyo))).await
+    );
+    // the same due date is already tomorrow in UTC
+    assert!(due(DueWindow::today(now.fixed_offset())).await.is_empty());
+
+    assert_eq!(vec![ids[1], ids[2]], due(DueWindow::within(now, 7)).await);
+}
+
+async fn subtask_scenario<T: TodoRepository, L: LabelRepository>(fixture: &Fixture<T, L>) {
+    let Fixture { todos, owner, .. } = fixture;
+    let parent = todos
+        .create(
+            *owner,
+            CreateTodo {
+                auto_complete: true,
+                ..CreateTodo::new("parent".to_string(), vec![])
+            },
+        )
+        .await
+        .unwrap();
+    let a = create(todos, *owner, "a", vec![], Some(parent.id)).await;
+    let b = create(todos, *owner, "b", vec![], Some(parent.id)).await;
+    let grandchild = create(todos, *owner, "grandchild", vec![], Some(a.id)).await;
+    let missing = grandchild.id + 1000;
+    let err = todos
+        .create(
+            *owner,
+            CreateTodo {
+                parent_id: Some(missing),
+                ..CreateTodo::new("orphan".to_string(), vec![])
+            },
+        )
+        .await
+        .expect_err("[create] orphan returned Ok");
+    assert!(
+        matches!(
+            err.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::InvalidParent(id)) if *id == missing
+        ),
+        "{err:?}"
+    );
+
+    assert_eq!(
+        vec![a.clone(), b.clone()],
+        todos.children(*owner, parent.id).await.unwrap()
+    );
+    let descendants = todos.descendants(*owner, vec![parent.id]).await.unwrap();
+    assert_eq!(vec![a.clone(), b.clone(), grandchild.clone()], descendants);
+    let tree = TodoNode::build(vec![parent.clone()], descendants);
+    assert_eq!(1, tree.len());
+    assert_eq!(2, tree[0].children.len());
+    assert_eq!(grandchild, tree[0].children[0].children[0].todo);
+
+    // completion rolls up once every child is done, and back down when one reopens
+    let complete = |id: i32, completed: bool| async move {
+        let payload = UpdateTodo {
+            completed: Some(completed),
+            ..UpdateTodo::default()
+        };
+        todos.update(*owner, id, payload).await.unwrap();
+    };
+    let completed = |id: i32| async move { todos.find(*owner, id).await.unwrap().completed };
+    complete(a.id, true).await;
+    assert!(!completed(parent.id).await);
+    complete(b.id, true).await;
+    assert!(completed(parent.id).await);
+    complete(b.id, false).await;
+    assert!(!completed(parent.id).await);
+    complete(b.id, true).await;
+    assert!(completed(parent.id).await);
+    // and so does a new open subtask
+    let c = create(todos, *owner, "c", vec![], Some(parent.id)).await;
+    assert!(!completed(parent.id).await);
+
+    // a todo can not move below itself
+    let err = todos
+        .update(
+            *owner,
+            parent.id,
+            UpdateTodo {
+                parent_id: Some(Some(grandchild.id)),
+                ..UpdateTodo::default()
+            },
+        )
+        .await
+        .expect_err("[update] cycle returned Ok");
+    assert!(
+        matches!(
+            err.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::InvalidParent(id)) if *id == grandchild.id
+        ),
+        "{err:?}"
+    );
+    assert_eq!(None, todos.find(*owner, parent.id).await.unwrap().parent_id);
+
+    todos
+        .delete(*owner, parent.id, DeleteMode::Cascade)
+        .await
+        .unwrap();
+    for id in [parent.id, a.id, b.id, c.id, grandchild.id] {
+        assert!(todos.find(*owner, id).await.is_err());
+    }
+}
+
+async fn project_scenario<T: TodoRepository, L: LabelRepository>(fixture: &Fixture<T, L>) {
+    let Fixture {
+        todos,
+        owner,
+        project,
+        ..
+    } = fixture;
+    let parent = todos
+        .create(
+            *owner,
+            CreateTodo {
+                auto_complete: true,
+                ..CreateTodo::new("parent".to_string(), vec![])
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(DEFAULT_PROJECT_ID, parent.project_id);
+    let done = create(todos, *owner, "done", vec![], Some(parent.id)).await;
+    let child = create(todos, *owner, "child", vec![], Some(parent.id)).await;
+    let grandchild = create(todos, *owner, "grandchild", vec![], Some(child.id)).await;
+    // subtasks live in the project of their parent
+    assert_eq!(DEFAULT_PROJECT_ID, grandchild.project_id);
+    let elsewhere = todos
+        .create(
+            *owner,
+            CreateTodo::new("elsewhere".to_string(), vec![]).in_project(*project),
+        )
+        .await
+        .unwrap();
+    assert_eq!(*project, elsewhere.project_id);
+    let err = todos
+        .create(
+            *owner,
+            CreateTodo {
+                parent_id: Some(parent.id),
+                ..CreateTodo::new("stray".to_string(), vec![]).in_project(*project)
+            },
+        )
+        .await
+        .expect_err("[create] project mismatch returned Ok");
+    assert!(
+        matches!(
+            err.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::InvalidParent(id)) if *id == parent.id
+        ),
+        "{err:?}"
+    );
+
+    let payload = UpdateTodo {
+        completed: Some(true),
+        ..UpdateTodo::default()
+    };
+    todos.update(*owner, done.id, payload).await.unwrap();
+    assert!(!todos.find(*owner, parent.id).await.unwrap().completed);
+
+    // moving a subtask takes its own subtasks along and detaches it from the parent,
+    // which is done once its open child left
+    let moved = todos
+        .move_to_project(*owner, child.id, *project)
+        .await
+        .unwrap();
+    assert_eq!((*project, None), (moved.project_id, moved.parent_id));
+    let grandchild = todos.find(*owner, grandchild.id).await.unwrap();
+    assert_eq!(*project, grandchild.project_id);
+    assert_eq!(Some(child.id), grandchild.parent_id);
+    let parent = todos.find(*owner, parent.id).await.unwrap();
+    assert_eq!(DEFAULT_PROJECT_ID, parent.project_id);
+    assert!(parent.completed);
+
+    // the subtree goes last in the new project, keeping its order
+    let query = TodoListQuery {
+        filter: TodoFilter {
+            project_id: Some(*project),
+            ..TodoFilter::default()
+        },
+        sort: SortField::Position,
+        order: SortField::Position.default_order(),
+        ..TodoListQuery::default()
+    };
+    let page = todos.list(*owner, query).await.unwrap();
+    assert_eq!(vec![elsewhere, moved.clone(), grandchild], page.items);
+
+    // a parent has to be in the same project
+    let err = todos
+        .update(
+            *owner,
+            moved.id,
+            UpdateTodo {
+                parent_id: Some(Some(parent.id)),
+                ..UpdateTodo::default()
+            },
+        )
+        .await
+        .expect_err("[update] project mismatch returned Ok");
+    assert!(
+        matches!(
+            err.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::InvalidParent(id)) if *id == parent.id
+        ),
+        "{err:?}"
+    );
+    let missing = moved.id + 1000;
+    let err = todos
+        .move_to_project(*owner, missing, *project)
+        .await
+        .expect_err("[move_to_project] missing returned Ok");
+    assert!(is_not_found(&err, missing), "{err:?}");
+}
+
+async fn ordering_scenario<T: TodoRepository, L: LabelRepository>(fixture: &Fixture<T, L>) {
+    let Fixture { todos, owner, .. } = fixture;
+    let first = create(todos, *owner, "first", vec![], None).await;
+    let second = create(todos, *owner, "second", vec![], None).await;
+    let first_child = create(todos, *owner, "first child", vec![], Some(second.id)).await;
+    let third = create(todos, *owner, "third", vec![], None).await;
+    let grandchild = create(todos, *owner, "grandchild", vec![], Some(first_child.id)).await;
+    let second_child = create(todos, *owner, "second child", vec![], Some(second.id)).await;
+    let newest_first = vec![
+        second_child.clone(),
+        grandchild.clone(),
+        third.clone(),
+        first_child.clone(),
+        second.clone(),
+        first.clone(),
+    ];
+
+    // the default list is newest first, whatever order the store keeps
+    let page = todos.list(*owner, TodoListQuery::default()).await.unwrap();
+    assert_eq!(newest_first, page.items);
+    assert_eq!(None, page.next_cursor);
+
+    // paging in twos visits every todo once, in the same order
+    let mut query = TodoListQuery {
+        limit: 2,
+        ..TodoListQuery::default()
+    };
+    let mut paged = Vec::new();
+    loop {
+        let page = todos.list(*owner, query.clone()).await.unwrap();
+        paged.extend(page.items);
+        let Some(cursor) = page.next_cursor else {
+            break;
+        };
+        query.cursor = Some(TodoCursor::decode(&cursor).unwrap());
+    }
+    assert_eq!(newest_first, paged);
+
+    let query = TodoListQuery {
+        order: SortOrder::Asc,
+        ..TodoListQuery::default()
+    };
+    let page = todos.list(*owner, query).await.unwrap();
+    assert_eq!(
+        newest_first.iter().rev().cloned().collect::<Vec<_>>(),
+        page.items
+    );
+
+    // subtasks are ordered by id
+    assert_eq!(
+        vec![first_child.clone(), second_child.clone()],
+        todos.children(*owner, second.id).await.unwrap()
+    );
+    assert_eq!(
+        vec![first_child, grandchild, second_child],
+        todos
+            .descendants(*owner, vec![third.id, second.id, first.id])
+            .await
+            .unwrap()
+    );
+}
+
+async fn smart_ordering_scenario<T: TodoRepository, L: LabelRepository>(fixture: &Fixture<T, L>) {
+    let Fixture { todos, owner, .. } = fixture;
+    let soon: DateTime<Utc> = "2030-01-01T09:00:00Z".parse().unwrap();
+    let later = soon + Duration::days(1);
+    let create = |text: &str, priority: Priority, due_at: Option<DateTime<Utc>>| {
+        let payload = CreateTodo {
+            priority,
+            due_at,
+            ..CreateTodo::new(text.to_string(), vec![])
+        };
+        async move { todos.create(*owner, payload).await.unwrap() }
+    };
+    // created in an order the sort has to undo
+    let done = create("done", Priority::Urgent, Some(soon)).await;
+    let done = todos
+        .update(
+            *owner,
+            done.id,
+            UpdateTodo {
+                completed: Some(true),
+                ..UpdateTodo::default()
+            },
+        )
+        .await
+        .unwrap();
+    let low = create("low", Priority::Low, None).await;
+    let high_later = create("high later", Priority::High, Some(later)).await;
+    let high_soon = create("high soon", Priority::High, Some(soon)).await;
+    let none_soon = create("none soon", Priority::None, Some(soon)).await;
+    let high_undated = create("high undated", Priority::High, None).await;
+    let high_later_again = create("high later again", Priority::High, Some(later)).await;
+    assert_eq!(Priority::High, high_soon.priority);
+
+    let smart = TodoListQuery {
+        sort: SortField::Smart,
+        order: SortField::Smart.default_order(),
+        ..TodoListQuery::default()
+    };
+    let most_pressing_first = vec![
+        high_soon.clone(),
+        high_later.clone(),
+        high_later_again.clone(),
+        high_undated.clone(),
+        low.clone(),
+        none_soon.clone(),
+        done.clone(),
+    ];
+    let page = todos.list(*owner, smart.clone()).await.unwrap();
+    assert_eq!(most_pressing_first, page.items);
+
+    // the cursor carries every part of the key
+    let mut query = TodoListQuery {
+        limit: 2,
+        ..smart.clone()
+    };
+    let mut paged = Vec::new();
+    loop {
+        let page = todos.list(*owner, query.clone()).await.unwrap();
+        paged.extend(page.items);
+        let Some(cursor) = page.next_cursor else {
+            break;
+        };
+        query.cursor = Some(TodoCursor::decode(&cursor).unwrap());
+    }
+    assert_eq!(most_pressing_first, paged);
+
+    let query = TodoListQuery {
+        order: SortOrder::Desc,
+        ..smart.clone()
+    };
+    let page = todos.list(*owner, query).await.unwrap();
+    assert_eq!(
+        most_pressing_first
+            .iter()
+            .rev()
+            .cloned()
+            .collect::<Vec<_>>(),
+        page.items
+    );
+
+    // raising the priority moves a todo up, past dated ones
+    let low = todos
+        .update(
+            *owner,
+            low.id,
+            UpdateTodo {
+                priority: Some(Priority::Urgent),
+                ..UpdateTodo::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(Priority::Urgent, low.priority);
+    let page = todos.list(*owner, smart).await.unwrap();
+    assert_eq!(low, page.items[0]);
+    assert_eq!(high_soon, page.items[1]);
+}
+
+async fn manual_ordering_scenario<T: TodoRepository, L: LabelRepository>(fixture: &Fixture<T, L>) {
+    let Fixture { todos, owner, .. } = fixture;
+    let by_position = TodoListQuery {
+        sort: SortField::Position,
+        order: SortField::Position.default_order(),
+        ..TodoListQuery::default()
+    };
+    let texts =
+        |page: TodoPage| -> Vec<String> { page.items.into_iter().map(|todo| todo.text).collect() };
+    let mut ids = HashMap::new();
+    for text in ["a", "b", "c", "d"] {
+        ids.insert(text, create(todos, *owner, text, vec![], None).await.id);
+    }
+    // new todos go last
+    let page = todos.list(*owner, by_position.clone()).await.unwrap();
+    assert_eq!(vec!["a", "b", "c", "d"], texts(page));
+
+    for (text, placement, expected) in [
+        ("d", Placement::Before(ids["b"]), ["a", "d", "b", "c"]),
+        ("a", Placement::After(ids["c"]), ["d", "b", "c", "a"]),
+        ("c", Placement::Index(0), ["c", "d", "b", "a"]),
+        ("d", Placement::Index(99), ["c", "b", "a", "d"]),
+        ("b", Placement::Index(2), ["c", "a", "b", "d"]),
+        ("b", Placement::Before(ids["b"]), ["c", "a", "b", "d"]),
+        ("a", Placement::Index(usize::MAX), ["c", "b", "d", "a"]),
+        ("a", Placement::Index(1), ["c", "a", "b", "d"]),
+    ] {
+        let moved = todos.reorder(*owner, ids[text], placement).await.unwrap();
+        let page = todos.list(*owner, by_position.clone()).await.unwrap();
+        assert!(page.items.contains(&moved), "{placement:?}");
+        assert_eq!(expected.to_vec(), texts(page), "{placement:?}");
+    }
+
+    // squeezing into the same spot over and over runs out of room and rebalances
+    let mut expected = vec!["c", "a", "b", "d"];
+    for _ in 0..40 {
+        let last = expected.pop().unwrap();
+        expected.insert(1, last);
+        todos
+            .reorder(*owner, ids[last], Placement::Index(1))
+            .await
+            .unwrap();
+        let page = todos.list(*owner, by_position.clone()).await.unwrap();
+        assert_eq!(expected, texts(page));
+    }
+
+    // the cursor carries the position
+    let mut query = TodoListQuery {
+        limit: 3,
+        ..by_position.clone()
+    };
+    let mut paged = Vec::new();
+    loop {
+        let page = todos.list(*owner, query.clone()).await.unwrap();
+        paged.extend(page.items.into_iter().map(|todo| todo.text));
+        let Some(cursor) = page.next_cursor else {
+            break;
+        };
+        query.cursor = Some(TodoCursor::decode(&cursor).unwrap());
+    }
+    assert_eq!(expected, paged);
+
+    let err = todos
+        .reorder(*owner, ids["a"], Placement::Before(i32::MAX))
+        .await
+        .unwrap_err();
+    assert!(is_invalid_placement(&err, i32::MAX), "{err:?}");
+    let err = todos
+        .reorder(*owner, i32::MAX, Placement::Index(0))
+        .await
+        .unwrap_err();
+    assert!(is_not_found(&err, i32::MAX), "{err:?}");
+    let foreign = create(todos, fixture.other_owner, "foreign", vec![], None).await;
+    let err = todos
+        .reorder(*owner, ids["a"], Placement::After(foreign.id))
+        .await
+        .unwrap_err();
+    // the same as a todo that does not exist, the todos of others stay hidden
+    assert!(is_invalid_placement(&err, foreign.id), "{err:?}");
+}
+
+async fn label_replacement_scenario<T: TodoRepository, L: LabelRepository>(
+    fixture: &Fixture<T, L>,
+) {
+    let Fixture {
+        todos,
+        labels,
+        owner,
+        ..
+    } = fixture;
+    let a = labels.create(*owner, "a".to_string()).await.unwrap();
+    let b = labels.create(*owner, "b".to_string()).await.unwrap();
+    let c = labels.create(*owner, "c".to_string()).await.unwrap();
+
+    // labels keep the order they were given in
+    let todo = create(todos, *owner, "todo", vec![b.id, a.id], None).await;
+    assert_eq!(vec![b.clone(), a.clone()], todo.labels);
+    assert_eq!(todo, todos.find(*owner, todo.id).await.unwrap());
+
+    // a todo carries each label once
+    let err = todos
+        .create(
+            *owner,
+            CreateTodo::new("repeated".to_string(), vec![a.id, b.id, a.id]),
+        )
+        .await
+        .expect_err("[create] repeated label returned Ok");
+    assert!(is_duplicate(&err, a.id), "{err:?}");
+    let payload = UpdateTodo {
+        labels: Some(vec![c.id, c.id]),
+        ..UpdateTodo::default()
+    };
+    let err = todos
+        .update(*owner, todo.id, payload)
+        .await
+        .expect_err("[update] repeated label returned Ok");
+    assert!(is_duplicate(&err, c.id), "{err:?}");
+    assert_eq!(todo, todos.find(*owner, todo.id).await.unwrap());
+
+    // leaving the labels out keeps them
+    let payload = UpdateTodo {
+        text: Some("renamed".to_string()),
+        ..UpdateTodo::default()
+    };
+    let updated = todos.update(*owner, todo.id, payload).await.unwrap();
+    assert_eq!("renamed", updated.text);
+    assert_eq!(vec![b.clone(), a.clone()], updated.labels);
+
+    // giving them replaces the whole set rather than adding to it
+    let payload = UpdateTodo {
+        labels: Some(vec![c.id, a.id]),
+        ..UpdateTodo::default()
+    };
+    let updated = todos.update(*owner, todo.id, payload).await.unwrap();
+    assert_eq!(vec![c.clone(), a.clone()], updated.labels);
+    assert_eq!(updated, todos.find(*owner, todo.id).await.unwrap());
+
+    let payload = UpdateTodo {
+        labels: Some(vec![]),
+        ..UpdateTodo::default()
+    };
+    let updated = todos.update(*owner, todo.id, payload).await.unwrap();
+    assert_eq!(Vec::<Label>::new(), updated.labels);
+    assert_eq!(updated, todos.find(*owner, todo.id).await.unwrap());
+}
+
+async fn label_delete_scenario<T: TodoRepository, L: LabelRepository>(fixture: &Fixture<T, L>) {
+    let Fixture {
+        todos,
+        labels,
+        owner,
+        other_owner,
+        ..
+    } = fixture;
+    let a = labels.create(*owner, "a".to_string()).await.unwrap();
+    let b = labels.create(*owner, "b".to_string()).await.unwrap();
+    let unused = labels.create(*owner, "unused".to_string()).await.unwrap();
+    let foreign = labels.create(*other_owner, "a".to_string()).await.unwrap();
+    let both = create(todos, *owner, "both", vec![a.id, b.id], None).await;
+    let only_a = create(todos, *owner, "only a", vec![a.id], Some(both.id)).await;
+    let only_b = create(todos, *owner, "only b", vec![b.id], None).await;
+
+    assert_eq!(
+        vec![both.id, only_a.id],
+        labels.usage(*owner, a.id).await.unwrap()
+    );
+    assert!(labels.usage(*owner, unused.id).await.unwrap().is_empty());
+    let err = labels
+        .usage(*owner, foreign.id)
+        .await
+        .expect_err("[usage] foreign returned Ok");
+    assert!(is_not_found(&err, foreign.id), "{err:?}");
+
+    // restrict refuses naming every todo carrying the label, and changes nothing
+    let err = labels
+        .delete(*owner, a.id, LabelDeleteMode::Restrict)
+        .await
+        .expect_err("[delete] restrict returned Ok");
+    assert!(
+        matches!(
+            err.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::InUse { id, todos })
+                if *id == a.id && *todos == vec![both.id, only_a.id]
+        ),
+        "{err:?}"
+    );
+    assert_eq!(both, todos.find(*owner, both.id).await.unwrap());
+    labels
+        .delete(*owner, unused.id, LabelDeleteMode::Restrict)
+        .await
+        .unwrap();
+
+    // todos always show the current name of their labels
+    let b = labels
+        .update(*owner, b.id, "renamed".to_string())
+        .await
+        .unwrap();
+    assert_eq!(
+        vec![a.clone(), b.clone()],
+        todos.find(*owner, both.id).await.unwrap().labels
+    );
+
+    // detaching keeps the todos and the rest of their labels
+    labels
+        .delete(*owner, a.id, LabelDeleteMode::Detach)
+        .await
+        .unwrap();
+    assert_eq!(vec![b.clone()], labels.all(*owner).await.unwrap());
+    assert_eq!(
+        vec![b.clone()],
+        todos.find(*owner, both.id).await.unwrap().labels
+    );
+    assert_eq!(
+        Vec::<Label>::new(),
+        todos.find(*owner, only_a.id).await.unwrap().labels
+    );
+    assert_eq!(
+        vec![b.clone()],
+        todos.find(*owner, only_b.id).await.unwrap().labels
+    );
+    assert_eq!(vec![foreign], labels.all(*other_owner).await.unwrap());
+    let err = labels
+        .usage(*owner, a.id)
+        .await
+        .expect_err("[usage] deleted returned Ok");
+    assert!(is_not_found(&err, a.id), "{err:?}");
+}
+
+async fn label_merge_scenario<T: TodoRepository, L: LabelRepository>(fixture: &Fixture<T, L>) {
+    let Fixture {
+        todos,
+        labels,
+        owner,
+        other_owner,
+        ..
+    } = fixture;
+    let target = labels.create(*owner, "bug".to_string()).await.unwrap();
+    let buggy = labels.create(*owner, "buggy".to_string()).await.unwrap();
+    let plural = labels.create(*owner, "bugs".to_string()).await.unwrap();
+    let other = labels.create(*owner, "ui".to_string()).await.unwrap();
+    let foreign = labels
+        .create(*other_owner, "bug".to_string())
+        .await
+        .unwrap();
+    let with_target = create(
+        todos,
+        *owner,
+        "with target",
+        vec![buggy.id, other.id, target.id],
+        None,
+    )
+    .await;
+    let with_both = create(todos, *owner, "with both", vec![plural.id, buggy.id], None).await;
+    let untouched = create(todos, *owner, "untouched", vec![other.id], None).await;
+    let target_only = create(todos, *owner, "target only", vec![target.id], None).await;
+
+    // every label has to be there before anything is touched
+    for (id, sources, missing) in [
+        (target.id, vec![buggy.id, foreign.id], foreign.id),
+        (foreign.id, vec![buggy.id], foreign.id),
+    ] {
+        let err = labels
+            .merge(*owner, id, sources)
+            .await
+            .expect_err("[merge] foreign returned Ok");
+        assert!(is_not_found(&err, missing), "{err:?}");
+    }
+    assert_eq!(
+        vec![buggy.clone(), other.clone(), target.clone()],
+        todos.find(*owner, with_target.id).await.unwrap().labels
+    );
+
+    // repeats and the target itself among the sources are ignored
+    let affected = labels
+        .merge(
+            *owner,
+            target.id,
+            vec![buggy.id, plural.id, buggy.id, target.id],
+        )
+        .await
+        .unwrap();
+    assert_eq!(2, affected);
+    assert_eq!(
+        vec![target.clone(), other.clone()],
+        labels.all(*owner).await.unwrap()
+    );
+
+    // each todo keeps a single merged label, where the first of them was
+    assert_eq!(
+        vec![target.clone(), other.clone()],
+        todos.find(*owner, with_target.id).await.unwrap().labels
+    );
+    assert_eq!(
+        vec![target.clone()],
+        todos.find(*owner, with_both.id).await.unwrap().labels
+    );
+    assert_eq!(untouched, todos.find(*owner, untouched.id).await.unwrap());
+    assert_eq!(
+        target_only,
+        todos.find(*owner, target_only.id).await.unwrap()
+    );
+    assert_eq!(
+        vec![with_target.id, with_both.id, target_only.id],
+        labels.usage(*owner, target.id).await.unwrap()
+    );
+
+    let err = labels
+        .merge(*owner, target.id, vec![buggy.id])
+        .await
+        .expect_err("[merge] merged returned Ok");
+    assert!(is_not_found(&err, buggy.id), "{err:?}");
+    assert_eq!(vec![foreign], labels.all(*other_owner).await.unwrap());
+}
+
+async fn cascade_scenario<T: TodoRepository, L: LabelRepository>(fixture: &Fixture<T, L>) {
+    let Fixture {
+        todos,
+        labels,
+        owner,
+        ..
+    } = fixture;
+    let label = labels.create(*owner, "label".to_string()).await.unwrap();
+    let root = create(todos, *owner, "root", vec![], None).await;
+    let a = create(todos, *owner, "a", vec![label.id], Some(root.id)).await;
+    let b = create(todos, *owner, "b", vec![], Some(root.id)).await;
+    let grandchild = create(todos, *owner, "grandchild", vec![label.id], Some(a.id)).await;
+    let sibling = create(todos, *owner, "sibling", vec![label.id], None).await;
+
+    // restrict names the direct subtasks only, ordered by id
+    let err = todos
+        .delete(*owner, root.id, DeleteMode::Restrict)
+        .await
+        .expect_err("[delete] restrict returned Ok");
+    assert!(
+        matches!(
+            err.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::HasChildren { id, children })
+                if *id == root.id && *children == vec![a.id, b.id]
+        ),
+        "{err:?}"
+    );
+    assert_eq!(root, todos.find(*owner, root.id).await.unwrap());
+
+    // a leaf goes with either mode
+    todos
+        .delete(*owner, b.id, DeleteMode::Restrict)
+        .await
+        .unwrap();
+    assert_eq!(
+        vec![a.clone()],
+        todos.children(*owner, root.id).await.unwrap()
+    );
+
+    // cascade takes the whole subtree and nothing else
+    todos
+        .delete(*owner, root.id, DeleteMode::Cascade)
+        .await
+        .unwrap();
+    for id in [root.id, a.id, grandchild.id] {
+        let err = todos
+            .find(*owner, id)
+            .await
+            .expect_err("[find] returned Ok");
+        assert!(is_not_found(&err, id), "{err:?}");
+    }
+    let page = todos.list(*owner, TodoListQuery::default()).await.unwrap();
+    assert_eq!(vec![sibling.clone()], page.items);
+    assert_eq!(vec![label.clone()], sibling.labels);
+    assert_eq!(vec![label], labels.all(*owner).await.unwrap());
+}
@@ -0,0 +1,441 @@
+//! The todo repository on Postgres.
+
+use super::sql::TodoQueries;
+use super::*;
+use sqlx::{types::Json, PgExecutor, PgPool, Postgres, QueryBuilder};
+
+/// The labels of `todos.id` as a JSON array in the order they were attached,
+/// so a todo comes back in one row whatever the number of its labels.
+const LABELS_JSON: &str = r#"(
+    SELECT COALESCE(json_agg(json_build_object('id', labels.id, 'name', labels.name) ORDER BY tl.id), '[]')
+    FROM todo_labels tl
+    JOIN labels ON labels.id = tl.label_id
+    WHERE tl.todo_id = todos.id
+) AS labels"#;
+
+#[derive(Debug, Clone, FromRow)]
+struct TodoWithLabelsFromRow {
+    #[sqlx(flatten)]
+    todo: TodoFromRow,
+    labels: Json<Vec<Label>>,
+}
+
+impl From<TodoWithLabelsFromRow> for TodoEntity {
+    fn from(row: TodoWithLabelsFromRow) -> Self {
+        row.todo.with_labels(row.labels.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TodoRepositoryForDb {
+    pub pool: PgPool,
+}
+
+impl TodoRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        TodoRepositoryForDb { pool }
+    }
+}
+
+async fn find_todo<'e, E: PgExecutor<'e>>(
+    executor: E,
+    owner_id: i32,
+    id: i32,
+) -> anyhow::Result<TodoEntity> {
+    let todo = sqlx::query_as::<_, TodoWithLabelsFromRow>(&format!(
+        r#"
+            SELECT todos.*, {}
+            FROM todos
+            WHERE todos.id = $1 AND todos.owner_id = $2;
+        "#,
+        LABELS_JSON
+    ))
+    .bind(id)
+    .bind(owner_id)
+    .fetch_optional(executor)
+    .await?
+    .ok_or(RepositoryError::NotFound(id))?;
+
+    Ok(todo.into())
+}
+
+#[async_trait]
+impl TodoRepository for TodoRepositoryForDb {
+    async fn create(&self, owner_id: i32, payload: CreateTodo) -> anyhow::Result<TodoEntity> {
+        unit_of_work(&self.pool, |conn| {
+            Postgres::create_todo(conn, owner_id, payload)
+        })
+        .await
+    }
+
+    async fn find(&self, owner_id: i32, id: i32) -> anyhow::Result<TodoEntity> {
+        find_todo(&self.pool, owner_id, id).await
+    }
+
+    async fn list(&self, owner_id: i32, query: TodoListQuery) -> anyhow::Result<TodoPage> {
+        // one row per todo, so LIMIT counts todos and labels are only gathered for the page
+        let mut builder = QueryBuilder::<Postgres>::new("SELECT todos.*, ");
+        builder
+            .push(LABELS_JSON)
+            .push(" FROM todos WHERE todos.owner_id = ")
+            .push_bind(owner_id);
+        query.push_conditions(&mut builder);
+        query.push_order_by(&mut builder, "todos");
+        builder.push(" LIMIT ").push_bind(query.limit + 1);
+
+        let items = builder
+            .build_query_as::<TodoWithLabelsFromRow>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(TodoPage::new(
+            items.into_iter().map(TodoEntity::from).collect(),
+            &query,
+        ))
+    }
+
+    async fn update(
+        &self,
+        owner_id: i32,
+        id: i32,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<TodoEntity> {
+        unit_of_work(&self.pool, |conn| {
+            Box::pin(async move {
+                let old_todo = find_todo(&mut *conn, owner_id, id).await?;
+                Postgres::update_todo(conn, owner_id, old_todo, payload).await
+            })
+        })
+        .await
+    }
+
+    async fn delete(&self, owner_id: i32, id: i32, mode: DeleteMode) -> anyhow::Result<()> {
+        unit_of_work(&self.pool, |conn| {
+            Box::pin(async move {
+                let parent_id = sqlx::query_scalar::<_, Option<i32>>(
+                    r#"
+                        SELECT parent_id FROM todos WHERE id = $1 AND owner_id = $2
+                    "#,
+                )
+                .bind(id)
+                .bind(owner_id)
+                .fetch_optional(&mut *conn)
+                .await?
+                .ok_or(RepositoryError::NotFound(id))?;
+
+                let subtree = Postgres::subtree(conn, id).await?;
+                let children: Vec<i32> = subtree
+                    .iter()
+                    .filter(|(_, parent_id)| *parent_id == Some(id))
+                    .map(|(child_id, _)| *child_id)
+                    .collect();
+                if !children.is_empty() && mode == DeleteMode::Restrict {
+                    return Err(RepositoryError::HasChildren { id, children }.into());
+                }
+                let ids: Vec<i32> = subtree.into_iter().map(|(id, _)| id).collect();
+                Postgres::delete_todos(conn, &ids).await?;
+
+                Postgres::roll_up(conn, parent_id).await
+            })
+        })
+        .await
+    }
+
+    async fn children(&self, owner_id: i32, id: i32) -> anyhow::Result<Vec<TodoEntity>> {
+        let mut conn = self.pool.acquire().await?;
+        find_todo(&mut *conn, owner_id, id).await?;
+        let items = sqlx::query_as::<_, TodoWithLabelsFromRow>(&format!(
+            r#"
+                SELECT todos.*, {}
+                FROM todos
+                WHERE todos.parent_id = $1
+                ORDER BY todos.id ASC;
+            "#,
+            LABELS_JSON
+        ))
+        .bind(id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        Ok(items.into_iter().map(TodoEntity::from).collect())
+    }
+
+    async fn descendants(&self, owner_id: i32, ids: Vec<i32>) -> anyhow::Result<Vec<TodoEntity>> {
+        let items = sqlx::query_as::<_, TodoWithLabelsFromRow>(&format!(
+            r#"
+                WITH RECURSIVE subtree AS (
+                    SELECT id FROM todos WHERE parent_id = ANY ( $1 ) AND owner_id = $2
+                    UNION ALL
+                    SELECT todos.id
+                    FROM todos
+                    JOIN subtree ON todos.parent_id = subtree.id
+                )
+                SELECT todos.*, {}
+                FROM todos
+                JOIN subtree ON todos.id = subtree.id
+                ORDER BY todos.id ASC;
+            "#,
+            LABELS_JSON
+        ))
+        .bind(ids)
+        .bind(owner_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(items.into_iter().map(TodoEntity::from).collect())
+    }
+
+    async fn move_to_project(
+        &self,
+        owner_id: i32,
+        id: i32,
+        project_id: i32,
+    ) -> anyhow::Result<TodoEntity> {
+        unit_of_work(&self.pool, |conn| {
+            Box::pin(async move {
+                let old_todo = find_todo(&mut *conn, owner_id, id).await?;
+                if old_todo.project_id == project_id {
+                    return Ok(old_todo);
+                }
+                Postgres::check_project(conn, owner_id, project_id).await?;
+                let ids: Vec<i32> = Postgres::subtree(conn, id)
+                    .await?
+                    .into_iter()
+                    .map(|(id, _)| id)
+                    .collect();
+                Postgres::append_to_project(conn, owner_id, project_id, &ids).await?;
+
+                if old_todo.parent_id.is_some() {
+                    sqlx::query(
+                        r#"
+                            UPDATE todos SET parent_id = NULL WHERE id = $1
+                        "#,
+                    )
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await?;
+                    Postgres::roll_up(conn, old_todo.parent_id).await?;
+                }
+
+                find_todo(conn, owner_id, id).await
+            })
+        })
+        .await
+    }
+
+    async fn reorder(
+        &self,
+        owner_id: i32,
+        id: i32,
+        placement: Placement,
+    ) -> anyhow::Result<TodoEntity> {
+        unit_of_work(&self.pool, |conn| {
+            Box::pin(async move {
+                let todo = find_todo(&mut *conn, owner_id, id).await?;
+                if let Placement::Before(target) | Placement::After(target) = placement {
+                    if target == id {
+                        return Ok(todo);
+                    }
+                    Postgres::check_target(conn, owner_id, todo.project_id, target).await?;
+                }
+                let position =
+                    Postgres::position_at(conn, owner_id, todo.project_id, id, placement).await?;
+                Postgres::set_position(conn, id, position, todo.labels).await
+            })
+        })
+        .await
+    }
+
+    async fn stats(&self) -> anyhow::Result<TodoStats> {
+        let mut conn = self.pool.acquire().await?;
+        Postgres::stats(&mut conn).await
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "database-test")]
+mod test {
+    use super::*;
+    use crate::repositories::label::LabelRepositoryForDb;
+    use crate::repositories::project::{ProjectRepository, ProjectRepositoryForDb};
+    use crate::repositories::user::test_utils::fixture_user;
+    use dotenv::dotenv;
+    use sqlx::PgPool;
+    use std::env;
+
+    #[tokio::test]
+    async fn contract_scenario() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        let owner = fixture_user(&pool, "[contract_scenario] owner").await;
+        let other_owner = fixture_user(&pool, "[contract_scenario] other owner").await;
+        // kept from an earlier run, as the scenarios only clear todos and labels
+        let projects = ProjectRepositoryForDb::new(pool.clone());
+        let existing = projects.all(owner.id).await.unwrap();
+        let project = match existing.into_iter().find(|p| p.id != DEFAULT_PROJECT_ID) {
+            Some(project) => project,
+            None => projects
+                .create(owner.id, "[contract_scenario] project".to_string())
+                .await
+                .unwrap(),
+        };
+        contract::run(&contract::Fixture {
+            todos: TodoRepositoryForDb::new(pool.clone()),
+            labels: LabelRepositoryForDb::new(pool),
+            owner: owner.id,
+            other_owner: other_owner.id,
+            project: project.id,
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn create_leaves_nothing_when_commit_fails() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        let owner = fixture_user(&pool, "[create_leaves_nothing_when_commit_fails] owner").await;
+
+        let repository = TodoRepositoryForDb::new(pool.clone());
+        let text = "[create_leaves_nothing_when_commit_fails] text";
+        // the todo row is written before the unknown label is rejected
+        let res = repository
+            .create(owner.id, CreateTodo::new(text.to_string(), vec![i32::MAX]))
+            .await;
+        assert!(res.is_err());
+
+        let rows = sqlx::query("SELECT * FROM todos WHERE text = $1")
+            .bind(text)
+            .fetch_all(&pool)
+            .await
+            .expect("[create] todos fetch error");
+        assert!(rows.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unit_of_work_rolls_back_injected_failure() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        let owner = fixture_user(&pool, "[unit_of_work_rolls_back_injected_failure] owner").await;
+
+        let label = sqlx::query_as::<_, Label>(
+            r#"
+                INSERT INTO labels ( name, owner_id )
+                VALUES ( '[unit_of_work_rolls_back_injected_failure] label', $1 )
+                RETURNING *
+            "#,
+        )
+        .bind(owner.id)
+        .fetch_one(&pool)
+        .await
+        .expect("Failed to insert label data.");
+        let repository = TodoRepositoryForDb::new(pool.clone());
+        let text = "[unit_of_work_rolls_back_injected_failure] text";
+
+        // create: fail after the todo row is written
+        let payload = CreateTodo::new(text.to_string(), vec![label.id]);
+        let owner_id = owner.id;
+        let res: anyhow::Result<()> = unit_of_work(&pool, |conn| {
+            Box::pin(async move {
+                let row =
+                    Postgres::insert_todo(conn, owner_id, &payload, DEFAULT_PROJECT_ID).await?;
+                Postgres::insert_todo_labels(conn, owner_id, row.id, &payload.labels).await?;
+                Err(anyhow::anyhow!("injected failure"))
+            })
+        })
+        .await;
+        assert!(res.is_err());
+        let rows = sqlx::query("SELECT * FROM todos WHERE text = $1")
+            .bind(text)
+            .fetch_all(&pool)
+            .await
+            .expect("[create] todos fetch error");
+        assert!(rows.is_empty());
+
+        // update / delete: fail after the labels were removed
+        let todo = repository
+            .create(owner.id, CreateTodo::new(text.to_string(), vec![label.id]))
+            .await
+            .expect("[create] returned Err");
+        let id = todo.id;
+        let res: anyhow::Result<()> = unit_of_work(&pool, |conn| {
+            Box::pin(async move {
+                sqlx::query("UPDATE todos SET text = 'changed' WHERE id = $1")
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await?;
+                Postgres::delete_todo_labels(conn, id).await?;
+                Err(anyhow::anyhow!("injected failure"))
+            })
+        })
+        .await;
+        assert!(res.is_err());
+        let found = repository
+            .find(owner.id, id)
+            .await
+            .expect("[find] returned Err");
+        assert_eq!(todo, found);
+
+        repository
+            .delete(owner.id, id, DeleteMode::Restrict)
+            .await
+            .expect("[delete] returned Err");
+        sqlx::query("DELETE FROM labels WHERE id = $1")
+            .bind(label.id)
+            .execute(&pool)
+            .await
+            .expect("Failed to delete label data.");
+    }
+
+    #[tokio::test]
+    async fn unknown_project_is_rejected() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        let owner = fixture_user(&pool, "[unknown_project_is_rejected] owner").await;
+        let repository = TodoRepositoryForDb::new(pool.clone());
+
+        let res = repository
+            .create(
+                owner.id,
+                CreateTodo::new("[unknown_project_is_rejected] text".to_string(), vec![])
+                    .in_project(-1),
+            )
+            .await
+            .expect_err("[create] unknown project returned Ok");
+        assert!(matches!(
+            res.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::InvalidProject(-1))
+        ));
+
+        let todo = repository
+            .create(
+                owner.id,
+                CreateTodo::new("[unknown_project_is_rejected] text".to_string(), vec![]),
+            )
+            .await
+            .expect("[create] returned Err");
+        let res = repository
+            .move_to_project(owner.id, todo.id, -1)
+            .await
+            .expect_err("[move_to_project] unknown project returned Ok");
+        assert!(matches!(
+            res.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::InvalidProject(-1))
+        ));
+        repository
+            .delete(owner.id, todo.id, DeleteMode::Restrict)
+            .await
+            .expect("[delete] returned Err");
+    }
+}
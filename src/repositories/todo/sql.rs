@@ -0,0 +1,717 @@
+//! The queries the Postgres and SQLite repositories have in common, written once over
+//! [`Dialect`] and run on the connection of a unit of work.
+
+use super::*;
+use sqlx::{
+    database::HasArguments, ColumnIndex, Decode, Encode, Executor, IntoArguments, Postgres,
+    QueryBuilder, Sqlite, Type,
+};
+
+impl SortField {
+    /// SQL expressions ordered on before the `id` tie breaker.
+    /// Text is compared bytewise and todos without a due date sort after every dated one,
+    /// which is the order `SortKey` implements for the in-process repositories.
+    /// Priority is negated so that every expression ascends along with the order.
+    pub(super) fn sql_keys<DB: Dialect>(&self, table: &str) -> Vec<String> {
+        let due_at = format!("COALESCE({}.due_at, {})", table, DB::INFINITY);
+        match self {
+            SortField::Id => vec![],
+            SortField::Text => vec![format!("{}.text{}", table, DB::BYTEWISE)],
+            SortField::DueAt => vec![due_at],
+            SortField::Smart => vec![
+                format!("{}.completed", table),
+                format!("-{}.priority", table),
+                due_at,
+            ],
+            SortField::Position => vec![format!("{}.position", table)],
+        }
+    }
+}
+
+impl TodoListQuery {
+    pub(crate) fn push_conditions<'a, DB: Dialect>(&self, builder: &mut QueryBuilder<'a, DB>)
+    where
+        i32: Encode<'a, DB> + Type<DB>,
+        i64: Encode<'a, DB> + Type<DB>,
+        bool: Encode<'a, DB> + Type<DB>,
+        String: Encode<'a, DB> + Type<DB>,
+        Priority: Encode<'a, DB> + Type<DB>,
+        DateTime<Utc>: Encode<'a, DB> + Type<DB>,
+        Option<DateTime<Utc>>: Encode<'a, DB>,
+    {
+        let filter = &self.filter;
+        if let Some(project_id) = filter.project_id {
+            builder
+                .push(" AND todos.project_id = ")
+                .push_bind(project_id);
+        }
+        if let Some(completed) = filter.completed {
+            builder.push(" AND todos.completed = ").push_bind(completed);
+        }
+        if !filter.label_ids.is_empty() {
+            builder.push(
+                " AND EXISTS ( SELECT 1 FROM todo_labels tl WHERE tl.todo_id = todos.id AND ",
+            );
+            DB::push_in_ids(builder, "tl.label_id", &filter.label_ids);
+            builder.push(" )");
+        }
+        if let Some(text) = &filter.text {
+            let pattern = text
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            builder
+                .push(" AND todos.text ")
+                .push(DB::ILIKE)
+                .push(" ")
+                .push_bind(format!("%{}%", pattern))
+                .push(r#" ESCAPE '\'"#);
+        }
+        if let Some(due) = filter.due {
+            builder
+                .push(" AND todos.completed = false AND todos.due_at < ")
+                .push_bind(due.to);
+            if let Some(from) = due.from {
+                builder.push(" AND todos.due_at >= ").push_bind(from);
+            }
+        }
+        if filter.roots_only {
+            builder.push(" AND todos.parent_id IS NULL");
+        }
+        if let Some(cursor) = &self.cursor {
+            let operator = match self.order {
+                SortOrder::Asc => ">",
+                SortOrder::Desc => "<",
+            };
+            builder.push(" AND ( ");
+            for key in self.sort.sql_keys::<DB>("todos") {
+                builder.push(key).push(", ");
+            }
+            builder.push("todos.id ) ").push(operator).push(" ( ");
+            match &cursor.key {
+                SortKey::Id => {}
+                SortKey::Text(text) => {
+                    builder
+                        .push_bind(text.clone())
+                        .push(DB::BYTEWISE)
+                        .push(", ");
+                }
+                SortKey::DueAt(due_at) => {
+                    builder
+                        .push("COALESCE ( ")
+                        .push_bind(*due_at)
+                        .push(", ")
+                        .push(DB::INFINITY)
+                        .push(" ), ");
+                }
+                SortKey::Smart {
+                    completed,
+                    priority,
+                    due_at,
+                } => {
+                    builder
+                        .push_bind(*completed)
+                        .push(", -")
+                        .push_bind(*priority)
+                        .push(", COALESCE ( ")
+                        .push_bind(*due_at)
+                        .push(", ")
+                        .push(DB::INFINITY)
+                        .push(" ), ");
+                }
+                SortKey::Position(position) => {
+                    builder.push_bind(*position).push(", ");
+                }
+            }
+            builder.push_bind(cursor.id).push(" )");
+        }
+    }
+
+    pub(crate) fn push_order_by<DB: Dialect>(
+        &self,
+        builder: &mut QueryBuilder<'_, DB>,
+        table: &str,
+    ) {
+        builder.push(" ORDER BY ");
+        for key in self.sort.sql_keys::<DB>(table) {
+            builder
+                .push(key)
+                .push(" ")
+                .push(self.order.sql())
+                .push(", ");
+        }
+        builder.push(table).push(".id ").push(self.order.sql());
+    }
+}
+
+#[async_trait]
+pub(super) trait TodoQueries: Dialect
+where
+    for<'c> &'c mut Self::Connection: Executor<'c, Database = Self>,
+    for<'q> <Self as HasArguments<'q>>::Arguments: IntoArguments<'q, Self>,
+    for<'q> i32: Encode<'q, Self> + Decode<'q, Self> + Type<Self>,
+    for<'q> i64: Encode<'q, Self> + Decode<'q, Self> + Type<Self>,
+    for<'q> bool: Encode<'q, Self> + Decode<'q, Self> + Type<Self>,
+    for<'q> String: Encode<'q, Self> + Decode<'q, Self> + Type<Self>,
+    for<'q> Priority: Encode<'q, Self> + Decode<'q, Self> + Type<Self>,
+    for<'q> DateTime<Utc>: Encode<'q, Self> + Decode<'q, Self> + Type<Self>,
+    for<'q> Option<i32>: Encode<'q, Self>,
+    for<'q> Option<DateTime<Utc>>: Encode<'q, Self>,
+    usize: ColumnIndex<Self::Row>,
+    for<'a> &'a str: ColumnIndex<Self::Row>,
+{
+    /// Writes `payload` as the last todo of `project_id`.
+    async fn insert_todo(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        payload: &CreateTodo,
+        project_id: i32,
+    ) -> anyhow::Result<TodoFromRow> {
+        let row = sqlx::query_as::<Self, TodoFromRow>(
+            r#"
+                INSERT INTO todos ( text, completed, due_at, parent_id, auto_complete, project_id, owner_id, priority, position )
+                VALUES ( $1, false, $2, $3, $4, $5, $6, $7, COALESCE ((
+                    SELECT max(position) FROM todos WHERE owner_id = $6 AND project_id = $5
+                ), 0 ) + $8 )
+                RETURNING *;
+            "#,
+        )
+        .bind(payload.text.clone())
+        .bind(payload.due_at)
+        .bind(payload.parent_id)
+        .bind(payload.auto_complete)
+        .bind(project_id)
+        .bind(owner_id)
+        .bind(payload.priority)
+        .bind(POSITION_GAP)
+        .fetch_one(conn)
+        .await?;
+
+        Ok(row)
+    }
+
+    async fn create_todo(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        payload: CreateTodo,
+    ) -> anyhow::Result<TodoEntity> {
+        let project_id = match payload.parent_id {
+            Some(parent_id) => {
+                let parent_project_id = Self::check_parent(conn, owner_id, None, parent_id).await?;
+                if payload
+                    .project_id
+                    .is_some_and(|project_id| project_id != parent_project_id)
+                {
+                    return Err(RepositoryError::InvalidParent(parent_id).into());
+                }
+                parent_project_id
+            }
+            None => {
+                let project_id = payload.project_id.unwrap_or(DEFAULT_PROJECT_ID);
+                Self::check_project(conn, owner_id, project_id).await?;
+                project_id
+            }
+        };
+        let row = Self::insert_todo(conn, owner_id, &payload, project_id).await?;
+        let labels = Self::insert_todo_labels(conn, owner_id, row.id, &payload.labels).await?;
+        Self::roll_up(conn, row.parent_id).await?;
+        Ok(row.with_labels(labels))
+    }
+
+    /// Applies `payload` to `old_todo`, read by the same unit of work.
+    async fn update_todo(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        old_todo: TodoEntity,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<TodoEntity> {
+        let id = old_todo.id;
+        let parent_id = payload.parent_id.unwrap_or(old_todo.parent_id);
+        if let Some(parent_id) = parent_id.filter(|_| parent_id != old_todo.parent_id) {
+            // subtasks always share the project of their parent
+            if Self::check_parent(conn, owner_id, Some(id), parent_id).await? != old_todo.project_id
+            {
+                return Err(RepositoryError::InvalidParent(parent_id).into());
+            }
+        }
+        let auto_complete = payload.auto_complete.unwrap_or(old_todo.auto_complete);
+        // switching auto-complete on takes the completion of the subtasks, if any
+        let row = sqlx::query_as::<Self, TodoFromRow>(&format!(
+            r#"
+                UPDATE todos SET text=$1, due_at=$3, parent_id=$4, auto_complete=$5, priority=$8,
+                completed = CASE WHEN $7 THEN COALESCE((
+                    SELECT {} FROM todos children WHERE children.parent_id = $6
+                ), $2) ELSE $2 END
+                WHERE id=$6
+                RETURNING *
+            "#,
+            Self::bool_and("children.completed")
+        ))
+        .bind(payload.text.unwrap_or(old_todo.text))
+        .bind(payload.completed.unwrap_or(old_todo.completed))
+        .bind(payload.due_at.unwrap_or(old_todo.due_at))
+        .bind(parent_id)
+        .bind(auto_complete)
+        .bind(id)
+        .bind(auto_complete && !old_todo.auto_complete)
+        .bind(payload.priority.unwrap_or(old_todo.priority))
+        .fetch_one(&mut *conn)
+        .await?;
+
+        let labels = match payload.labels {
+            Some(labels) => {
+                // todos label update
+                Self::delete_todo_labels(conn, id).await?;
+                Self::insert_todo_labels(conn, owner_id, id, &labels).await?
+            }
+            None => old_todo.labels,
+        };
+
+        Self::roll_up(conn, parent_id).await?;
+        if old_todo.parent_id != parent_id {
+            Self::roll_up(conn, old_todo.parent_id).await?;
+        }
+
+        Ok(row.with_labels(labels))
+    }
+
+    /// Moves `id` to `position`, keeping `labels`.
+    async fn set_position(
+        conn: &mut Self::Connection,
+        id: i32,
+        position: i64,
+        labels: Vec<Label>,
+    ) -> anyhow::Result<TodoEntity> {
+        let row = sqlx::query_as::<Self, TodoFromRow>(
+            r#"
+                UPDATE todos SET position = $1 WHERE id = $2 RETURNING *
+            "#,
+        )
+        .bind(position)
+        .bind(id)
+        .fetch_one(conn)
+        .await?;
+
+        Ok(row.with_labels(labels))
+    }
+
+    /// A free position at `placement` among the todos of `owner_id` in `project_id` other
+    /// than `id`, `None` when the neighbours there leave no room.
+    async fn free_position(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        project_id: i32,
+        id: i32,
+        placement: Placement,
+    ) -> anyhow::Result<Option<i64>> {
+        let (prev, next) = match placement {
+            Placement::Before(target) => {
+                sqlx::query_as::<Self, (Option<i64>, Option<i64>)>(
+                    r#"
+                        SELECT (
+                            SELECT position FROM todos
+                            WHERE owner_id = $1 AND project_id = $2 AND id <> $3
+                            AND ( position, id ) < ( target.position, target.id )
+                            ORDER BY position DESC, id DESC LIMIT 1
+                        ), target.position
+                        FROM todos target WHERE target.id = $4
+                    "#,
+                )
+                .bind(owner_id)
+                .bind(project_id)
+                .bind(id)
+                .bind(target)
+                .fetch_one(&mut *conn)
+                .await?
+            }
+            Placement::After(target) => {
+                sqlx::query_as::<Self, (Option<i64>, Option<i64>)>(
+                    r#"
+                        SELECT target.position, (
+                            SELECT position FROM todos
+                            WHERE owner_id = $1 AND project_id = $2 AND id <> $3
+                            AND ( position, id ) > ( target.position, target.id )
+                            ORDER BY position, id LIMIT 1
+                        )
+                        FROM todos target WHERE target.id = $4
+                    "#,
+                )
+                .bind(owner_id)
+                .bind(project_id)
+                .bind(id)
+                .bind(target)
+                .fetch_one(&mut *conn)
+                .await?
+            }
+            Placement::Index(index) => {
+                // the last todo is the one before any index past the end
+                sqlx::query_as::<Self, (Option<i64>, Option<i64>)>(
+                    r#"
+                        WITH others AS (
+                            SELECT position, row_number() OVER ( ORDER BY position, id ) - 1 AS n
+                            FROM todos
+                            WHERE owner_id = $1 AND project_id = $2 AND id <> $3
+                        )
+                        SELECT
+                            ( SELECT position FROM others WHERE n < $4 ORDER BY n DESC LIMIT 1 ),
+                            ( SELECT position FROM others WHERE n = $4 )
+                    "#,
+                )
+                .bind(owner_id)
+                .bind(project_id)
+                .bind(id)
+                .bind(i64::try_from(index).unwrap_or(i64::MAX))
+                .fetch_one(&mut *conn)
+                .await?
+            }
+        };
+
+        Ok(position_between(prev, next))
+    }
+
+    /// Spreads the todos of `owner_id` in `project_id` `POSITION_GAP` apart again, keeping
+    /// their order.
+    async fn rebalance(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        project_id: i32,
+    ) -> anyhow::Result<()> {
+        sqlx::query::<Self>(
+            r#"
+                UPDATE todos SET position = ranked.n * $3
+                FROM (
+                    SELECT id, row_number() OVER ( ORDER BY position, id ) AS n
+                    FROM todos
+                    WHERE owner_id = $1 AND project_id = $2
+                ) ranked
+                WHERE todos.id = ranked.id
+            "#,
+        )
+        .bind(owner_id)
+        .bind(project_id)
+        .bind(POSITION_GAP)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// The position `id` takes at `placement` in `project_id`, rebalancing the project first
+    /// when the neighbours there leave no room.
+    async fn position_at(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        project_id: i32,
+        id: i32,
+        placement: Placement,
+    ) -> anyhow::Result<i64> {
+        if let Some(position) =
+            Self::free_position(conn, owner_id, project_id, id, placement).await?
+        {
+            return Ok(position);
+        }
+        Self::rebalance(conn, owner_id, project_id).await?;
+        Self::free_position(conn, owner_id, project_id, id, placement)
+            .await?
+            .context("no free position after rebalancing")
+    }
+
+    /// Fails with `InvalidPlacement` unless `target` is a todo of `owner_id` in `project_id`.
+    async fn check_target(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        project_id: i32,
+        target: i32,
+    ) -> anyhow::Result<()> {
+        let target_project = sqlx::query_scalar::<Self, i32>(
+            r#"
+                SELECT project_id FROM todos WHERE id = $1 AND owner_id = $2
+            "#,
+        )
+        .bind(target)
+        .bind(owner_id)
+        .fetch_optional(conn)
+        .await?;
+        if target_project != Some(project_id) {
+            return Err(RepositoryError::InvalidPlacement(target).into());
+        }
+
+        Ok(())
+    }
+
+    /// Fails with `InvalidParent` unless `parent_id` exists and is not `id` or one of its
+    /// subtasks. Returns the project of the parent.
+    async fn check_parent(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        id: Option<i32>,
+        parent_id: i32,
+    ) -> anyhow::Result<i32> {
+        let ancestors = sqlx::query_as::<Self, (i32, i32)>(
+            r#"
+                WITH RECURSIVE ancestors AS (
+                    SELECT id, parent_id, project_id FROM todos WHERE id = $1 AND owner_id = $2
+                    UNION ALL
+                    SELECT todos.id, todos.parent_id, todos.project_id
+                    FROM todos
+                    JOIN ancestors ON todos.id = ancestors.parent_id
+                )
+                SELECT id, project_id FROM ancestors;
+            "#,
+        )
+        .bind(parent_id)
+        .bind(owner_id)
+        .fetch_all(conn)
+        .await?;
+
+        match ancestors
+            .iter()
+            .find(|(ancestor_id, _)| *ancestor_id == parent_id)
+        {
+            Some((_, project_id))
+                if !id.is_some_and(|id| {
+                    ancestors.iter().any(|(ancestor_id, _)| *ancestor_id == id)
+                }) =>
+            {
+                Ok(*project_id)
+            }
+            _ => Err(RepositoryError::InvalidParent(parent_id).into()),
+        }
+    }
+
+    /// Fails with `InvalidProject` unless `project_id` exists and is visible to `owner_id`.
+    async fn check_project(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        project_id: i32,
+    ) -> anyhow::Result<()> {
+        sqlx::query_scalar::<Self, i32>(
+            r#"
+                SELECT id FROM projects WHERE id = $1 AND ( owner_id = $2 OR id = $3 )
+            "#,
+        )
+        .bind(project_id)
+        .bind(owner_id)
+        .bind(DEFAULT_PROJECT_ID)
+        .fetch_optional(conn)
+        .await?
+        .ok_or(RepositoryError::InvalidProject(project_id))?;
+
+        Ok(())
+    }
+
+    /// `id` and every todo below it, with their parents, ordered by id.
+    async fn subtree(
+        conn: &mut Self::Connection,
+        id: i32,
+    ) -> anyhow::Result<Vec<(i32, Option<i32>)>> {
+        let subtree = sqlx::query_as::<Self, (i32, Option<i32>)>(
+            r#"
+                WITH RECURSIVE subtree AS (
+                    SELECT id, parent_id FROM todos WHERE id = $1
+                    UNION ALL
+                    SELECT todos.id, todos.parent_id
+                    FROM todos
+                    JOIN subtree ON todos.parent_id = subtree.id
+                )
+                SELECT id, parent_id FROM subtree ORDER BY id;
+            "#,
+        )
+        .bind(id)
+        .fetch_all(conn)
+        .await?;
+
+        Ok(subtree)
+    }
+
+    /// Recomputes the completion of `parent_id` and its ancestors that auto-complete,
+    /// stopping at the first one that does not change.
+    async fn roll_up(
+        conn: &mut Self::Connection,
+        mut parent_id: Option<i32>,
+    ) -> anyhow::Result<()> {
+        let sql = format!(
+            r#"
+                UPDATE todos SET completed = children.completed
+                FROM (
+                    SELECT {} AS completed FROM todos WHERE parent_id = $1
+                ) children
+                WHERE todos.id = $1
+                AND todos.auto_complete
+                AND children.completed IS NOT NULL
+                AND todos.completed <> children.completed
+                RETURNING todos.parent_id;
+            "#,
+            Self::bool_and("completed")
+        );
+        while let Some(id) = parent_id {
+            parent_id = sqlx::query_scalar::<Self, Option<i32>>(&sql)
+                .bind(id)
+                .fetch_optional(&mut *conn)
+                .await?
+                .flatten();
+        }
+        Ok(())
+    }
+
+    /// Attaches `labels` to `id` in the given order, returning them as attached.
+    async fn insert_todo_labels(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        id: i32,
+        labels: &[i32],
+    ) -> anyhow::Result<Vec<Label>> {
+        if let Some(label_id) = repeated_label(labels) {
+            return Err(RepositoryError::Duplicate(label_id).into());
+        }
+        // a label of someone else is as good as missing
+        let unknown = sqlx::query_scalar::<Self, i32>(&format!(
+            r#"
+                SELECT t.id FROM {}
+                WHERE NOT EXISTS ( SELECT 1 FROM labels WHERE labels.id = t.id AND labels.owner_id = $2 )
+                ORDER BY t.n;
+            "#,
+            Self::ids_table("$1", "t")
+        ))
+        .bind(Self::ids(labels))
+        .bind(owner_id)
+        .fetch_all(&mut *conn)
+        .await?;
+        if !unknown.is_empty() {
+            return Err(RepositoryError::UnknownLabels(unknown).into());
+        }
+
+        sqlx::query::<Self>(&format!(
+            r#"
+                INSERT INTO todo_labels ( todo_id, label_id )
+                SELECT $1, t.id FROM {}
+                ORDER BY t.n;
+            "#,
+            Self::ids_table("$2", "t")
+        ))
+        .bind(id)
+        .bind(Self::ids(labels))
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| {
+            if is_unique_violation(&e) {
+                // a concurrent update of the same todo got its labels in first
+                RepositoryError::Duplicate(id).into()
+            } else {
+                anyhow::Error::from(e)
+            }
+        })?;
+
+        let labels = sqlx::query_as::<Self, Label>(
+            r#"
+                SELECT labels.id, labels.name
+                FROM todo_labels tl
+                JOIN labels ON labels.id = tl.label_id
+                WHERE tl.todo_id = $1
+                ORDER BY tl.id;
+            "#,
+        )
+        .bind(id)
+        .fetch_all(conn)
+        .await?;
+
+        Ok(labels)
+    }
+
+    async fn delete_todo_labels(conn: &mut Self::Connection, id: i32) -> anyhow::Result<()> {
+        sqlx::query::<Self>(
+            r#"
+                DELETE FROM todo_labels WHERE todo_id = $1
+            "#,
+        )
+        .bind(id)
+        .execute(conn)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(())
+    }
+
+    /// Deletes the todos `ids` with their labels.
+    async fn delete_todos(conn: &mut Self::Connection, ids: &[i32]) -> anyhow::Result<()> {
+        sqlx::query::<Self>(&format!(
+            "DELETE FROM todo_labels WHERE {}",
+            Self::in_ids("todo_id", "$1")
+        ))
+        .bind(Self::ids(ids))
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query::<Self>(&format!(
+            "DELETE FROM todos WHERE {}",
+            Self::in_ids("id", "$1")
+        ))
+        .bind(Self::ids(ids))
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Moves the todos `ids` of `owner_id` into `project_id`, appended to its order and
+    /// keeping their own.
+    async fn append_to_project(
+        conn: &mut Self::Connection,
+        owner_id: i32,
+        project_id: i32,
+        ids: &[i32],
+    ) -> anyhow::Result<()> {
+        sqlx::query::<Self>(&format!(
+            r#"
+                UPDATE todos SET project_id = $1, position = tail.position + moved.n * $4
+                FROM (
+                    SELECT id, row_number() OVER ( ORDER BY position, id ) AS n
+                    FROM todos WHERE {}
+                ) moved, (
+                    SELECT COALESCE ( max(position), 0 ) AS position
+                    FROM todos WHERE owner_id = $3 AND project_id = $1
+                ) tail
+                WHERE todos.id = moved.id
+            "#,
+            Self::in_ids("id", "$2")
+        ))
+        .bind(project_id)
+        .bind(Self::ids(ids))
+        .bind(owner_id)
+        .bind(POSITION_GAP)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn stats(conn: &mut Self::Connection) -> anyhow::Result<TodoStats> {
+        let (open, completed) = sqlx::query_as::<Self, (i64, i64)>(
+            r#"
+                SELECT
+                    COUNT(*) FILTER ( WHERE NOT completed ),
+                    COUNT(*) FILTER ( WHERE completed )
+                FROM todos
+            "#,
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        let by_label = sqlx::query_as::<Self, (i32, i64)>(
+            "SELECT label_id, COUNT(*) FROM todo_labels GROUP BY label_id",
+        )
+        .fetch_all(conn)
+        .await?;
+
+        Ok(TodoStats {
+            open,
+            completed,
+            by_label: by_label.into_iter().collect(),
+        })
+    }
+}
+
+impl TodoQueries for Postgres {}
+
+impl TodoQueries for Sqlite {}
@@ -0,0 +1,333 @@
+//! The todo repository on SQLite, where `json_each` stands in for arrays
+//! and timestamps are RFC 3339 text in UTC, which sorts chronologically.
+//! Text search folds the case of ASCII letters only.
+
+use super::sql::TodoQueries;
+use super::*;
+use sqlx::{QueryBuilder, Sqlite, SqliteExecutor, SqlitePool};
+
+#[derive(Debug, Clone)]
+pub struct TodoRepositoryForSqlite {
+    pub pool: SqlitePool,
+}
+
+impl TodoRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        TodoRepositoryForSqlite { pool }
+    }
+}
+
+async fn find_todo<'e, E: SqliteExecutor<'e>>(
+    executor: E,
+    owner_id: i32,
+    id: i32,
+) -> anyhow::Result<TodoEntity> {
+    let items = sqlx::query_as::<_, TodoWithLabelFromRow>(
+        r#"
+            SELECT todos.*, labels.id as label_id, labels.name as label_name
+            FROM todos
+            LEFT OUTER JOIN todo_labels tl ON todos.id = tl.todo_id
+            LEFT OUTER JOIN labels ON labels.id = tl.label_id
+            WHERE todos.id = $1 AND todos.owner_id = $2
+            ORDER BY tl.id ASC;
+        "#,
+    )
+    .bind(id)
+    .bind(owner_id)
+    .fetch_all(executor)
+    .await?;
+
+    let todos = fold_entities(items);
+    let todo = todos.first().ok_or(RepositoryError::NotFound(id))?;
+    Ok(todo.clone())
+}
+
+#[async_trait]
+impl TodoRepository for TodoRepositoryForSqlite {
+    async fn create(&self, owner_id: i32, payload: CreateTodo) -> anyhow::Result<TodoEntity> {
+        unit_of_work(&self.pool, |conn| {
+            Sqlite::create_todo(conn, owner_id, payload)
+        })
+        .await
+    }
+
+    async fn find(&self, owner_id: i32, id: i32) -> anyhow::Result<TodoEntity> {
+        find_todo(&self.pool, owner_id, id).await
+    }
+
+    async fn list(&self, owner_id: i32, query: TodoListQuery) -> anyhow::Result<TodoPage> {
+        // page through todos alone first so that LIMIT counts todos rather than label rows
+        let mut builder = QueryBuilder::<Sqlite>::new(
+            "WITH page AS ( SELECT todos.* FROM todos WHERE todos.owner_id = ",
+        );
+        builder.push_bind(owner_id);
+        query.push_conditions(&mut builder);
+        query.push_order_by(&mut builder, "todos");
+        builder.push(" LIMIT ").push_bind(query.limit + 1);
+        builder.push(
+            r#" )
+                SELECT page.*, labels.id as label_id, labels.name as label_name
+                FROM page
+                LEFT OUTER JOIN todo_labels tl ON page.id = tl.todo_id
+                LEFT OUTER JOIN labels ON labels.id = tl.label_id"#,
+        );
+        query.push_order_by(&mut builder, "page");
+        builder.push(", tl.id ASC");
+
+        let items = builder
+            .build_query_as::<TodoWithLabelFromRow>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(TodoPage::new(fold_entities(items), &query))
+    }
+
+    async fn update(
+        &self,
+        owner_id: i32,
+        id: i32,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<TodoEntity> {
+        unit_of_work(&self.pool, |conn| {
+            Box::pin(async move {
+                let old_todo = find_todo(&mut *conn, owner_id, id).await?;
+                Sqlite::update_todo(conn, owner_id, old_todo, payload).await
+            })
+        })
+        .await
+    }
+
+    async fn delete(&self, owner_id: i32, id: i32, mode: DeleteMode) -> anyhow::Result<()> {
+        unit_of_work(&self.pool, |conn| {
+            Box::pin(async move {
+                let parent_id = sqlx::query_scalar::<_, Option<i32>>(
+                    r#"
+                        SELECT parent_id FROM todos WHERE id = $1 AND owner_id = $2
+                    "#,
+                )
+                .bind(id)
+                .bind(owner_id)
+                .fetch_optional(&mut *conn)
+                .await?
+                .ok_or(RepositoryError::NotFound(id))?;
+
+                let subtree = Sqlite::subtree(conn, id).await?;
+                let children: Vec<i32> = subtree
+                    .iter()
+                    .filter(|(_, parent_id)| *parent_id == Some(id))
+                    .map(|(child_id, _)| *child_id)
+                    .collect();
+                if !children.is_empty() && mode == DeleteMode::Restrict {
+                    return Err(RepositoryError::HasChildren { id, children }.into());
+                }
+                let ids: Vec<i32> = subtree.into_iter().map(|(id, _)| id).collect();
+                Sqlite::delete_todos(conn, &ids).await?;
+
+                Sqlite::roll_up(conn, parent_id).await
+            })
+        })
+        .await
+    }
+
+    async fn children(&self, owner_id: i32, id: i32) -> anyhow::Result<Vec<TodoEntity>> {
+        let mut conn = self.pool.acquire().await?;
+        find_todo(&mut *conn, owner_id, id).await?;
+        let items = sqlx::query_as::<_, TodoWithLabelFromRow>(
+            r#"
+                SELECT todos.*, labels.id as label_id, labels.name as label_name
+                FROM todos
+                LEFT OUTER JOIN todo_labels tl ON todos.id = tl.todo_id
+                LEFT OUTER JOIN labels ON labels.id = tl.label_id
+                WHERE todos.parent_id = $1
+                ORDER BY todos.id ASC, tl.id ASC;
+            "#,
+        )
+        .bind(id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        Ok(fold_entities(items))
+    }
+
+    async fn descendants(&self, owner_id: i32, ids: Vec<i32>) -> anyhow::Result<Vec<TodoEntity>> {
+        let items = sqlx::query_as::<_, TodoWithLabelFromRow>(
+            r#"
+                WITH RECURSIVE subtree AS (
+                    SELECT * FROM todos
+                    WHERE parent_id IN ( SELECT value FROM json_each ( $1 ) ) AND owner_id = $2
+                    UNION ALL
+                    SELECT todos.*
+                    FROM todos
+                    JOIN subtree ON todos.parent_id = subtree.id
+                )
+                SELECT subtree.*, labels.id as label_id, labels.name as label_name
+                FROM subtree
+                LEFT OUTER JOIN todo_labels tl ON subtree.id = tl.todo_id
+                LEFT OUTER JOIN labels ON labels.id = tl.label_id
+                ORDER BY subtree.id ASC, tl.id ASC;
+            "#,
+        )
+        .bind(Sqlite::ids(&ids))
+        .bind(owner_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(fold_entities(items))
+    }
+
+    async fn move_to_project(
+        &self,
+        owner_id: i32,
+        id: i32,
+        project_id: i32,
+    ) -> anyhow::Result<TodoEntity> {
+        unit_of_work(&self.pool, |conn| {
+            Box::pin(async move {
+                let old_todo = find_todo(&mut *conn, owner_id, id).await?;
+                if old_todo.project_id == project_id {
+                    return Ok(old_todo);
+                }
+                Sqlite::check_project(conn, owner_id, project_id).await?;
+                let ids: Vec<i32> = Sqlite::subtree(conn, id)
+                    .await?
+                    .into_iter()
+                    .map(|(id, _)| id)
+                    .collect();
+                Sqlite::append_to_project(conn, owner_id, project_id, &ids).await?;
+
+                if old_todo.parent_id.is_some() {
+                    sqlx::query(
+                        r#"
+                            UPDATE todos SET parent_id = NULL WHERE id = $1
+                        "#,
+                    )
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await?;
+                    Sqlite::roll_up(conn, old_todo.parent_id).await?;
+                }
+
+                find_todo(conn, owner_id, id).await
+            })
+        })
+        .await
+    }
+
+    async fn reorder(
+        &self,
+        owner_id: i32,
+        id: i32,
+        placement: Placement,
+    ) -> anyhow::Result<TodoEntity> {
+        unit_of_work(&self.pool, |conn| {
+            Box::pin(async move {
+                let todo = find_todo(&mut *conn, owner_id, id).await?;
+                if let Placement::Before(target) | Placement::After(target) = placement {
+                    if target == id {
+                        return Ok(todo);
+                    }
+                    Sqlite::check_target(conn, owner_id, todo.project_id, target).await?;
+                }
+                let position =
+                    Sqlite::position_at(conn, owner_id, todo.project_id, id, placement).await?;
+                Sqlite::set_position(conn, id, position, todo.labels).await
+            })
+        })
+        .await
+    }
+
+    async fn stats(&self) -> anyhow::Result<TodoStats> {
+        let mut conn = self.pool.acquire().await?;
+        Sqlite::stats(&mut conn).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::DatabaseConfig;
+    use crate::migrations::{self, test_utils::sqlite_pool};
+    use crate::repositories::file::test_utils::TempDir;
+    use crate::repositories::label::LabelRepositoryForSqlite;
+    use crate::repositories::project::{ProjectRepository, ProjectRepositoryForSqlite};
+    use crate::repositories::user::test_utils::sqlite_fixture_user;
+
+    #[tokio::test]
+    async fn contract_scenario() {
+        let pool = sqlite_pool().await;
+        let owner = sqlite_fixture_user(&pool, "owner").await;
+        let other_owner = sqlite_fixture_user(&pool, "other owner").await;
+        let project = ProjectRepositoryForSqlite::new(pool.clone())
+            .create(owner.id, "project".to_string())
+            .await
+            .unwrap();
+        contract::run(&contract::Fixture {
+            todos: TodoRepositoryForSqlite::new(pool.clone()),
+            labels: LabelRepositoryForSqlite::new(pool),
+            owner: owner.id,
+            other_owner: other_owner.id,
+            project: project.id,
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_writes_wait_for_each_other() {
+        let dir = TempDir::new("sqlite-concurrent");
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = DatabaseConfig {
+            url: Some(format!("sqlite://{}", dir.join("todos.db").display())),
+            max_connections: 8,
+            ..Default::default()
+        };
+        let pool = config
+            .pool_options::<Sqlite>()
+            .connect_with(config.sqlite_options().unwrap())
+            .await
+            .expect("fail open sqlite database");
+        migrations::up(&pool).await.unwrap();
+        let owner = sqlite_fixture_user(&pool, "owner").await;
+        let repository = TodoRepositoryForSqlite::new(pool);
+
+        // every create reads the tail of the project before it appends to it
+        let handles: Vec<_> = (0..16)
+            .map(|n| {
+                let repository = repository.clone();
+                tokio::spawn(async move {
+                    repository
+                        .create(owner.id, CreateTodo::new(format!("todo {}", n), vec![]))
+                        .await
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap().expect("[create] returned Err");
+        }
+        let page = repository
+            .list(owner.id, TodoListQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(16, page.items.len());
+    }
+
+    #[tokio::test]
+    async fn create_leaves_nothing_when_labels_are_unknown() {
+        let pool = sqlite_pool().await;
+        let owner = sqlite_fixture_user(&pool, "owner").await;
+        let repository = TodoRepositoryForSqlite::new(pool.clone());
+
+        // the todo row is written before the unknown label is rejected
+        let res = repository
+            .create(
+                owner.id,
+                CreateTodo::new("text".to_string(), vec![i32::MAX]),
+            )
+            .await;
+        assert!(res.is_err());
+        let count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM todos")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(0, count);
+    }
+}
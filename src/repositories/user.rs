@@ -0,0 +1,440 @@
+use crate::repositories::{Dialect, RepositoryError};
+use anyhow::Ok;
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+use validator::Validate;
+
+#[async_trait]
+pub trait UserRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    /// Fails with `Duplicate` when the username is taken.
+    async fn create(&self, username: String, password_hash: String) -> anyhow::Result<User>;
+    /// The user called `username` with its password hash.
+    async fn credentials(&self, username: String) -> anyhow::Result<Option<(User, String)>>;
+    async fn create_session(
+        &self,
+        user_id: i32,
+        token_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> anyhow::Result<()>;
+    /// The owner of the session, unless it expired before `now`.
+    async fn find_session(
+        &self,
+        token_hash: String,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Option<User>>;
+    async fn delete_session(&self, token_hash: String) -> anyhow::Result<()>;
+    async fn create_token(
+        &self,
+        user_id: i32,
+        token_hash: String,
+        payload: CreateApiToken,
+    ) -> anyhow::Result<ApiToken>;
+    async fn tokens(&self, user_id: i32) -> anyhow::Result<Vec<ApiToken>>;
+    /// Fails with `NotFound` unless the token belongs to the user.
+    async fn delete_token(&self, user_id: i32, id: i32) -> anyhow::Result<()>;
+    /// The owner and scopes of the token unless it expired before `now`,
+    /// recording `now` as its last use.
+    async fn use_token(
+        &self,
+        token_hash: String,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Option<(User, Vec<Scope>)>>;
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow)]
+pub struct User {
+    pub id: i32,
+    pub username: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateUser {
+    #[validate(length(min = 3, message = "Under text length"))]
+    #[validate(length(max = 50, message = "Over text length"))]
+    pub username: String,
+    #[validate(length(min = 8, message = "Under text length"))]
+    #[validate(length(max = 128, message = "Over text length"))]
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct Login {
+    pub username: String,
+    pub password: String,
+}
+
+/// What an API token may do. Sessions are not limited by scopes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "todos:read")]
+    TodosRead,
+    #[serde(rename = "todos:write")]
+    TodosWrite,
+    #[serde(rename = "labels:read")]
+    LabelsRead,
+    #[serde(rename = "labels:write")]
+    LabelsWrite,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 4] = [
+        Scope::TodosRead,
+        Scope::TodosWrite,
+        Scope::LabelsRead,
+        Scope::LabelsWrite,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::TodosRead => "todos:read",
+            Scope::TodosWrite => "todos:write",
+            Scope::LabelsRead => "labels:read",
+            Scope::LabelsWrite => "labels:write",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Option<Self> {
+        Scope::ALL.into_iter().find(|s| s.as_str() == scope)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ApiToken {
+    pub id: i32,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Validate)]
+pub struct CreateApiToken {
+    #[validate(length(min = 1, message = "can not be empty"))]
+    #[validate(length(max = 100, message = "Over text length"))]
+    pub name: String,
+    #[validate(length(min = 1, message = "can not be empty"))]
+    pub scopes: Vec<Scope>,
+    /// Never expires when missing.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, FromRow)]
+struct UserWithPasswordFromRow {
+    id: i32,
+    username: String,
+    password_hash: String,
+}
+
+/// Scopes no longer known to this binary are dropped.
+fn parse_scopes(scopes: &[String]) -> Vec<Scope> {
+    scopes
+        .iter()
+        .filter_map(|scope| Scope::parse(scope))
+        .collect()
+}
+
+fn format_scopes(scopes: &[Scope]) -> Vec<String> {
+    scopes
+        .iter()
+        .map(|scope| scope.as_str().to_string())
+        .collect()
+}
+
+mod pg;
+mod sql;
+mod sqlite;
+
+pub use pg::UserRepositoryForDb;
+pub use sqlite::UserRepositoryForSqlite;
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use crate::repositories::file::{FileStore, UserRepositoryForFile};
+    use axum::async_trait;
+    use std::{
+        collections::HashMap,
+        sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    };
+
+    /// The user a database test works as, created on first use.
+    #[cfg(feature = "database-test")]
+    pub async fn fixture_user(pool: &sqlx::PgPool, username: &str) -> User {
+        sqlx::query_as::<_, User>(
+            r#"
+                INSERT INTO users ( username, password_hash )
+                VALUES ( $1, '' )
+                ON CONFLICT ( username ) DO UPDATE SET username = EXCLUDED.username
+                RETURNING id, username
+            "#,
+        )
+        .bind(username)
+        .fetch_one(pool)
+        .await
+        .expect("Failed to insert user data.")
+    }
+
+    /// The user a SQLite test works as.
+    pub async fn sqlite_fixture_user(pool: &SqlitePool, username: &str) -> User {
+        UserRepositoryForSqlite::new(pool.clone())
+            .create(username.to_string(), String::new())
+            .await
+            .expect("Failed to insert user data.")
+    }
+
+    pub async fn file_fixture_user(store: &FileStore, username: &str) -> User {
+        UserRepositoryForFile::new(store.clone())
+            .create(username.to_string(), String::new())
+            .await
+            .expect("Failed to insert user data.")
+    }
+
+    #[derive(Debug, Default)]
+    struct UserDatas {
+        users: HashMap<i32, (User, String)>,
+        sessions: HashMap<String, (i32, DateTime<Utc>)>,
+        /// id => (user id, token hash, token)
+        tokens: HashMap<i32, (i32, String, ApiToken)>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct UserRepositoryForMemory {
+        store: Arc<RwLock<UserDatas>>,
+    }
+
+    impl UserRepositoryForMemory {
+        pub fn new() -> Self {
+            UserRepositoryForMemory {
+                store: Arc::default(),
+            }
+        }
+
+        /// Adds a user that is signed in with the session `token_hash` for good.
+        pub fn with_session(self, user: User, token_hash: String) -> Self {
+            {
+                let mut store = self.write_store_ref();
+                store
+                    .sessions
+                    .insert(token_hash, (user.id, DateTime::<Utc>::MAX_UTC));
+                store.users.insert(user.id, (user, String::new()));
+            }
+            self
+        }
+
+        fn write_store_ref(&self) -> RwLockWriteGuard<'_, UserDatas> {
+            self.store.write().unwrap()
+        }
+
+        fn read_store_ref(&self) -> RwLockReadGuard<'_, UserDatas> {
+            self.store.read().unwrap()
+        }
+    }
+
+    #[async_trait]
+    impl UserRepository for UserRepositoryForMemory {
+        async fn create(&self, username: String, password_hash: String) -> anyhow::Result<User> {
+            let mut store = self.write_store_ref();
+            if let Some((user, _)) = store
+                .users
+                .values()
+                .find(|(user, _)| user.username == username)
+            {
+                return Err(RepositoryError::Duplicate(user.id).into());
+            }
+            let id = store.users.keys().max().map_or(1, |id| id + 1);
+            let user = User { id, username };
+            store.users.insert(id, (user.clone(), password_hash));
+            Ok(user)
+        }
+
+        async fn credentials(&self, username: String) -> anyhow::Result<Option<(User, String)>> {
+            let store = self.read_store_ref();
+            Ok(store
+                .users
+                .values()
+                .find(|(user, _)| user.username == username)
+                .cloned())
+        }
+
+        async fn create_session(
+            &self,
+            user_id: i32,
+            token_hash: String,
+            expires_at: DateTime<Utc>,
+        ) -> anyhow::Result<()> {
+            let mut store = self.write_store_ref();
+            store.sessions.insert(token_hash, (user_id, expires_at));
+            Ok(())
+        }
+
+        async fn find_session(
+            &self,
+            token_hash: String,
+            now: DateTime<Utc>,
+        ) -> anyhow::Result<Option<User>> {
+            let store = self.read_store_ref();
+            Ok(store
+                .sessions
+                .get(&token_hash)
+                .filter(|(_, expires_at)| *expires_at > now)
+                .and_then(|(user_id, _)| store.users.get(user_id))
+                .map(|(user, _)| user.clone()))
+        }
+
+        async fn delete_session(&self, token_hash: String) -> anyhow::Result<()> {
+            let mut store = self.write_store_ref();
+            store.sessions.remove(&token_hash);
+            Ok(())
+        }
+
+        async fn create_token(
+            &self,
+            user_id: i32,
+            token_hash: String,
+            payload: CreateApiToken,
+        ) -> anyhow::Result<ApiToken> {
+            let mut store = self.write_store_ref();
+            let id = store.tokens.keys().max().map_or(1, |id| id + 1);
+            let token = ApiToken {
+                id,
+                name: payload.name,
+                scopes: payload.scopes,
+                created_at: Utc::now(),
+                last_used_at: None,
+                expires_at: payload.expires_at,
+            };
+            store
+                .tokens
+                .insert(id, (user_id, token_hash, token.clone()));
+            Ok(token)
+        }
+
+        async fn tokens(&self, user_id: i32) -> anyhow::Result<Vec<ApiToken>> {
+            let store = self.read_store_ref();
+            let mut tokens = store
+                .tokens
+                .values()
+                .filter(|(owner_id, _, _)| *owner_id == user_id)
+                .map(|(_, _, token)| token.clone())
+                .collect::<Vec<_>>();
+            tokens.sort_by_key(|token| token.id);
+            Ok(tokens)
+        }
+
+        async fn delete_token(&self, user_id: i32, id: i32) -> anyhow::Result<()> {
+            let mut store = self.write_store_ref();
+            match store.tokens.get(&id) {
+                Some((owner_id, _, _)) if *owner_id == user_id => {
+                    store.tokens.remove(&id);
+                    Ok(())
+                }
+                _ => Err(RepositoryError::NotFound(id).into()),
+            }
+        }
+
+        async fn use_token(
+            &self,
+            token_hash: String,
+            now: DateTime<Utc>,
+        ) -> anyhow::Result<Option<(User, Vec<Scope>)>> {
+            let mut store = self.write_store_ref();
+            let Some((user_id, _, token)) = store
+                .tokens
+                .values_mut()
+                .find(|(_, hash, _)| *hash == token_hash)
+                .filter(|(_, _, token)| token.expires_at.is_none_or(|at| at > now))
+            else {
+                return Ok(None);
+            };
+            token.last_used_at = Some(now);
+            let (user_id, scopes) = (*user_id, token.scopes.clone());
+            Ok(store
+                .users
+                .get(&user_id)
+                .map(|(user, _)| (user.clone(), scopes)))
+        }
+    }
+
+    mod test {
+        use super::*;
+        use chrono::Duration;
+
+        #[tokio::test]
+        async fn user_session_scenario() {
+            let repository = UserRepositoryForMemory::new();
+            let user = repository
+                .create("alice".to_string(), "hash".to_string())
+                .await
+                .expect("failed create user");
+            assert_eq!(1, user.id);
+            let res = repository
+                .create("alice".to_string(), "other".to_string())
+                .await
+                .expect_err("duplicate username returned Ok");
+            assert!(matches!(
+                res.downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::Duplicate(1))
+            ));
+
+            let credentials = repository.credentials("alice".to_string()).await.unwrap();
+            assert_eq!(Some((user.clone(), "hash".to_string())), credentials);
+
+            let now = Utc::now();
+            repository
+                .create_session(user.id, "token".to_string(), now + Duration::hours(1))
+                .await
+                .unwrap();
+            let found = repository.find_session("token".to_string(), now).await;
+            assert_eq!(Some(user), found.unwrap());
+            let expired = repository
+                .find_session("token".to_string(), now + Duration::hours(2))
+                .await;
+            assert_eq!(None, expired.unwrap());
+
+            repository
+                .delete_session("token".to_string())
+                .await
+                .unwrap();
+            let found = repository.find_session("token".to_string(), now).await;
+            assert_eq!(None, found.unwrap());
+        }
+
+        #[tokio::test]
+        async fn user_token_scenario() {
+            let repository = UserRepositoryForMemory::new();
+            let user = repository
+                .create("alice".to_string(), "hash".to_string())
+                .await
+                .unwrap();
+            let now = Utc::now();
+            let payload = CreateApiToken {
+                name: "ci".to_string(),
+                scopes: vec![Scope::TodosRead],
+                expires_at: Some(now + Duration::hours(1)),
+            };
+            let token = repository
+                .create_token(user.id, "hash".to_string(), payload)
+                .await
+                .unwrap();
+            assert_eq!(None, token.last_used_at);
+
+            let used = repository.use_token("hash".to_string(), now).await.unwrap();
+            assert_eq!(Some((user.clone(), vec![Scope::TodosRead])), used);
+            let tokens = repository.tokens(user.id).await.unwrap();
+            assert_eq!(Some(now), tokens[0].last_used_at);
+            let expired = repository
+                .use_token("hash".to_string(), now + Duration::hours(2))
+                .await
+                .unwrap();
+            assert_eq!(None, expired);
+
+            let res = repository.delete_token(user.id + 1, token.id).await;
+            assert!(res.is_err());
+            repository.delete_token(user.id, token.id).await.unwrap();
+            assert!(repository.tokens(user.id).await.unwrap().is_empty());
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! The user repository on Postgres.
+
+use super::sql::UserQueries;
+use super::*;
+use sqlx::{PgPool, Postgres};
+
+#[derive(Debug, FromRow)]
+struct ApiTokenFromRow {
+    id: i32,
+    name: String,
+    scopes: Vec<String>,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiTokenFromRow> for ApiToken {
+    fn from(row: ApiTokenFromRow) -> Self {
+        ApiToken {
+            id: row.id,
+            name: row.name,
+            scopes: parse_scopes(&row.scopes),
+            created_at: row.created_at,
+            last_used_at: row.last_used_at,
+            expires_at: row.expires_at,
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct TokenUserFromRow {
+    id: i32,
+    username: String,
+    scopes: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct UserRepositoryForDb {
+    pool: PgPool,
+}
+
+impl UserRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl UserRepository for UserRepositoryForDb {
+    async fn create(&self, username: String, password_hash: String) -> anyhow::Result<User> {
+        let mut conn = self.pool.acquire().await?;
+        Postgres::create_user(&mut conn, username, password_hash).await
+    }
+
+    async fn credentials(&self, username: String) -> anyhow::Result<Option<(User, String)>> {
+        let mut conn = self.pool.acquire().await?;
+        Postgres::credentials(&mut conn, username).await
+    }
+
+    async fn create_session(
+        &self,
+        user_id: i32,
+        token_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        Postgres::create_session(&mut conn, user_id, token_hash, expires_at).await
+    }
+
+    async fn find_session(
+        &self,
+        token_hash: String,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Option<User>> {
+        let mut conn = self.pool.acquire().await?;
+        Postgres::find_session(&mut conn, token_hash, now).await
+    }
+
+    async fn delete_session(&self, token_hash: String) -> anyhow::Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        Postgres::delete_session(&mut conn, token_hash).await
+    }
+
+    async fn create_token(
+        &self,
+        user_id: i32,
+        token_hash: String,
+        payload: CreateApiToken,
+    ) -> anyhow::Result<ApiToken> {
+        let row = sqlx::query_as::<_, ApiTokenFromRow>(
+            r#"
+                INSERT INTO api_tokens ( user_id, name, token_hash, scopes, expires_at )
+                VALUES ( $1, $2, $3, $4, $5 )
+                RETURNING id, name, scopes, created_at, last_used_at, expires_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(payload.name)
+        .bind(token_hash)
+        .bind(format_scopes(&payload.scopes))
+        .bind(payload.expires_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.into())
+    }
+
+    async fn tokens(&self, user_id: i32) -> anyhow::Result<Vec<ApiToken>> {
+        let rows = sqlx::query_as::<_, ApiTokenFromRow>(
+            r#"
+                SELECT id, name, scopes, created_at, last_used_at, expires_at
+                FROM api_tokens
+                WHERE user_id = $1
+                ORDER BY id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(ApiToken::from).collect())
+    }
+
+    async fn delete_token(&self, user_id: i32, id: i32) -> anyhow::Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        Postgres::delete_token(&mut conn, user_id, id).await
+    }
+
+    async fn use_token(
+        &self,
+        token_hash: String,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Option<(User, Vec<Scope>)>> {
+        let row = sqlx::query_as::<_, TokenUserFromRow>(
+            r#"
+                WITH used AS (
+                    UPDATE api_tokens SET last_used_at = $2
+                    WHERE token_hash = $1 AND ( expires_at IS NULL OR expires_at > $2 )
+                    RETURNING user_id, scopes
+                )
+                SELECT users.id, users.username, used.scopes
+                FROM used
+                JOIN users ON users.id = used.user_id
+            "#,
+        )
+        .bind(token_hash)
+        .bind(now)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| {
+            let scopes = parse_scopes(&row.scopes);
+            (
+                User {
+                    id: row.id,
+                    username: row.username,
+                },
+                scopes,
+            )
+        }))
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "database-test")]
+mod test {
+    use super::*;
+    use chrono::Duration;
+    use dotenv::dotenv;
+    use sqlx::PgPool;
+    use std::env;
+
+    #[tokio::test]
+    async fn session_scenario() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        let repository = UserRepositoryForDb::new(pool);
+        let user = test_utils::fixture_user(&repository.pool, "[session_scenario] user").await;
+
+        // create
+        let res = repository
+            .create(user.username.clone(), "hash".to_string())
+            .await
+            .expect_err("[create] duplicate username returned Ok");
+        assert!(matches!(
+            res.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Duplicate(id)) if *id == user.id
+        ));
+
+        // credentials
+        let (found, _) = repository
+            .credentials(user.username.clone())
+            .await
+            .expect("[credentials] returned Err")
+            .expect("[credentials] returned None");
+        assert_eq!(user, found);
+        let missing = repository
+            .credentials("[session_scenario] nobody".to_string())
+            .await
+            .expect("[credentials] returned Err");
+        assert_eq!(None, missing);
+
+        // sessions
+        let now = Utc::now();
+        let token_hash = format!("[session_scenario] {}", now.timestamp_nanos_opt().unwrap());
+        repository
+            .create_session(user.id, token_hash.clone(), now + Duration::hours(1))
+            .await
+            .expect("[create_session] returned Err");
+        let found = repository
+            .find_session(token_hash.clone(), now)
+            .await
+            .expect("[find_session] returned Err");
+        assert_eq!(Some(user), found);
+        let expired = repository
+            .find_session(token_hash.clone(), now + Duration::hours(2))
+            .await
+            .expect("[find_session] returned Err");
+        assert_eq!(None, expired);
+
+        repository
+            .delete_session(token_hash.clone())
+            .await
+            .expect("[delete_session] returned Err");
+        let found = repository
+            .find_session(token_hash, now)
+            .await
+            .expect("[find_session] returned Err");
+        assert_eq!(None, found);
+    }
+
+    #[tokio::test]
+    async fn token_scenario() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        let repository = UserRepositoryForDb::new(pool);
+        let user = test_utils::fixture_user(&repository.pool, "[token_scenario] user").await;
+        let other = test_utils::fixture_user(&repository.pool, "[token_scenario] other").await;
+
+        // create
+        let now = Utc::now();
+        let token_hash = format!("[token_scenario] {}", now.timestamp_nanos_opt().unwrap());
+        let payload = CreateApiToken {
+            name: "[token_scenario] ci".to_string(),
+            scopes: vec![Scope::TodosRead, Scope::LabelsWrite],
+            expires_at: Some(now + Duration::hours(1)),
+        };
+        let token = repository
+            .create_token(user.id, token_hash.clone(), payload)
+            .await
+            .expect("[create_token] returned Err");
+        assert_eq!(vec![Scope::TodosRead, Scope::LabelsWrite], token.scopes);
+        assert_eq!(None, token.last_used_at);
+
+        // use
+        let (found, scopes) = repository
+            .use_token(token_hash.clone(), now)
+            .await
+            .expect("[use_token] returned Err")
+            .expect("[use_token] returned None");
+        assert_eq!(user, found);
+        assert_eq!(token.scopes, scopes);
+        let tokens = repository
+            .tokens(user.id)
+            .await
+            .expect("[tokens] returned Err");
+        let used = tokens.iter().find(|t| t.id == token.id).unwrap();
+        assert!(used.last_used_at.is_some());
+        let expired = repository
+            .use_token(token_hash.clone(), now + Duration::hours(2))
+            .await
+            .expect("[use_token] returned Err");
+        assert_eq!(None, expired);
+
+        // delete
+        let res = repository
+            .delete_token(other.id, token.id)
+            .await
+            .expect_err("[delete_token] other user returned Ok");
+        assert!(matches!(
+            res.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(id)) if *id == token.id
+        ));
+        repository
+            .delete_token(user.id, token.id)
+            .await
+            .expect("[delete_token] returned Err");
+        let found = repository
+            .use_token(token_hash, now)
+            .await
+            .expect("[use_token] returned Err");
+        assert_eq!(None, found);
+    }
+}
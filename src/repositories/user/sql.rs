@@ -0,0 +1,159 @@
+//! The user queries of the Postgres and SQLite repositories, written once over [`Dialect`].
+//! API tokens keep their scopes in a column each database types its own way, so the queries
+//! reading them stay with the backends.
+
+use super::*;
+use sqlx::{
+    database::HasArguments, ColumnIndex, Decode, Encode, Executor, IntoArguments, Postgres, Sqlite,
+    Type,
+};
+
+#[async_trait]
+pub(super) trait UserQueries: Dialect
+where
+    for<'c> &'c mut Self::Connection: Executor<'c, Database = Self>,
+    for<'q> <Self as HasArguments<'q>>::Arguments: IntoArguments<'q, Self>,
+    for<'q> i32: Encode<'q, Self> + Decode<'q, Self> + Type<Self>,
+    for<'q> String: Encode<'q, Self> + Type<Self>,
+    for<'q> DateTime<Utc>: Encode<'q, Self> + Type<Self>,
+    for<'r> User: FromRow<'r, Self::Row>,
+    for<'r> UserWithPasswordFromRow: FromRow<'r, Self::Row>,
+    usize: ColumnIndex<Self::Row>,
+{
+    async fn create_user(
+        conn: &mut Self::Connection,
+        username: String,
+        password_hash: String,
+    ) -> anyhow::Result<User> {
+        let user = sqlx::query_as::<Self, User>(
+            r#"
+                INSERT INTO users ( username, password_hash )
+                VALUES ( $1, $2 )
+                ON CONFLICT ( username ) DO NOTHING
+                RETURNING id, username
+            "#,
+        )
+        .bind(username.clone())
+        .bind(password_hash)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        match user {
+            Some(user) => Ok(user),
+            None => {
+                let id = sqlx::query_scalar::<Self, i32>(
+                    r#"
+                        SELECT id FROM users WHERE username = $1
+                    "#,
+                )
+                .bind(username)
+                .fetch_one(conn)
+                .await?;
+                Err(RepositoryError::Duplicate(id).into())
+            }
+        }
+    }
+
+    async fn credentials(
+        conn: &mut Self::Connection,
+        username: String,
+    ) -> anyhow::Result<Option<(User, String)>> {
+        let row = sqlx::query_as::<Self, UserWithPasswordFromRow>(
+            r#"
+                SELECT id, username, password_hash FROM users WHERE username = $1
+            "#,
+        )
+        .bind(username)
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(row.map(|row| {
+            (
+                User {
+                    id: row.id,
+                    username: row.username,
+                },
+                row.password_hash,
+            )
+        }))
+    }
+
+    async fn create_session(
+        conn: &mut Self::Connection,
+        user_id: i32,
+        token_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        sqlx::query::<Self>(
+            r#"
+                INSERT INTO sessions ( user_id, token_hash, expires_at )
+                VALUES ( $1, $2, $3 )
+            "#,
+        )
+        .bind(user_id)
+        .bind(token_hash)
+        .bind(expires_at)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_session(
+        conn: &mut Self::Connection,
+        token_hash: String,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Option<User>> {
+        let user = sqlx::query_as::<Self, User>(
+            r#"
+                SELECT users.id, users.username
+                FROM sessions
+                JOIN users ON users.id = sessions.user_id
+                WHERE sessions.token_hash = $1 AND sessions.expires_at > $2
+            "#,
+        )
+        .bind(token_hash)
+        .bind(now)
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn delete_session(conn: &mut Self::Connection, token_hash: String) -> anyhow::Result<()> {
+        sqlx::query::<Self>(
+            r#"
+                DELETE FROM sessions WHERE token_hash = $1
+            "#,
+        )
+        .bind(token_hash)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_token(
+        conn: &mut Self::Connection,
+        user_id: i32,
+        id: i32,
+    ) -> anyhow::Result<()> {
+        sqlx::query_scalar::<Self, i32>(
+            r#"
+                DELETE FROM api_tokens WHERE id = $1 AND user_id = $2
+                RETURNING id
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(conn)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(())
+    }
+}
+
+impl UserQueries for Postgres {}
+
+impl UserQueries for Sqlite {}
@@ -0,0 +1,251 @@
+//! The user repository on SQLite, which keeps the scopes of a token comma separated.
+
+use super::sql::UserQueries;
+use super::*;
+use sqlx::Sqlite;
+
+#[derive(Debug, FromRow)]
+struct SqliteApiTokenFromRow {
+    id: i32,
+    name: String,
+    /// comma separated
+    scopes: String,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+fn split_scopes(scopes: &str) -> Vec<String> {
+    scopes.split(',').map(str::to_string).collect()
+}
+
+impl From<SqliteApiTokenFromRow> for ApiToken {
+    fn from(row: SqliteApiTokenFromRow) -> Self {
+        ApiToken {
+            id: row.id,
+            name: row.name,
+            scopes: parse_scopes(&split_scopes(&row.scopes)),
+            created_at: row.created_at,
+            last_used_at: row.last_used_at,
+            expires_at: row.expires_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UserRepositoryForSqlite {
+    pool: SqlitePool,
+}
+
+impl UserRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl UserRepository for UserRepositoryForSqlite {
+    async fn create(&self, username: String, password_hash: String) -> anyhow::Result<User> {
+        let mut conn = self.pool.acquire().await?;
+        Sqlite::create_user(&mut conn, username, password_hash).await
+    }
+
+    async fn credentials(&self, username: String) -> anyhow::Result<Option<(User, String)>> {
+        let mut conn = self.pool.acquire().await?;
+        Sqlite::credentials(&mut conn, username).await
+    }
+
+    async fn create_session(
+        &self,
+        user_id: i32,
+        token_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        Sqlite::create_session(&mut conn, user_id, token_hash, expires_at).await
+    }
+
+    async fn find_session(
+        &self,
+        token_hash: String,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Option<User>> {
+        let mut conn = self.pool.acquire().await?;
+        Sqlite::find_session(&mut conn, token_hash, now).await
+    }
+
+    async fn delete_session(&self, token_hash: String) -> anyhow::Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        Sqlite::delete_session(&mut conn, token_hash).await
+    }
+
+    async fn create_token(
+        &self,
+        user_id: i32,
+        token_hash: String,
+        payload: CreateApiToken,
+    ) -> anyhow::Result<ApiToken> {
+        let row = sqlx::query_as::<_, SqliteApiTokenFromRow>(
+            r#"
+                INSERT INTO api_tokens ( user_id, name, token_hash, scopes, expires_at )
+                VALUES ( $1, $2, $3, $4, $5 )
+                RETURNING id, name, scopes, created_at, last_used_at, expires_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(payload.name)
+        .bind(token_hash)
+        .bind(format_scopes(&payload.scopes).join(","))
+        .bind(payload.expires_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.into())
+    }
+
+    async fn tokens(&self, user_id: i32) -> anyhow::Result<Vec<ApiToken>> {
+        let rows = sqlx::query_as::<_, SqliteApiTokenFromRow>(
+            r#"
+                SELECT id, name, scopes, created_at, last_used_at, expires_at
+                FROM api_tokens
+                WHERE user_id = $1
+                ORDER BY id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(ApiToken::from).collect())
+    }
+
+    async fn delete_token(&self, user_id: i32, id: i32) -> anyhow::Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        Sqlite::delete_token(&mut conn, user_id, id).await
+    }
+
+    async fn use_token(
+        &self,
+        token_hash: String,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Option<(User, Vec<Scope>)>> {
+        // SQLite has no data modifying CTEs, the owner is looked up in a second statement
+        let used = sqlx::query_as::<_, (i32, String)>(
+            r#"
+                UPDATE api_tokens SET last_used_at = $2
+                WHERE token_hash = $1 AND ( expires_at IS NULL OR expires_at > $2 )
+                RETURNING user_id, scopes
+            "#,
+        )
+        .bind(token_hash)
+        .bind(now)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some((user_id, scopes)) = used else {
+            return Ok(None);
+        };
+        let user = sqlx::query_as::<_, User>(
+            r#"
+                SELECT id, username FROM users WHERE id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user.map(|user| (user, parse_scopes(&split_scopes(&scopes)))))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::migrations::test_utils::sqlite_pool;
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn session_and_token_scenario() {
+        let repository = UserRepositoryForSqlite::new(sqlite_pool().await);
+        let user = repository
+            .create("alice".to_string(), "hash".to_string())
+            .await
+            .expect("[create] returned Err");
+        let res = repository
+            .create("alice".to_string(), "other".to_string())
+            .await
+            .expect_err("[create] duplicate username returned Ok");
+        assert!(matches!(
+            res.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Duplicate(id)) if *id == user.id
+        ));
+        let credentials = repository
+            .credentials("alice".to_string())
+            .await
+            .expect("[credentials] returned Err");
+        assert_eq!(Some((user.clone(), "hash".to_string())), credentials);
+
+        // sessions
+        let now = Utc::now();
+        repository
+            .create_session(user.id, "session".to_string(), now + Duration::hours(1))
+            .await
+            .expect("[create_session] returned Err");
+        let found = repository
+            .find_session("session".to_string(), now)
+            .await
+            .expect("[find_session] returned Err");
+        assert_eq!(Some(user.clone()), found);
+        let expired = repository
+            .find_session("session".to_string(), now + Duration::hours(2))
+            .await
+            .expect("[find_session] returned Err");
+        assert_eq!(None, expired);
+        repository
+            .delete_session("session".to_string())
+            .await
+            .expect("[delete_session] returned Err");
+        let found = repository
+            .find_session("session".to_string(), now)
+            .await
+            .expect("[find_session] returned Err");
+        assert_eq!(None, found);
+
+        // tokens
+        let payload = CreateApiToken {
+            name: "ci".to_string(),
+            scopes: vec![Scope::TodosRead, Scope::LabelsWrite],
+            expires_at: Some(now + Duration::hours(1)),
+        };
+        let token = repository
+            .create_token(user.id, "token".to_string(), payload)
+            .await
+            .expect("[create_token] returned Err");
+        assert_eq!(vec![Scope::TodosRead, Scope::LabelsWrite], token.scopes);
+        let used = repository
+            .use_token("token".to_string(), now)
+            .await
+            .expect("[use_token] returned Err");
+        assert_eq!(
+            Some((user.clone(), vec![Scope::TodosRead, Scope::LabelsWrite])),
+            used
+        );
+        let tokens = repository
+            .tokens(user.id)
+            .await
+            .expect("[tokens] returned Err");
+        assert_eq!(Some(now), tokens[0].last_used_at);
+        let expired = repository
+            .use_token("token".to_string(), now + Duration::hours(2))
+            .await
+            .expect("[use_token] returned Err");
+        assert_eq!(None, expired);
+
+        let res = repository.delete_token(user.id + 1, token.id).await;
+        assert!(res.is_err());
+        repository
+            .delete_token(user.id, token.id)
+            .await
+            .expect("[delete_token] returned Err");
+        assert!(repository.tokens(user.id).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc,
+};
+
+/// Counts the requests being served so shutdown knows what it is waiting for.
+#[derive(Debug, Clone, Default)]
+pub struct RequestTracker {
+    in_flight: Arc<AtomicUsize>,
+    completed: Arc<AtomicU64>,
+}
+
+impl RequestTracker {
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    pub fn completed(&self) -> u64 {
+        self.completed.load(Ordering::SeqCst)
+    }
+}
+
+/// Leaves the in-flight count even when the request future is dropped midway.
+struct InFlightGuard(RequestTracker);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub async fn track_requests(
+    State(tracker): State<RequestTracker>,
+    req: Request,
+    next: Next,
+) -> Response {
+    tracker.in_flight.fetch_add(1, Ordering::SeqCst);
+    let _guard = InFlightGuard(tracker.clone());
+    let res = next.run(req).await;
+    tracker.completed.fetch_add(1, Ordering::SeqCst);
+    res
+}
+
+/// Resolves on SIGINT or SIGTERM with the name of the signal.
+pub async fn signal() -> &'static str {
+    let interrupt = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("fail install SIGINT handler");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("fail install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => "SIGINT",
+        _ = terminate => "SIGTERM",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn counts_in_flight_requests() {
+        let tracker = RequestTracker::default();
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel::<()>();
+        let started_tx = Arc::new(std::sync::Mutex::new(Some(started_tx)));
+        let app = Router::new()
+            .route(
+                "/",
+                get(move || async move {
+                    if let Some(tx) = started_tx.lock().unwrap().take() {
+                        tx.send(()).unwrap();
+                    }
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }),
+            )
+            .layer(middleware::from_fn_with_state(
+                tracker.clone(),
+                track_requests,
+            ));
+
+        let req = Request::builder().uri("/").body(Body::empty()).unwrap();
+        let handle = tokio::spawn(app.oneshot(req));
+        started_rx.await.unwrap();
+        assert_eq!(1, tracker.in_flight());
+        assert_eq!(0, tracker.completed());
+
+        handle.await.unwrap().unwrap();
+        assert_eq!(0, tracker.in_flight());
+        assert_eq!(1, tracker.completed());
+    }
+}
@@ -0,0 +1,107 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use rand::RngCore;
+use std::time::Instant;
+use tracing::{field::Empty, Instrument};
+
+pub static REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest `X-Request-Id` taken over from the client.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Id of the request being served, also available to handlers as an extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    /// The id sent by the client when it is sane, a fresh one otherwise.
+    fn of(req: &Request) -> Self {
+        let given = req
+            .headers()
+            .get(&REQUEST_ID)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| {
+                (1..=MAX_REQUEST_ID_LEN).contains(&id.len())
+                    && id
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "-_.:".contains(c))
+            });
+        match given {
+            Some(id) => Self(id.to_string()),
+            None => Self::generate(),
+        }
+    }
+
+    fn generate() -> Self {
+        let mut bytes = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+}
+
+/// Middleware that serves every request inside a `request` span carrying its id,
+/// so whatever the handler and repositories log is tied to the request.
+pub async fn trace_requests(mut req: Request, next: Next) -> Response {
+    let request_id = RequestId::of(&req);
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |path| path.as_str())
+        .to_string();
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id.0,
+        method = %req.method(),
+        route = %route,
+        status = Empty,
+        latency_ms = Empty,
+    );
+    req.extensions_mut().insert(request_id.clone());
+
+    let started = Instant::now();
+    let mut res = next.run(req).instrument(span.clone()).await;
+    let status = res.status();
+    span.record("status", status.as_u16());
+    span.record("latency_ms", started.elapsed().as_secs_f64() * 1000.0);
+    span.in_scope(|| {
+        if status.is_server_error() {
+            tracing::warn!("request failed");
+        } else {
+            tracing::info!("request finished");
+        }
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&request_id.0) {
+        res.headers_mut().insert(REQUEST_ID.clone(), value);
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::body::Body;
+
+    #[test]
+    fn keeps_sane_ids_only() {
+        let req = |id: &str| {
+            Request::builder()
+                .header(&REQUEST_ID, id)
+                .body(Body::empty())
+                .unwrap()
+        };
+        assert_eq!(
+            RequestId("abc-123".to_string()),
+            RequestId::of(&req("abc-123"))
+        );
+        assert_ne!(RequestId("a b".to_string()), RequestId::of(&req("a b")));
+        let long = "a".repeat(MAX_REQUEST_ID_LEN + 1);
+        assert_eq!(32, RequestId::of(&req(&long)).0.len());
+        let missing = Request::builder().body(Body::empty()).unwrap();
+        assert_ne!(RequestId::of(&missing), RequestId::of(&missing));
+    }
+}